@@ -0,0 +1,155 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpMessage, ResponseError};
+use anyhow::{anyhow, Result};
+use futures_util::future::LocalBoxFuture;
+
+use super::error::{unauthenticated, ApiError};
+use super::server_connection_impl::ServerConnection;
+use super::settings::Settings;
+use super::table_models::User;
+use super::tokens;
+use super::versioning::unversioned;
+
+// Everything else needs a session token, or an email and password in the headers
+const PUBLIC_ROUTES: &[(Method, &str)] = &[
+    (Method::GET, "/"),
+    (Method::GET, "/openapi.json"),
+    (Method::GET, "/docs"),
+    // Probes don't sign in; `/metrics` takes an admin session or `server.metrics_token`
+    (Method::GET, "/healthz"),
+    (Method::GET, "/readyz"),
+    (Method::POST, "/login"),
+    (Method::GET, "/logout"),
+    (Method::POST, "/register"),
+    (Method::GET, "/verify"),
+    (Method::POST, "/verify/resend"),
+    (Method::POST, "/password/forgot"),
+    (Method::POST, "/password/reset"),
+    // These check the credentials themselves; sign-in would refuse accounts that are
+    // required to have two-factor authentication but haven't set it up yet
+    (Method::POST, "/account/2fa/enroll"),
+    (Method::POST, "/account/2fa/confirm"),
+];
+
+pub fn is_public(method: &Method, path: &str) -> bool {
+    let path = unversioned(path);
+
+    // CORS preflight requests never carry credentials
+    *method == Method::OPTIONS || PUBLIC_ROUTES.iter().any(|(m, p)| m == method && *p == path)
+}
+
+// A scraper sending `Authorization: Bearer <server.metrics_token>`. The digests are
+// compared so the time taken doesn't give the token away.
+pub fn is_metrics_scraper(settings: &Settings, headers: &HeaderMap) -> bool {
+    let sent = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match (&settings.server.metrics_token, sent) {
+        (Some(token), Some(sent)) => tokens::digest(token) == tokens::digest(sent),
+        _ => false,
+    }
+}
+
+// Rejects unauthenticated requests to any route outside `PUBLIC_ROUTES` with 401.
+// The signed-in `User` is left in the request extensions for the handlers.
+pub struct RequireSession;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireSession
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireSessionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireSessionMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequireSessionMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireSessionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let scraper = req.path() == "/metrics"
+                && req
+                    .app_data::<web::Data<Settings>>()
+                    .is_some_and(|s| is_metrics_scraper(s, req.headers()));
+
+            if !is_public(req.method(), req.path()) && !scraper {
+                match authenticate(&req) {
+                    Ok(user) => {
+                        req.extensions_mut().insert(user);
+                    }
+                    Err(e) => {
+                        let response = ApiError::from(e).error_response();
+                        return Ok(req.into_response(response).map_into_right_body());
+                    }
+                }
+            }
+
+            service.call(req).await.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+fn authenticate(req: &ServiceRequest) -> Result<User> {
+    let settings = req
+        .app_data::<web::Data<Settings>>()
+        .ok_or_else(|| anyhow!("Settings must be registered as app data."))?
+        .clone()
+        .into_inner();
+
+    let mut conn = ServerConnection::new(settings);
+    conn.set_client_ip(req.peer_addr().map(|a| a.ip().to_string()));
+
+    let headers = req.headers();
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned())
+    };
+
+    match (
+        header("session_token"),
+        header("login_email"),
+        header("login_password"),
+    ) {
+        (Some(token), _, _) => conn.resume_session(&token)?,
+        (None, Some(email), Some(password)) => {
+            conn.login(email, password, header("totp_code"))?
+        }
+        _ => return Err(unauthenticated("Must be signed in.")),
+    }
+
+    conn.current_user()
+}
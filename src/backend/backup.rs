@@ -0,0 +1,258 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use serde_derive::Serialize;
+
+use super::error::{bad_request, not_found};
+use super::settings::{BackupSettings, Settings};
+use super::storage::SCHEMA_VERSION;
+
+// `snapshot-20261018T194921123Z.db`, in UTC down to the millisecond
+const PREFIX: &str = "snapshot-";
+const EXTENSION: &str = ".db";
+const STAMP: &str = "%Y%m%dT%H%M%S%3fZ";
+
+// Copied a few pages at a time with a pause in between, so writers aren't held off
+// for the whole copy. SQLite restarts the copy if another connection writes meanwhile.
+const PAGES_PER_STEP: std::os::raw::c_int = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub name: String,
+    // RFC 3339
+    pub taken_at: String,
+    pub size_bytes: u64,
+    #[serde(skip)]
+    time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Verification {
+    pub name: String,
+    pub ok: bool,
+    pub schema_version: i32,
+    // Empty when the snapshot is intact and this build can restore it
+    pub problems: Vec<String>,
+}
+
+// Copies the live database into a new snapshot with SQLite's online backup API. The copy
+// is written under a temporary name first, so a snapshot that exists is complete.
+pub fn take(database: &Path, dir: &Path) -> Result<Snapshot> {
+    fs::create_dir_all(dir)?;
+
+    let time = Utc::now();
+    let name = format!("{}{}{}", PREFIX, time.format(STAMP), EXTENSION);
+    let path = dir.join(&name);
+    let partial = dir.join(format!("{}.partial", name));
+
+    let copied = (|| -> Result<()> {
+        let source = Connection::open_with_flags(database, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut target = Connection::open(&partial)?;
+        Backup::new(&source, &mut target)?.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+
+        Ok(())
+    })();
+
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    fs::rename(&partial, &path)?;
+
+    Ok(Snapshot {
+        name,
+        taken_at: time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        size_bytes: fs::metadata(&path)?.len(),
+        time,
+    })
+}
+
+// Newest first. Files that don't look like snapshots are ignored.
+pub fn list(dir: &Path) -> Result<Vec<Snapshot>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if let Some(time) = taken_at(&name) {
+            snapshots.push(Snapshot {
+                name,
+                taken_at: time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                size_bytes: entry.metadata()?.len(),
+                time,
+            });
+        }
+    }
+
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.time));
+    Ok(snapshots)
+}
+
+// Runs the integrity check on a snapshot and compares its schema with this build's
+pub fn verify(dir: &Path, name: &str) -> Result<Verification> {
+    let path = find(dir, name)?;
+    let mut verification = Verification {
+        name: name.to_owned(),
+        ok: false,
+        schema_version: 0,
+        problems: Vec::new(),
+    };
+
+    let checked = (|| -> Result<(Vec<String>, i32)> {
+        let connection = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = connection.prepare("PRAGMA integrity_check")?;
+        let integrity = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        let version = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        Ok((integrity, version))
+    })();
+
+    match checked {
+        Ok((integrity, version)) => {
+            verification.schema_version = version;
+            verification
+                .problems
+                .extend(integrity.into_iter().filter(|line| line != "ok"));
+
+            if version != SCHEMA_VERSION {
+                verification.problems.push(format!(
+                    "Snapshot schema is at version {}, this build expects {}.",
+                    version, SCHEMA_VERSION
+                ));
+            }
+        }
+        // Not a database at all, or unreadable
+        Err(e) => verification.problems.push(e.to_string()),
+    }
+
+    verification.ok = verification.problems.is_empty();
+    Ok(verification)
+}
+
+// Verifies the snapshot, saves the current database as a snapshot of its own and then
+// copies the snapshot over the live database. Returns the snapshot of the old state.
+pub fn restore(database: &Path, dir: &Path, name: &str) -> Result<Snapshot> {
+    let verification = verify(dir, name)?;
+    if !verification.ok {
+        return Err(bad_request(format!(
+            "Snapshot {} cannot be restored: {}",
+            name,
+            verification.problems.join(" ")
+        )));
+    }
+
+    let previous = take(database, dir)?;
+
+    let source = Connection::open_with_flags(find(dir, name)?, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut target = Connection::open(database)?;
+    Backup::new(&source, &mut target)?.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+
+    Ok(previous)
+}
+
+// Deletes the snapshots the retention policy no longer needs; returns them
+pub fn prune(dir: &Path, policy: &BackupSettings) -> Result<Vec<Snapshot>> {
+    let snapshots = list(dir)?;
+    let mut keep: HashSet<usize> = HashSet::new();
+
+    // The newest snapshot is never pruned
+    if !snapshots.is_empty() {
+        keep.insert(0);
+    }
+
+    keep_newest_per_period(&snapshots, policy.keep_hourly, "%Y%m%d%H", &mut keep);
+    keep_newest_per_period(&snapshots, policy.keep_daily, "%Y%m%d", &mut keep);
+    keep_newest_per_period(&snapshots, policy.keep_weekly, "%G-W%V", &mut keep);
+
+    let mut removed = Vec::new();
+    for (i, snapshot) in snapshots.into_iter().enumerate() {
+        if !keep.contains(&i) {
+            fs::remove_file(dir.join(&snapshot.name))?;
+            removed.push(snapshot);
+        }
+    }
+
+    Ok(removed)
+}
+
+// Takes a snapshot every `backup.interval_minutes` for as long as the process runs
+pub fn schedule(settings: Arc<Settings>) {
+    let minutes = match settings.backup.interval_minutes {
+        Some(m) => m,
+        None => return,
+    };
+
+    if settings.database.url.is_some() {
+        tracing::warn!("backup.interval_minutes is ignored for PostgreSQL databases");
+        return;
+    }
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(minutes * 60));
+
+        let dir = settings.backup_dir();
+        match take(&settings.database.path, &dir) {
+            Ok(snapshot) => {
+                tracing::info!(snapshot = %snapshot.name, "scheduled snapshot taken");
+                if let Err(e) = prune(&dir, &settings.backup) {
+                    tracing::warn!(error = %e, "could not prune snapshots");
+                }
+            }
+            Err(e) => tracing::error!(error = %e, "scheduled snapshot failed"),
+        }
+    });
+}
+
+// `snapshots` is newest first, so the first one seen in each period is that period's newest
+fn keep_newest_per_period(
+    snapshots: &[Snapshot],
+    periods: usize,
+    period_format: &str,
+    keep: &mut HashSet<usize>,
+) {
+    let mut seen = HashSet::new();
+
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        if seen.len() == periods {
+            break;
+        }
+        if seen.insert(snapshot.time.format(period_format).to_string()) {
+            keep.insert(i);
+        }
+    }
+}
+
+// Only names this module produces are accepted, which also keeps paths inside `dir`
+fn find(dir: &Path, name: &str) -> Result<PathBuf> {
+    let path = dir.join(name);
+
+    if taken_at(name).is_none() || !path.is_file() {
+        return Err(not_found(format!("No snapshot named '{}'.", name)));
+    }
+
+    Ok(path)
+}
+
+fn taken_at(name: &str) -> Option<DateTime<Utc>> {
+    let stamp = name.strip_prefix(PREFIX)?.strip_suffix(EXTENSION)?;
+
+    NaiveDateTime::parse_from_str(stamp, STAMP)
+        .ok()
+        .map(|t| t.and_utc())
+}
@@ -0,0 +1,172 @@
+use std::fmt::Display;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use rusqlite::ErrorCode;
+use serde_json::json;
+
+use super::policy::ValidationErrors;
+
+// Failures that mean something to API clients. `ServerConnection` returns them wrapped in
+// `anyhow::Error` (see the helpers below); anything else is reported as internal.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Forbidden(String),
+    Unauthenticated(String),
+    Validation(ValidationErrors),
+    Conflict(String),
+    Internal(String),
+}
+
+pub fn bad_request(message: impl Into<String>) -> anyhow::Error {
+    ApiError::BadRequest(message.into()).into()
+}
+
+pub fn not_found(message: impl Into<String>) -> anyhow::Error {
+    ApiError::NotFound(message.into()).into()
+}
+
+pub fn forbidden(message: impl Into<String>) -> anyhow::Error {
+    ApiError::Forbidden(message.into()).into()
+}
+
+pub fn unauthenticated(message: impl Into<String>) -> anyhow::Error {
+    ApiError::Unauthenticated(message.into()).into()
+}
+
+pub fn conflict(message: impl Into<String>) -> anyhow::Error {
+    ApiError::Conflict(message.into()).into()
+}
+
+// A failure as it may appear in logs and operator output. Database errors come down to
+// their codes: their messages can quote the statement, and statements carry password
+// and token hashes.
+pub fn redacted(e: &anyhow::Error) -> String {
+    #[cfg(feature = "postgres")]
+    if let Some(pg) = e.downcast_ref::<postgres::Error>() {
+        return match pg.code() {
+            Some(state) => format!("database error (SQLSTATE {})", state.code()),
+            None => String::from("database connection error"),
+        };
+    }
+
+    match e.downcast_ref::<rusqlite::Error>() {
+        Some(
+            rusqlite::Error::SqliteFailure(f, _) | rusqlite::Error::SqlInputError { error: f, .. },
+        ) => format!("database error ({:?}, code {})", f.code, f.extended_code),
+        _ => e.to_string(),
+    }
+}
+
+impl ApiError {
+    // Stable identifier for clients to branch on; the message is for people
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Unauthenticated(_) => "unauthenticated",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::BadRequest(m)
+            | ApiError::NotFound(m)
+            | ApiError::Forbidden(m)
+            | ApiError::Unauthenticated(m)
+            | ApiError::Conflict(m)
+            | ApiError::Internal(m) => write!(f, "{}", m),
+            ApiError::Validation(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<ApiError>() {
+            Ok(api) => return api,
+            Err(e) => e,
+        };
+
+        let e = match e.downcast::<ValidationErrors>() {
+            Ok(v) => return ApiError::Validation(v),
+            Err(e) => e,
+        };
+
+        // Class 23 covers what SQLite reports as a constraint violation
+        #[cfg(feature = "postgres")]
+        if let Some(db) = e.downcast_ref::<postgres::Error>().and_then(|e| e.as_db_error()) {
+            if db.code().code().starts_with("23") {
+                return ApiError::Conflict(db.message().to_owned());
+            }
+        }
+
+        match e.downcast_ref::<rusqlite::Error>() {
+            Some(rusqlite::Error::SqliteFailure(f, message))
+                if f.code == ErrorCode::ConstraintViolation =>
+            {
+                ApiError::Conflict(
+                    message
+                        .clone()
+                        .unwrap_or_else(|| String::from("The record conflicts with an existing one.")),
+                )
+            }
+            Some(rusqlite::Error::QueryReturnedNoRows) => {
+                ApiError::NotFound(String::from("Not found."))
+            }
+            _ => ApiError::Internal(redacted(&e)),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Internal(e.to_string())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Unauthenticated(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // `{"code": "not_found", "error": "User not found."}`, plus `details` with one entry
+    // per offending field for validation failures. Internal details stay in the server log.
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ApiError::Internal(message) => tracing::error!(error = %message, "internal error"),
+            ApiError::Forbidden(_) | ApiError::Unauthenticated(_) => {
+                tracing::warn!(code = self.code(), reason = %self, "permission denied")
+            }
+            _ => tracing::debug!(code = self.code(), reason = %self, "request rejected"),
+        }
+
+        let body = match self {
+            ApiError::Validation(v) => {
+                json!({"code": self.code(), "error": v.to_string(), "details": v.errors})
+            }
+            ApiError::Internal(_) => json!({"code": self.code(), "error": "Internal server error."}),
+            _ => json!({"code": self.code(), "error": self.to_string()}),
+        };
+
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
@@ -0,0 +1,287 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use anyhow::Result;
+use rust_xlsxwriter::{Workbook, Worksheet};
+use serde_json::Value;
+
+use super::error::{bad_request, not_found};
+use super::filter::Filter;
+use super::policy::ValidationErrors;
+use super::table_models::Table;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Xlsx,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            "xlsx" => Ok(ExportFormat::Xlsx),
+            _ => Err(bad_request(format!(
+                "Unknown format '{}' (expected csv, jsonl or xlsx).",
+                s
+            ))),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+const EXPORT_NAMES: &[&str] = &[
+    "users",
+    "students",
+    "teachers",
+    "courses",
+    "enrollments",
+    "departments",
+    "terms",
+];
+
+// What can be exported, the table behind it, and the columns that never leave the
+// server. Credential tables (sessions, resets, 2FA, lockouts) are deliberately absent.
+fn exportable(name: &str) -> Option<(Table, &'static str, &'static [&'static str])> {
+    match name {
+        "users" => Some((Table::Users, "USERS", &["password"])),
+        "students" => Some((Table::StudentAccount, "STUDENT_ACCOUNT", &[])),
+        "teachers" => Some((Table::TeacherAccount, "TEACHER_ACCOUNT", &[])),
+        "courses" => Some((Table::Courses, "COURSES", &[])),
+        "enrollments" => Some((Table::StudentCourses, "STUDENT_COURSES", &[])),
+        "departments" => Some((Table::Departments, "DEPARTMENTS", &[])),
+        "terms" => Some((Table::Terms, "TERMS", &[])),
+        _ => None,
+    }
+}
+
+pub struct ExportRequest {
+    pub name: String,
+    pub sql_table: &'static str,
+    pub excluded: &'static [&'static str],
+    pub filters: Vec<Filter>,
+    pub format: ExportFormat,
+}
+
+impl ExportRequest {
+    // Checks the export name and every `column=value` filter before any data is read
+    pub fn parse(name: &str, format: ExportFormat, params: &[(String, String)]) -> Result<Self> {
+        let (table, sql_table, excluded) = exportable(name).ok_or_else(|| {
+            not_found(format!(
+                "Unknown export '{}' (expected one of: {}).",
+                name,
+                EXPORT_NAMES.join(", ")
+            ))
+        })?;
+
+        let mut errors = ValidationErrors::default();
+        let mut filters = Vec::new();
+
+        for (column, value) in params {
+            if excluded.contains(&column.as_str()) {
+                errors.add(column, "not_filterable", &format!("Cannot filter by '{}'.", column));
+                continue;
+            }
+
+            match Filter::from_param(&table, column, value) {
+                Ok(filter) => filters.push(filter),
+                Err(message) => errors.add(column, "not_filterable", &message),
+            }
+        }
+
+        errors.into_result()?;
+
+        Ok(Self {
+            name: name.to_owned(),
+            sql_table,
+            excluded,
+            filters,
+            format,
+        })
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.name, self.format.extension())
+    }
+}
+
+// Receives rows one at a time and writes them out in some format
+pub trait RowSink {
+    fn header(&mut self, columns: &[String]) -> Result<()>;
+    fn row(&mut self, values: &[Value]) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+pub fn sink_for<'a, W: Write + 'a>(format: ExportFormat, out: W) -> Box<dyn RowSink + 'a> {
+    match format {
+        ExportFormat::Csv => Box::new(CsvSink {
+            writer: csv::Writer::from_writer(out),
+        }),
+        ExportFormat::Jsonl => Box::new(JsonlSink {
+            out,
+            columns: Vec::new(),
+        }),
+        ExportFormat::Xlsx => {
+            let mut workbook = Workbook::new();
+            workbook.add_worksheet_with_constant_memory();
+
+            Box::new(XlsxSink {
+                out,
+                workbook,
+                next_row: 0,
+            })
+        }
+    }
+}
+
+struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> RowSink for CsvSink<W> {
+    fn header(&mut self, columns: &[String]) -> Result<()> {
+        self.writer.write_record(columns)?;
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Value]) -> Result<()> {
+        self.writer.write_record(values.iter().map(|v| match v {
+            Value::Null => String::new(),
+            Value::String(s) => s.to_owned(),
+            other => other.to_string(),
+        }))?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct JsonlSink<W: Write> {
+    out: W,
+    columns: Vec<String>,
+}
+
+impl<W: Write> RowSink for JsonlSink<W> {
+    fn header(&mut self, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    // Written by hand so the keys keep the table's column order
+    fn row(&mut self, values: &[Value]) -> Result<()> {
+        let fields: Vec<String> = self
+            .columns
+            .iter()
+            .zip(values)
+            .map(|(column, value)| format!("{}:{}", Value::from(column.as_str()), value))
+            .collect();
+
+        writeln!(self.out, "{{{}}}", fields.join(","))?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+// Rows are written through a constant-memory worksheet backed by a temp file. The
+// workbook itself is a zip archive, so the file can only be emitted once it's complete.
+struct XlsxSink<W: Write> {
+    out: W,
+    workbook: Workbook,
+    next_row: u32,
+}
+
+impl<W: Write> XlsxSink<W> {
+    fn write_row<T>(
+        &mut self,
+        cells: &[T],
+        write: impl Fn(&mut Worksheet, u32, u16, &T) -> Result<()>,
+    ) -> Result<()> {
+        let row = self.next_row;
+        let sheet = self.workbook.worksheet_from_index(0)?;
+
+        for (col, cell) in cells.iter().enumerate() {
+            write(sheet, row, col as u16, cell)?;
+        }
+
+        self.next_row += 1;
+        Ok(())
+    }
+}
+
+impl<W: Write> RowSink for XlsxSink<W> {
+    fn header(&mut self, columns: &[String]) -> Result<()> {
+        self.write_row(columns, |sheet, row, col, name| {
+            sheet.write_string(row, col, name)?;
+            Ok(())
+        })
+    }
+
+    fn row(&mut self, values: &[Value]) -> Result<()> {
+        self.write_row(values, |sheet, row, col, value| {
+            match value {
+                Value::Null => {}
+                Value::Number(n) => {
+                    sheet.write_number(row, col, n.as_f64().unwrap_or_default())?;
+                }
+                Value::String(s) => {
+                    sheet.write_string(row, col, s)?;
+                }
+                other => {
+                    sheet.write_string(row, col, other.to_string())?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let buffer = self.workbook.save_to_buffer()?;
+        self.out.write_all(&buffer)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+// Removes the excluded columns from a row, keeping the order
+pub fn without_excluded(
+    columns: &[String],
+    values: Vec<Value>,
+    excluded: &[&str],
+) -> (Vec<String>, Vec<Value>) {
+    columns
+        .iter()
+        .cloned()
+        .zip(values)
+        .filter(|(c, _)| !excluded.contains(&c.as_str()))
+        .unzip()
+}
@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 
 use super::db_driver::Join;
+use super::table_models::{escape, Table};
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 pub trait Filterable {
     fn to_sql(&self) -> String;
@@ -28,6 +30,16 @@ pub enum Filter {
     Courses(CoursesFilter),
     Departments(DepartmentsFilter),
     StudentCourses(StudentCoursesFilter),
+    Sessions(SessionsFilter),
+    PasswordResets(PasswordResetsFilter),
+    TwoFactor(TwoFactorFilter),
+    RecoveryCodes(RecoveryCodesFilter),
+    SystemSettings(SystemSettingsFilter),
+    LoginAttempts(LoginAttemptsFilter),
+    Terms(TermsFilter),
+    StandingHistory(StandingHistoryFilter),
+    Notifications(NotificationsFilter),
+    Holds(HoldsFilter),
 }
 
 impl Display for Filter {
@@ -39,6 +51,16 @@ impl Display for Filter {
             Filter::Courses(_) => write!(f, "COURSES"),
             Filter::Departments(_) => write!(f, "DEPARTMENTS"),
             Filter::StudentCourses(_) => write!(f, "STUDENT_COURSES"),
+            Filter::Sessions(_) => write!(f, "SESSIONS"),
+            Filter::PasswordResets(_) => write!(f, "PASSWORD_RESETS"),
+            Filter::TwoFactor(_) => write!(f, "TWO_FACTOR"),
+            Filter::RecoveryCodes(_) => write!(f, "RECOVERY_CODES"),
+            Filter::SystemSettings(_) => write!(f, "SYSTEM_SETTINGS"),
+            Filter::LoginAttempts(_) => write!(f, "LOGIN_ATTEMPTS"),
+            Filter::Terms(_) => write!(f, "TERMS"),
+            Filter::StandingHistory(_) => write!(f, "STANDING_HISTORY"),
+            Filter::Notifications(_) => write!(f, "NOTIFICATIONS"),
+            Filter::Holds(_) => write!(f, "HOLDS"),
         }
     }
 }
@@ -63,10 +85,115 @@ impl Filterable for Filter {
             Filter::Courses(x) => x.to_sql(),
             Filter::Departments(x) => x.to_sql(),
             Filter::StudentCourses(x) => x.to_sql(),
+            Filter::Sessions(x) => x.to_sql(),
+            Filter::PasswordResets(x) => x.to_sql(),
+            Filter::TwoFactor(x) => x.to_sql(),
+            Filter::RecoveryCodes(x) => x.to_sql(),
+            Filter::SystemSettings(x) => x.to_sql(),
+            Filter::LoginAttempts(x) => x.to_sql(),
+            Filter::Terms(x) => x.to_sql(),
+            Filter::StandingHistory(x) => x.to_sql(),
+            Filter::Notifications(x) => x.to_sql(),
+            Filter::Holds(x) => x.to_sql(),
         }
     }
 }
 
+impl Filter {
+    // Builds a filter from a `column=value` pair, e.g. a query parameter. Only the columns
+    // it makes sense to select rows by are accepted.
+    pub fn from_param(table: &Table, column: &str, value: &str) -> Result<Filter, String> {
+        let text = || value.to_owned();
+
+        let filter = match (table, column) {
+            (Table::Users, "id") => Filter::Users(UsersFilter::Id(parse(column, value)?)),
+            (Table::Users, "username") => Filter::Users(UsersFilter::Username(text())),
+            (Table::Users, "email") => Filter::Users(UsersFilter::Email(text())),
+            (Table::Users, "phone") => Filter::Users(UsersFilter::Phone(text())),
+            (Table::Users, "role") => Filter::Users(UsersFilter::Role(text())),
+            (Table::Users, "verified") => {
+                Filter::Users(UsersFilter::Verified(parse(column, value)?))
+            }
+            (Table::Users, "suspended") => {
+                Filter::Users(UsersFilter::Suspended(parse(column, value)?))
+            }
+            (Table::Users, "forcenewpw") => {
+                Filter::Users(UsersFilter::Forcenewpw(parse(column, value)?))
+            }
+
+            (Table::StudentAccount, "id") => {
+                Filter::StudentAccount(StudentAccountFilter::Id(parse(column, value)?))
+            }
+            (Table::StudentAccount, "student_id") => {
+                Filter::StudentAccount(StudentAccountFilter::StudentId(parse(column, value)?))
+            }
+            (Table::StudentAccount, "advisor_id") => {
+                Filter::StudentAccount(StudentAccountFilter::AdvisorId(parse(column, value)?))
+            }
+            (Table::StudentAccount, "discipline") => {
+                Filter::StudentAccount(StudentAccountFilter::Discipline(text()))
+            }
+            (Table::StudentAccount, "enrollment") => {
+                Filter::StudentAccount(StudentAccountFilter::Enrollment(text()))
+            }
+            (Table::StudentAccount, "can_grad") => {
+                Filter::StudentAccount(StudentAccountFilter::CanGrad(parse(column, value)?))
+            }
+
+            (Table::TeacherAccount, "id") => {
+                Filter::TeacherAccount(TeacherAccountFilter::Id(parse(column, value)?))
+            }
+            (Table::TeacherAccount, "teacher_id") => {
+                Filter::TeacherAccount(TeacherAccountFilter::TeacherId(parse(column, value)?))
+            }
+            (Table::TeacherAccount, "dept_id") => {
+                Filter::TeacherAccount(TeacherAccountFilter::DeptId(parse(column, value)?))
+            }
+
+            (Table::Courses, "id") => Filter::Courses(CoursesFilter::Id(parse(column, value)?)),
+            (Table::Courses, "teacher_id") => {
+                Filter::Courses(CoursesFilter::TeacherId(parse(column, value)?))
+            }
+            (Table::Courses, "course") => Filter::Courses(CoursesFilter::Course(text())),
+            (Table::Courses, "course_nr") => Filter::Courses(CoursesFilter::CourseNr(text())),
+            (Table::Courses, "cr_cost") => {
+                Filter::Courses(CoursesFilter::CrCost(parse(column, value)?))
+            }
+
+            (Table::StudentCourses, "student_id") => {
+                Filter::StudentCourses(StudentCoursesFilter::StudentId(parse(column, value)?))
+            }
+            (Table::StudentCourses, "course_id") => {
+                Filter::StudentCourses(StudentCoursesFilter::CourseId(parse(column, value)?))
+            }
+            (Table::StudentCourses, "grade") => {
+                Filter::StudentCourses(StudentCoursesFilter::Grade(parse(column, value)?))
+            }
+            (Table::StudentCourses, "semester") => {
+                Filter::StudentCourses(StudentCoursesFilter::Semester(text()))
+            }
+
+            (Table::Departments, "id") => {
+                Filter::Departments(DepartmentsFilter::Id(parse(column, value)?))
+            }
+            (Table::Departments, "name") => Filter::Departments(DepartmentsFilter::Name(text())),
+
+            (Table::Terms, "id") => Filter::Terms(TermsFilter::Id(parse(column, value)?)),
+            (Table::Terms, "name") => Filter::Terms(TermsFilter::Name(text())),
+
+            _ => return Err(format!("Cannot filter by '{}'.", column)),
+        };
+
+        Ok(filter)
+    }
+}
+
+fn parse<T: FromStr>(column: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{}' for '{}'.", value, column))
+}
+
 pub enum UsersFilter {
     Username(String),
     Email(String),
@@ -82,10 +209,10 @@ pub enum UsersFilter {
 impl Filterable for UsersFilter {
     fn to_sql(&self) -> String {
         match self {
-            UsersFilter::Username(username) => format!("username = '{}'", username),
-            UsersFilter::Email(email) => format!("email = '{}'", email),
-            UsersFilter::Phone(phone) => format!("phone = '{}'", phone),
-            UsersFilter::Role(role) => format!("role = '{}'", role),
+            UsersFilter::Username(username) => format!("username = '{}'", escape(username)),
+            UsersFilter::Email(email) => format!("email = '{}'", escape(email)),
+            UsersFilter::Phone(phone) => format!("phone = '{}'", escape(phone)),
+            UsersFilter::Role(role) => format!("role = '{}'", escape(role)),
             UsersFilter::Verified(verified) => format!("verified = {}", verified),
             UsersFilter::Suspended(suspended) => format!("suspended = {}", suspended),
             UsersFilter::Forcenewpw(forcenewpw) => format!("forcenewpw = {}", forcenewpw),
@@ -114,10 +241,10 @@ impl Filterable for StudentAccountFilter {
             StudentAccountFilter::StudentId(student_id) => format!("student_id = {}", student_id),
             StudentAccountFilter::AdvisorId(advisor_id) => format!("advisor_id = {}", advisor_id),
             StudentAccountFilter::Discipline(discipline) => {
-                format!("discipline = '{}'", discipline)
+                format!("discipline = '{}'", escape(discipline))
             }
             StudentAccountFilter::Enrollment(enrollment) => {
-                format!("enrollment = '{}'", enrollment)
+                format!("enrollment = '{}'", escape(enrollment))
             }
             StudentAccountFilter::Cgpa(cgpa) => format!("cgpa = {}", cgpa),
             StudentAccountFilter::CanGrad(can_grad) => format!("can_grad = {}", can_grad),
@@ -142,7 +269,7 @@ impl Filterable for TeacherAccountFilter {
         match self {
             TeacherAccountFilter::TeacherId(teacher_id) => format!("teacher_id = {}", teacher_id),
             TeacherAccountFilter::DeptId(dept_id) => format!("dept_id = {}", dept_id),
-            TeacherAccountFilter::Dept(dept) => format!("dept = '{}'", dept),
+            TeacherAccountFilter::Dept(dept) => format!("dept = '{}'", escape(dept)),
             TeacherAccountFilter::Id(id) => format!("id = {}", id),
             TeacherAccountFilter::All => String::from("1 = 1"), // always true
         }
@@ -153,6 +280,7 @@ pub enum CoursesFilter {
     Id(i32),
     TeacherId(i32),
     Course(String),
+    CourseNr(String),
     CrCost(i32),
     CreatedAt(String),
     UpdatedAt(String),
@@ -164,7 +292,8 @@ impl Filterable for CoursesFilter {
         match self {
             CoursesFilter::Id(id) => format!("id = {}", id),
             CoursesFilter::TeacherId(teacher_id) => format!("teacher_id = {}", teacher_id),
-            CoursesFilter::Course(course) => format!("course = '{}'", course),
+            CoursesFilter::Course(course) => format!("course = '{}'", escape(course)),
+            CoursesFilter::CourseNr(course_nr) => format!("course_nr = '{}'", escape(course_nr)),
             CoursesFilter::CrCost(cr_cost) => format!("cr_cost = {}", cr_cost),
             CoursesFilter::CreatedAt(created_at) => format!("created_at = '{}'", created_at),
             CoursesFilter::UpdatedAt(updated_at) => format!("updated_at = '{}'", updated_at),
@@ -184,7 +313,7 @@ impl Filterable for DepartmentsFilter {
     fn to_sql(&self) -> String {
         match self {
             DepartmentsFilter::DeptHead(dept_head) => format!("dept_head = {}", dept_head),
-            DepartmentsFilter::Name(name) => format!("name = '{}'", name),
+            DepartmentsFilter::Name(name) => format!("name = '{}'", escape(name)),
             DepartmentsFilter::Id(id) => format!("id = {}", id),
             DepartmentsFilter::All => String::from("1 = 1"), // always true
         }
@@ -206,9 +335,197 @@ impl Filterable for StudentCoursesFilter {
             StudentCoursesFilter::StudentId(student_id) => format!("student_id = {}", student_id),
             StudentCoursesFilter::CourseId(course_id) => format!("course_id = {}", course_id),
             StudentCoursesFilter::Grade(grade) => format!("grade = {}", grade),
-            StudentCoursesFilter::Semester(semester) => format!("semester = '{}'", semester),
+            StudentCoursesFilter::Semester(semester) => {
+                format!("semester = '{}'", escape(semester))
+            }
             StudentCoursesFilter::Id(id) => format!("id = {}", id),
             StudentCoursesFilter::All => String::from("1 = 1"), // always true
         }
     }
 }
+
+pub enum SessionsFilter {
+    UserId(i32),
+    TokenHash(String),
+    // Sessions still valid at this Unix time
+    ExpiresAfter(i64),
+    Id(i32),
+    All,
+}
+
+impl Filterable for SessionsFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            SessionsFilter::UserId(user_id) => format!("user_id = {}", user_id),
+            SessionsFilter::TokenHash(token_hash) => format!("token_hash = '{}'", token_hash),
+            SessionsFilter::ExpiresAfter(time) => format!("expires_at > {}", time),
+            SessionsFilter::Id(id) => format!("id = {}", id),
+            SessionsFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum PasswordResetsFilter {
+    UserId(i32),
+    TokenHash(String),
+    Used(bool),
+    Id(i32),
+    All,
+}
+
+impl Filterable for PasswordResetsFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            PasswordResetsFilter::UserId(user_id) => format!("user_id = {}", user_id),
+            PasswordResetsFilter::TokenHash(token_hash) => format!("token_hash = '{}'", token_hash),
+            PasswordResetsFilter::Used(used) => format!("used = {}", used),
+            PasswordResetsFilter::Id(id) => format!("id = {}", id),
+            PasswordResetsFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum TwoFactorFilter {
+    UserId(i32),
+    Enabled(bool),
+    Id(i32),
+    All,
+}
+
+impl Filterable for TwoFactorFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            TwoFactorFilter::UserId(user_id) => format!("user_id = {}", user_id),
+            TwoFactorFilter::Enabled(enabled) => format!("enabled = {}", enabled),
+            TwoFactorFilter::Id(id) => format!("id = {}", id),
+            TwoFactorFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum RecoveryCodesFilter {
+    UserId(i32),
+    Used(bool),
+    Id(i32),
+    All,
+}
+
+impl Filterable for RecoveryCodesFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            RecoveryCodesFilter::UserId(user_id) => format!("user_id = {}", user_id),
+            RecoveryCodesFilter::Used(used) => format!("used = {}", used),
+            RecoveryCodesFilter::Id(id) => format!("id = {}", id),
+            RecoveryCodesFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum SystemSettingsFilter {
+    Key(String),
+    All,
+}
+
+impl Filterable for SystemSettingsFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            SystemSettingsFilter::Key(key) => format!("key = '{}'", escape(key)),
+            SystemSettingsFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum LoginAttemptsFilter {
+    Scope(String),
+    Key(String),
+    LockedAfter(i64),
+    Id(i32),
+    All,
+}
+
+impl Filterable for LoginAttemptsFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            LoginAttemptsFilter::Scope(scope) => format!("scope = '{}'", escape(scope)),
+            LoginAttemptsFilter::Key(key) => format!("key = '{}'", escape(key)),
+            LoginAttemptsFilter::LockedAfter(time) => format!("locked_until > {}", time),
+            LoginAttemptsFilter::Id(id) => format!("id = {}", id),
+            LoginAttemptsFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum TermsFilter {
+    Name(String),
+    // Terms running on the given YYYY-MM-DD date
+    Covering(String),
+    Id(i32),
+    All,
+}
+
+impl Filterable for TermsFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            TermsFilter::Name(name) => format!("name = '{}'", escape(name)),
+            TermsFilter::Covering(date) => format!(
+                "starts_on <= '{0}' AND ends_on >= '{0}'",
+                escape(date)
+            ),
+            TermsFilter::Id(id) => format!("id = {}", id),
+            TermsFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum StandingHistoryFilter {
+    StudentId(i32),
+    Term(String),
+    Id(i32),
+    All,
+}
+
+impl Filterable for StandingHistoryFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            StandingHistoryFilter::StudentId(student_id) => format!("student_id = {}", student_id),
+            StandingHistoryFilter::Term(term) => format!("term = '{}'", escape(term)),
+            StandingHistoryFilter::Id(id) => format!("id = {}", id),
+            StandingHistoryFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum NotificationsFilter {
+    UserId(i32),
+    Id(i32),
+    All,
+}
+
+impl Filterable for NotificationsFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            NotificationsFilter::UserId(user_id) => format!("user_id = {}", user_id),
+            NotificationsFilter::Id(id) => format!("id = {}", id),
+            NotificationsFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
+
+pub enum HoldsFilter {
+    StudentId(i32),
+    // Not released yet
+    Active,
+    Id(i32),
+    All,
+}
+
+impl Filterable for HoldsFilter {
+    fn to_sql(&self) -> String {
+        match self {
+            HoldsFilter::StudentId(student_id) => format!("student_id = {}", student_id),
+            HoldsFilter::Active => String::from("released_at = 0"),
+            HoldsFilter::Id(id) => format!("id = {}", id),
+            HoldsFilter::All => String::from("1 = 1"), // always true
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, HashMap};
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use serde_derive::{Deserialize, Serialize};
+
+use super::table_models::{EnrollmentWithCourse, Term};
+
+// Grades run from 0.0 to 4.0; a course is passed, and its credits earned, from a D up
+pub const PASSING: f32 = 1.0;
+
+// The `[standing]` section of the config file: how a closed term's grades decide a
+// student's standing, and what probation limits afterwards
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StandingRules {
+    // A cumulative GPA below this puts a student on probation
+    pub probation_below: f32,
+    // A term GPA below this, with the cumulative GPA still too low, suspends a student
+    // who was already on probation
+    pub suspension_below: f32,
+    // A term GPA of at least this, over at least `deans_list_credits` graded credits
+    pub deans_list_gpa: f32,
+    pub deans_list_credits: i32,
+    // The most credits a student on probation can carry in one term
+    pub probation_credit_cap: i32,
+}
+
+// A student's enrollments term by term, for transcripts and advising. When a course is
+// taken again, the latest graded attempt replaces the earlier ones in the cumulative
+// totals; each term's own totals still count every grade given that term.
+#[derive(Debug, Clone, Serialize)]
+pub struct AcademicHistory {
+    pub student_id: i32,
+    // Oldest first
+    pub terms: Vec<TermRecord>,
+    pub cumulative: Totals,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TermRecord {
+    pub term: String,
+    // None for semesters that aren't set up as terms
+    pub starts_on: Option<String>,
+    pub ends_on: Option<String>,
+    pub courses: Vec<CourseRecord>,
+    pub totals: Totals,
+    // Everything up to and including this term
+    pub cumulative: Totals,
+    pub standing: Standing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CourseRecord {
+    pub course_id: i32,
+    pub course_nr: String,
+    pub course: String,
+    pub credits: i32,
+    // None while the course is in progress
+    pub grade: Option<f32>,
+    // The course appears more than once in the history
+    pub repeated: bool,
+    // A later graded attempt took this one's place in the cumulative totals
+    pub replaced: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Totals {
+    // Credits of graded courses
+    pub attempted: i32,
+    // Credits of passed courses
+    pub earned: i32,
+    // Credits of courses still in progress
+    pub in_progress: i32,
+    pub quality_points: f32,
+    // None until something is graded
+    pub gpa: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Standing {
+    Good,
+    // Good standing, with honours for the term
+    DeansList,
+    Probation,
+    // Kept out of the following term
+    Suspension,
+    // Nothing graded yet this term
+    InProgress,
+}
+
+impl Default for StandingRules {
+    fn default() -> Self {
+        Self {
+            probation_below: 2.0,
+            suspension_below: 2.0,
+            deans_list_gpa: 3.5,
+            deans_list_credits: 12,
+            probation_credit_cap: 12,
+        }
+    }
+}
+
+impl StandingRules {
+    // The standing a term's grades earn, given the standing the student came into it with
+    pub fn evaluate(&self, previous: Standing, term: &Totals, cumulative: &Totals) -> Standing {
+        let (Some(term_gpa), Some(gpa)) = (term.gpa, cumulative.gpa) else {
+            return Standing::InProgress;
+        };
+
+        if gpa >= self.probation_below {
+            if term_gpa >= self.deans_list_gpa && term.attempted >= self.deans_list_credits {
+                Standing::DeansList
+            } else {
+                Standing::Good
+            }
+        } else if matches!(previous, Standing::Probation | Standing::Suspension)
+            && term_gpa < self.suspension_below
+        {
+            Standing::Suspension
+        } else {
+            Standing::Probation
+        }
+    }
+}
+
+impl Standing {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Standing::Good => "good",
+            Standing::DeansList => "deans_list",
+            Standing::Probation => "probation",
+            Standing::Suspension => "suspension",
+            Standing::InProgress => "in_progress",
+        }
+    }
+}
+
+impl FromStr for Standing {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "good" => Ok(Standing::Good),
+            "deans_list" => Ok(Standing::DeansList),
+            "probation" => Ok(Standing::Probation),
+            "suspension" => Ok(Standing::Suspension),
+            "in_progress" => Ok(Standing::InProgress),
+            _ => Err(anyhow!("Unknown standing '{}'.", s)),
+        }
+    }
+}
+
+impl Totals {
+    fn add(&mut self, credits: i32, grade: Option<f32>) {
+        match grade {
+            Some(grade) => {
+                self.attempted += credits;
+                self.quality_points += grade * credits as f32;
+                if grade >= PASSING {
+                    self.earned += credits;
+                }
+            }
+            None => self.in_progress += credits,
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        self.gpa = (self.attempted > 0).then(|| self.quality_points / self.attempted as f32);
+        self
+    }
+}
+
+// Enrollments filed under a semester that no term is named after come last. Each term's
+// standing follows from the last one decided before it, starting from good standing.
+pub fn build(
+    student_id: i32,
+    enrollments: Vec<EnrollmentWithCourse>,
+    terms: &[Term],
+    rules: &StandingRules,
+) -> AcademicHistory {
+    let known: HashMap<&str, &Term> = terms.iter().map(|t| (t.name.as_str(), t)).collect();
+
+    let mut by_term: Vec<(String, Vec<EnrollmentWithCourse>)> = Vec::new();
+    for e in enrollments {
+        match by_term.iter_mut().find(|(name, _)| *name == e.enrollment.semester) {
+            Some((_, taken)) => taken.push(e),
+            None => by_term.push((e.enrollment.semester.clone(), vec![e])),
+        }
+    }
+    by_term.sort_by(|(a, _), (b, _)| {
+        let starts = |name: &str| known.get(name).map(|t| t.starts_on.clone());
+        match (starts(a), starts(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.cmp(b),
+        }
+    });
+    for (_, taken) in &mut by_term {
+        taken.sort_by(|a, b| a.course.course_nr.cmp(&b.course.course_nr));
+    }
+
+    // Every attempt as (term, course, credits, grade), in order
+    let attempts: Vec<(usize, i32, i32, Option<f32>)> = by_term
+        .iter()
+        .enumerate()
+        .flat_map(|(t, (_, taken))| {
+            taken.iter().map(move |e| {
+                let grade = e.enrollment.grade;
+                (t, e.course.id, e.course.cr_cost, (grade >= 0.0).then_some(grade))
+            })
+        })
+        .collect();
+
+    let mut times_taken: HashMap<i32, usize> = HashMap::new();
+    for (_, course, _, _) in &attempts {
+        *times_taken.entry(*course).or_default() += 1;
+    }
+
+    // Totals over the terms up to `last`, each course counted by its latest graded attempt
+    let cumulative = |last: usize| {
+        let mut totals = Totals::default();
+        let mut latest: BTreeMap<i32, (i32, f32)> = BTreeMap::new();
+
+        for &(_, course, credits, grade) in attempts.iter().filter(|a| a.0 <= last) {
+            match grade {
+                Some(grade) => {
+                    latest.insert(course, (credits, grade));
+                }
+                None => totals.add(credits, None),
+            }
+        }
+        for (credits, grade) in latest.into_values() {
+            totals.add(credits, Some(grade));
+        }
+
+        totals.finish()
+    };
+
+    let mut previous = Standing::Good;
+    let records: Vec<TermRecord> = by_term
+        .iter()
+        .enumerate()
+        .map(|(t, (name, taken))| {
+            let mut totals = Totals::default();
+            let courses = taken
+                .iter()
+                .map(|e| {
+                    let grade = (e.enrollment.grade >= 0.0).then_some(e.enrollment.grade);
+                    totals.add(e.course.cr_cost, grade);
+
+                    CourseRecord {
+                        course_id: e.course.id,
+                        course_nr: e.course.course_nr.clone(),
+                        course: e.course.course.clone(),
+                        credits: e.course.cr_cost,
+                        grade,
+                        repeated: times_taken[&e.course.id] > 1,
+                        replaced: grade.is_some()
+                            && attempts
+                                .iter()
+                                .any(|a| a.0 > t && a.1 == e.course.id && a.3.is_some()),
+                    }
+                })
+                .collect();
+
+            let totals = totals.finish();
+            let cumulative = cumulative(t);
+            let standing = rules.evaluate(previous, &totals, &cumulative);
+            if standing != Standing::InProgress {
+                previous = standing;
+            }
+
+            TermRecord {
+                term: name.clone(),
+                starts_on: known.get(name.as_str()).map(|t| t.starts_on.clone()),
+                ends_on: known.get(name.as_str()).map(|t| t.ends_on.clone()),
+                courses,
+                totals,
+                cumulative,
+                standing,
+            }
+        })
+        .collect();
+
+    AcademicHistory {
+        student_id,
+        cumulative: records
+            .last()
+            .map(|r| r.cumulative.clone())
+            .unwrap_or_default(),
+        terms: records,
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::Read;
+use std::str::FromStr;
+
+use anyhow::Result;
+use csv::{ReaderBuilder, Trim};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+
+use super::error::{bad_request, not_found};
+use super::policy::{FieldError, ValidationErrors};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportKind {
+    Users,
+    Courses,
+    Enrollments,
+}
+
+impl FromStr for ImportKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "users" => Ok(ImportKind::Users),
+            "courses" => Ok(ImportKind::Courses),
+            "enrollments" => Ok(ImportKind::Enrollments),
+            _ => Err(not_found(format!(
+                "Unknown import '{}' (expected users, courses or enrollments).",
+                s
+            ))),
+        }
+    }
+}
+
+// username,email[,password][,phone][,role][,verified]
+// Without a password the account gets a random one and must go through a reset.
+#[derive(Debug, Deserialize)]
+pub struct UserRow {
+    pub username: String,
+    pub email: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub phone: String,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub verified: Option<bool>,
+}
+
+// course,course_nr,cr_cost,timeslots,teacher_email[,description][,capacity]
+#[derive(Debug, Deserialize)]
+pub struct CourseRow {
+    pub course: String,
+    pub course_nr: String,
+    pub cr_cost: i32,
+    pub timeslots: String,
+    pub teacher_email: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub capacity: i32,
+}
+
+// student_email,course_nr[,grade][,semester]
+// A missing grade means the course is in progress; a missing semester means the current term.
+#[derive(Debug, Deserialize)]
+pub struct EnrollmentRow {
+    pub student_email: String,
+    pub course_nr: String,
+    #[serde(default)]
+    pub grade: Option<f32>,
+    #[serde(default)]
+    pub semester: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowReport {
+    // Line in the file, counting the header as line 1
+    pub line: usize,
+    pub ok: bool,
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub kind: ImportKind,
+    pub dry_run: bool,
+    // Nothing is written unless every row is valid
+    pub committed: bool,
+    pub total: usize,
+    pub failed: usize,
+    pub rows: Vec<RowReport>,
+}
+
+impl ImportReport {
+    pub fn new(kind: ImportKind, dry_run: bool) -> Self {
+        Self {
+            kind,
+            dry_run,
+            committed: false,
+            total: 0,
+            failed: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, line: usize, errors: ValidationErrors) {
+        self.total += 1;
+
+        if !errors.is_empty() {
+            self.failed += 1;
+        }
+
+        self.rows.push(RowReport {
+            line,
+            ok: errors.is_empty(),
+            errors: errors.errors,
+        });
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+// Parses every row up front. Rows that don't parse come back as errors so they can be
+// reported alongside the ones that fail validation.
+pub fn read_rows<T: DeserializeOwned>(input: impl Read) -> Result<Vec<(usize, Result<T, ValidationErrors>)>> {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(input);
+    let headers = reader
+        .headers()
+        .map_err(|e| bad_request(format!("Cannot read CSV header: {}", e)))?
+        .clone();
+
+    let rows = reader
+        .deserialize::<T>()
+        .enumerate()
+        .map(|(i, row)| {
+            let line = i + 2;
+
+            let row = row.map_err(|e| {
+                let mut errors = ValidationErrors::default();
+                let (field, message) = match e.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => (
+                        err.field().and_then(|i| headers.get(i as usize)),
+                        err.kind().to_string(),
+                    ),
+                    _ => (None, e.to_string()),
+                };
+
+                errors.add(field.unwrap_or("row"), "unreadable", &message);
+                errors
+            });
+
+            (line, row)
+        })
+        .collect();
+
+    Ok(rows)
+}
@@ -0,0 +1,149 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+
+use super::settings::{LogFormat, LogSettings};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Header names containing any of these never reach the log
+const SECRET_HEADERS: &[&str] = &["password", "token", "code", "secret", "authorization", "cookie"];
+
+// Installs the global subscriber. The filter was checked when the settings were loaded.
+pub fn init(settings: &LogSettings) {
+    let filter = EnvFilter::try_new(&settings.filter).unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let installed = match settings.format {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init(),
+    };
+
+    if let Err(e) = installed {
+        eprintln!("Logging is already set up: {}", e);
+    }
+}
+
+// `session_token: [redacted], id: 4`
+pub fn redacted(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = name.as_str();
+            let value = if SECRET_HEADERS.iter().any(|s| name.contains(s)) {
+                "[redacted]"
+            } else {
+                value.to_str().unwrap_or("[binary]")
+            };
+
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Ids from clients and proxies are kept so one request can be followed across services
+fn request_id(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= 128
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c))
+        })
+        .map(|id| id.to_owned())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+// Runs every request inside a span carrying its id, logs the outcome and returns the id
+// in `X-Request-Id`.
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let started = Instant::now();
+        let id = request_id(&req);
+
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %req.method(),
+            path = %req.path(),
+            client = req.peer_addr().map(|a| a.ip().to_string()).unwrap_or_default(),
+            // Filled in once the handler knows who is signed in
+            user_id = tracing::field::Empty,
+        );
+        span.in_scope(|| tracing::debug!(headers = %redacted(req.headers()), "request received"));
+
+        Box::pin(
+            async move {
+                let mut res = service.call(req).await?;
+                let status = res.status().as_u16();
+                let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+                // Why a request was refused is logged where the error response is built
+                if status >= 500 {
+                    tracing::error!(status, elapsed_ms, "request failed");
+                } else {
+                    tracing::info!(status, elapsed_ms, "request handled");
+                }
+
+                if let Ok(id) = HeaderValue::from_str(&id) {
+                    res.headers_mut()
+                        .insert(HeaderName::from_static(REQUEST_ID_HEADER), id);
+                }
+
+                Ok(res)
+            }
+            .instrument(span),
+        )
+    }
+}
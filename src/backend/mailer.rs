@@ -0,0 +1,179 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Ok, Result};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use rusqlite::Connection;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::settings::{MailTransport, Settings};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub trait Mailer {
+    fn send(&self, email: &Email) -> Result<()>;
+}
+
+// Builds the mailer selected in the settings
+pub fn from_settings(settings: &Settings) -> Result<Box<dyn Mailer>> {
+    let mailer: Box<dyn Mailer> = match &settings.mail.transport {
+        MailTransport::Smtp {
+            host,
+            port,
+            username,
+            password,
+        } => Box::new(SmtpMailer::new(
+            &settings.mail.sender,
+            host,
+            *port,
+            username.clone().zip(password.clone()),
+        )?),
+        MailTransport::File { path } => Box::new(FileOutbox::new(path.to_owned())),
+        MailTransport::Sqlite { .. } => Box::new(SqliteOutbox::open(&settings.outbox_path())?),
+    };
+
+    Ok(mailer)
+}
+
+pub struct SmtpMailer {
+    sender: Mailbox,
+    transport: SmtpTransport,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        sender: &str,
+        host: &str,
+        port: u16,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self> {
+        let builder = match port {
+            465 => SmtpTransport::relay(host)?,
+            // Local relays and mail catchers usually don't speak TLS at all
+            25 | 1025 => SmtpTransport::builder_dangerous(host),
+            _ => SmtpTransport::starttls_relay(host)?,
+        };
+
+        let builder = match credentials {
+            Some((username, password)) => builder.credentials(Credentials::new(username, password)),
+            None => builder,
+        };
+
+        Ok(Self {
+            sender: sender.parse()?,
+            transport: builder.port(port).build(),
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<()> {
+        let message = Message::builder()
+            .from(self.sender.clone())
+            .to(email.to.parse()?)
+            .subject(email.subject.clone())
+            .body(email.body.clone())?;
+
+        self.transport.send(&message)?;
+
+        Ok(())
+    }
+}
+
+// Appends every message as a JSON line, handy for local development
+pub struct FileOutbox {
+    path: PathBuf,
+}
+
+impl FileOutbox {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Mailer for FileOutbox {
+    fn send(&self, email: &Email) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let line = json!({
+            "to": email.to,
+            "subject": email.subject,
+            "body": email.body,
+            "sent_at": chrono::Utc::now().to_rfc3339(),
+        });
+        writeln!(file, "{}", line)?;
+
+        Ok(())
+    }
+}
+
+// Stores every message in an OUTBOX table so tests can read them back
+pub struct SqliteOutbox {
+    connection: Connection,
+}
+
+impl SqliteOutbox {
+    pub fn open(path: &PathBuf) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS "OUTBOX" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "recipient" TEXT NOT NULL,
+                "subject" TEXT NOT NULL,
+                "body" TEXT NOT NULL,
+                "created_at" TEXT NOT NULL,
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+            "#,
+        )?;
+
+        Ok(Self { connection })
+    }
+
+    pub fn messages_for(&self, recipient: &str) -> Result<Vec<Email>> {
+        let mut stmt = self.connection.prepare(
+            r#"SELECT "recipient", "subject", "body" FROM "OUTBOX" WHERE "recipient" = ?1 ORDER BY "id""#,
+        )?;
+
+        let emails = stmt
+            .query_map([recipient], |row| {
+                rusqlite::Result::Ok(Email {
+                    to: row.get(0)?,
+                    subject: row.get(1)?,
+                    body: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Email>>>()?;
+
+        Ok(emails)
+    }
+}
+
+impl Mailer for SqliteOutbox {
+    fn send(&self, email: &Email) -> Result<()> {
+        self.connection.execute(
+            r#"INSERT INTO "OUTBOX" ("recipient", "subject", "body", "created_at") VALUES (?1, ?2, ?3, ?4)"#,
+            (
+                &email.to,
+                &email.subject,
+                &email.body,
+                chrono::Utc::now().to_rfc3339(),
+            ),
+        )?;
+
+        Ok(())
+    }
+}
@@ -1,7 +1,27 @@
+pub mod auth;
+pub mod backup;
 pub mod server_connection_impl;
 pub mod db_driver;
+pub mod error;
+pub mod export;
+pub mod filter;
+pub mod history;
+pub mod import;
+pub mod logging;
+pub mod mailer;
+pub mod monitoring;
+pub mod openapi;
 pub mod rest_api;
-mod filter;
+pub mod policy;
+pub mod seed;
+pub mod settings;
+pub mod storage;
+pub mod table_models;
+pub mod tokens;
+pub mod totp;
+pub mod versioning;
+pub mod views;
 mod password;
+#[cfg(feature = "postgres")]
+mod postgres_conn;
 mod sqlite_conn;
-mod table_models;
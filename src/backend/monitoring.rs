@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{get, web, Error, HttpMessage, HttpRequest, HttpResponse, Responder, ResponseError};
+use futures_util::future::LocalBoxFuture;
+use serde_json::json;
+
+use super::auth::is_metrics_scraper;
+use super::error::{forbidden, redacted, ApiError};
+use super::server_connection_impl::ServerConnection;
+use super::settings::Settings;
+use super::table_models::User;
+
+// Probes and metrics live outside `/api/v1`: they describe the process, not the API,
+// and must not move when the API does.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(healthz).service(readyz).service(metrics);
+}
+
+// The process is up and serving requests
+#[get("/healthz")]
+pub async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(json!({"status": "ok"}))
+}
+
+// The database can be written to and its schema is the one this build expects. Anyone
+// can ask, so why not is only logged.
+#[get("/readyz")]
+pub async fn readyz(settings: web::Data<Settings>) -> impl Responder {
+    let settings = settings.into_inner();
+    let checked = web::block(move || ServerConnection::check_ready(&settings))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|checked| checked);
+
+    match checked {
+        Ok(()) => HttpResponse::Ok().json(json!({"status": "ready"})),
+        Err(e) => {
+            tracing::warn!(error = %redacted(&e), "not ready");
+            HttpResponse::ServiceUnavailable().json(json!({"status": "unavailable"}))
+        }
+    }
+}
+
+// Prometheus text exposition format, for admins and scrapers holding the metrics token
+#[get("/metrics")]
+pub async fn metrics(req: HttpRequest, settings: web::Data<Settings>) -> HttpResponse {
+    let admin = req
+        .extensions()
+        .get::<User>()
+        .is_some_and(|u| u.role.to_lowercase() == "admin");
+    if !admin && !is_metrics_scraper(&settings, req.headers()) {
+        return ApiError::from(forbidden("Only admins and the metrics scraper can do this."))
+            .error_response();
+    }
+
+    let settings = settings.into_inner();
+    let usage = web::block(move || ServerConnection::open(settings)?.usage()).await;
+
+    let mut out = render();
+
+    // Counts that come from the database are left out rather than failing the scrape
+    if let Ok(Ok(usage)) = usage {
+        gauge(
+            &mut out,
+            "student_sys_active_sessions",
+            "Sessions that have not expired.",
+            usage.active_sessions,
+        );
+        gauge(
+            &mut out,
+            "student_sys_enrollments",
+            "Rows in the enrollment table.",
+            usage.enrollments,
+        );
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(out)
+}
+
+// Counts read from the database when `/metrics` is scraped
+pub struct Usage {
+    pub active_sessions: i64,
+    pub enrollments: i64,
+}
+
+const REQUEST_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+const QUERY_BUCKETS: &[f64] = &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter_mut()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+
+        for (bound, count) in self.buckets.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, separator, self.count);
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+// (method, route, status)
+static REQUESTS: Mutex<BTreeMap<(String, String, u16), u64>> = Mutex::new(BTreeMap::new());
+// (method, route)
+static REQUEST_SECONDS: Mutex<BTreeMap<(String, String), Histogram>> = Mutex::new(BTreeMap::new());
+static QUERY_SECONDS: Mutex<BTreeMap<&'static str, Histogram>> = Mutex::new(BTreeMap::new());
+static LOGIN_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+pub fn observe_request(method: &str, route: &str, status: u16, elapsed: Duration) {
+    let (method, route) = (method.to_owned(), route.to_owned());
+
+    if let Ok(mut requests) = REQUESTS.lock() {
+        *requests.entry((method.clone(), route.clone(), status)).or_default() += 1;
+    }
+    if let Ok(mut seconds) = REQUEST_SECONDS.lock() {
+        seconds
+            .entry((method, route))
+            .or_insert_with(|| Histogram::new(REQUEST_BUCKETS))
+            .observe(elapsed);
+    }
+}
+
+// Times one `DbDriver` operation
+pub fn time_query<T>(operation: &'static str, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+
+    if let Ok(mut seconds) = QUERY_SECONDS.lock() {
+        seconds
+            .entry(operation)
+            .or_insert_with(|| Histogram::new(QUERY_BUCKETS))
+            .observe(started.elapsed());
+    }
+
+    result
+}
+
+pub fn login_failed() {
+    LOGIN_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+fn render() -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP student_sys_http_requests_total HTTP requests by method, route and status.\n");
+    out.push_str("# TYPE student_sys_http_requests_total counter\n");
+    if let Ok(requests) = REQUESTS.lock() {
+        for ((method, route, status), count) in requests.iter() {
+            let _ = writeln!(
+                out,
+                "student_sys_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                method,
+                escape(route),
+                status,
+                count
+            );
+        }
+    }
+
+    out.push_str("# HELP student_sys_http_request_duration_seconds Time spent handling HTTP requests.\n");
+    out.push_str("# TYPE student_sys_http_request_duration_seconds histogram\n");
+    if let Ok(seconds) = REQUEST_SECONDS.lock() {
+        for ((method, route), histogram) in seconds.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+            histogram.write(&mut out, "student_sys_http_request_duration_seconds", &labels);
+        }
+    }
+
+    out.push_str("# HELP student_sys_db_query_duration_seconds Time spent in database operations.\n");
+    out.push_str("# TYPE student_sys_db_query_duration_seconds histogram\n");
+    if let Ok(seconds) = QUERY_SECONDS.lock() {
+        for (operation, histogram) in seconds.iter() {
+            let labels = format!("operation=\"{}\"", operation);
+            histogram.write(&mut out, "student_sys_db_query_duration_seconds", &labels);
+        }
+    }
+
+    out.push_str("# HELP student_sys_login_failures_total Failed sign-in attempts since the server started.\n");
+    out.push_str("# TYPE student_sys_login_failures_total counter\n");
+    let _ = writeln!(
+        out,
+        "student_sys_login_failures_total {}",
+        LOGIN_FAILURES.load(Ordering::Relaxed)
+    );
+
+    out
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: i64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Counts and times every request by its route pattern (`/api/v1/courses/{id}`), so ids
+// in the path don't each get their own series.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| String::from("unmatched"));
+
+        Box::pin(async move {
+            let res = service.call(req).await;
+            let status = match &res {
+                Ok(res) => res.status().as_u16(),
+                Err(e) => e.as_response_error().status_code().as_u16(),
+            };
+
+            observe_request(&method, &route, status, started.elapsed());
+            res
+        })
+    }
+}
@@ -0,0 +1,1241 @@
+use serde_json::{json, Map, Value};
+
+use super::versioning::V1;
+
+// The OpenAPI document is built from `ROUTES` and `schemas()` below. Both are checked
+// against the handlers and DTOs by `tests/openapi.rs`, so a route or field that changes
+// without its entry here fails the build.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Access {
+    Public,
+    Session,
+    Admin,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum In {
+    Header,
+    Query,
+}
+
+#[derive(Clone, Copy)]
+pub enum Type {
+    Str,
+    Int,
+    Bool,
+}
+
+pub struct Param {
+    pub location: In,
+    pub name: &'static str,
+    pub kind: Type,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+// What a successful call returns
+pub enum Returns {
+    Message,
+    Schema(&'static str),
+    ListOf(&'static str),
+    // Raw bytes in the listed media types
+    File(&'static [&'static str]),
+}
+
+pub struct Route {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub handler: &'static str,
+    pub tag: &'static str,
+    pub summary: &'static str,
+    pub access: Access,
+    pub params: &'static [Param],
+    // Media type of the request body, if it has one
+    pub body: Option<&'static str>,
+    pub returns: Returns,
+}
+
+const fn header(
+    name: &'static str,
+    kind: Type,
+    required: bool,
+    description: &'static str,
+) -> Param {
+    Param {
+        location: In::Header,
+        name,
+        kind,
+        required,
+        description,
+    }
+}
+
+const fn query(name: &'static str, kind: Type, required: bool, description: &'static str) -> Param {
+    Param {
+        location: In::Query,
+        name,
+        kind,
+        required,
+        description,
+    }
+}
+
+const COURSE_FIELDS: &[Param] = &[
+    header("name", Type::Str, true, "Course title"),
+    header(
+        "course_nr",
+        Type::Str,
+        true,
+        "Catalogue number, e.g. COS 3100",
+    ),
+    header("id", Type::Int, true, "User id of the teacher"),
+    header("cr_cost", Type::Int, true, "Credits, greater than zero"),
+    header("timeslots", Type::Str, true, "Meeting times, e.g. MW 10:00"),
+    header("description", Type::Str, true, "Free text"),
+    header("capacity", Type::Int, false, "Seats per term, 0 for no limit; kept when omitted on update"),
+];
+
+const CREDENTIALS: &[Param] = &[
+    header("login_email", Type::Str, true, "Account email"),
+    header("login_password", Type::Str, true, "Account password"),
+];
+
+pub const ROUTES: &[Route] = &[
+    Route {
+        method: "get",
+        path: "/",
+        handler: "index",
+        tag: "meta",
+        summary: "Liveness check",
+        access: Access::Public,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("Success"),
+    },
+    Route {
+        method: "get",
+        path: "/openapi.json",
+        handler: "openapi_json",
+        tag: "meta",
+        summary: "This document",
+        access: Access::Public,
+        params: &[],
+        body: None,
+        returns: Returns::File(&["application/json"]),
+    },
+    Route {
+        method: "get",
+        path: "/docs",
+        handler: "api_docs",
+        tag: "meta",
+        summary: "Interactive API reference",
+        access: Access::Public,
+        params: &[],
+        body: None,
+        returns: Returns::File(&["text/html"]),
+    },
+    Route {
+        method: "post",
+        path: "/login",
+        handler: "login",
+        tag: "auth",
+        summary: "Sign in; the session token comes back in the `session_token` response header",
+        access: Access::Public,
+        params: &[
+            header("login_email", Type::Str, true, "Account email"),
+            header("login_password", Type::Str, true, "Account password"),
+            header("totp_code", Type::Str, false, "Authenticator or recovery code, when 2FA is on"),
+        ],
+        body: None,
+        returns: Returns::Schema("UserResponse"),
+    },
+    Route {
+        method: "get",
+        path: "/logout",
+        handler: "logout",
+        tag: "auth",
+        summary: "End a session",
+        access: Access::Public,
+        params: &[
+            header("session_token", Type::Str, false, "The session to end"),
+            header("login_email", Type::Str, false, "Rejected without a session token"),
+            header("login_password", Type::Str, false, "Rejected without a session token"),
+        ],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/register",
+        handler: "register",
+        tag: "auth",
+        summary: "Create a student account",
+        access: Access::Public,
+        params: &[
+            header("username", Type::Str, true, "Display name"),
+            header("email", Type::Str, true, "Must match the allowed email domains"),
+            header("password", Type::Str, true, "Must satisfy the password policy"),
+            header("phone", Type::Str, false, "Normalised to E.164"),
+        ],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/admin/register",
+        handler: "register_admin",
+        tag: "auth",
+        summary: "Create an admin account",
+        access: Access::Admin,
+        params: &[
+            header("access_code", Type::Str, true, "Shared admin access code"),
+            header("username", Type::Str, true, "Display name"),
+            header("email", Type::Str, true, "Must match the allowed email domains"),
+            header("password", Type::Str, true, "Must satisfy the password policy"),
+            header("phone", Type::Str, false, "Normalised to E.164"),
+        ],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "get",
+        path: "/verify",
+        handler: "verify",
+        tag: "auth",
+        summary: "Confirm an email address with the token from the verification mail",
+        access: Access::Public,
+        params: &[query("token", Type::Str, true, "Token from the verification link")],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/verify/resend",
+        handler: "resend_verification",
+        tag: "auth",
+        summary: "Send the verification mail again",
+        access: Access::Public,
+        params: CREDENTIALS,
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/password/forgot",
+        handler: "forgot_password",
+        tag: "auth",
+        summary: "Mail a password reset link, if the account exists",
+        access: Access::Public,
+        params: &[header("email", Type::Str, true, "Account email")],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/password/reset",
+        handler: "reset_password",
+        tag: "auth",
+        summary: "Set a new password with a reset token",
+        access: Access::Public,
+        params: &[
+            header("token", Type::Str, true, "Token from the reset link"),
+            header("password", Type::Str, true, "Must satisfy the password policy"),
+        ],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/account/2fa/enroll",
+        handler: "enroll_two_factor",
+        tag: "two-factor",
+        summary: "Start setting up an authenticator app",
+        access: Access::Public,
+        params: CREDENTIALS,
+        body: None,
+        returns: Returns::Schema("TwoFactorSecret"),
+    },
+    Route {
+        method: "post",
+        path: "/account/2fa/confirm",
+        handler: "confirm_two_factor",
+        tag: "two-factor",
+        summary: "Finish setup with a first code; returns the recovery codes",
+        access: Access::Public,
+        params: &[
+            header("login_email", Type::Str, true, "Account email"),
+            header("login_password", Type::Str, true, "Account password"),
+            header("totp_code", Type::Str, true, "Current authenticator code"),
+        ],
+        body: None,
+        returns: Returns::Schema("RecoveryCodes"),
+    },
+    Route {
+        method: "post",
+        path: "/account/2fa/recovery-codes",
+        handler: "regenerate_recovery_codes",
+        tag: "two-factor",
+        summary: "Replace all recovery codes",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("RecoveryCodes"),
+    },
+    Route {
+        method: "delete",
+        path: "/account/2fa",
+        handler: "disable_two_factor",
+        tag: "two-factor",
+        summary: "Turn off two-factor authentication",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "delete",
+        path: "/admin/users/{id}/2fa",
+        handler: "reset_two_factor",
+        tag: "two-factor",
+        summary: "Turn off two-factor authentication for a user",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "get",
+        path: "/admin/2fa",
+        handler: "get_two_factor_policy",
+        tag: "two-factor",
+        summary: "Roles that must use two-factor authentication",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("TwoFactorPolicy"),
+    },
+    Route {
+        method: "patch",
+        path: "/admin/2fa",
+        handler: "set_two_factor_policy",
+        tag: "two-factor",
+        summary: "Set the roles that must use two-factor authentication",
+        access: Access::Admin,
+        params: &[header("roles", Type::Str, true, "Comma-separated roles; empty for none")],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "get",
+        path: "/users",
+        handler: "get_users",
+        tag: "users",
+        summary: "All users, as much as the caller may see of each",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("UserResponse"),
+    },
+    Route {
+        method: "get",
+        path: "/students",
+        handler: "get_students",
+        tag: "users",
+        summary: "All students",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("UserResponse"),
+    },
+    Route {
+        method: "get",
+        path: "/teachers",
+        handler: "get_teachers",
+        tag: "users",
+        summary: "All teachers",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("UserResponse"),
+    },
+    Route {
+        method: "get",
+        path: "/account",
+        handler: "get_self",
+        tag: "users",
+        summary: "The signed-in user with their role-specific records",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("Account"),
+    },
+    Route {
+        method: "get",
+        path: "/account/history",
+        handler: "get_own_history",
+        tag: "users",
+        summary: "The signed-in student's grades term by term, with GPAs, credits and standing",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("AcademicHistory"),
+    },
+    Route {
+        method: "get",
+        path: "/students/{id}/history",
+        handler: "get_student_history",
+        tag: "users",
+        summary: "A student's academic history; for their advisor and admins",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("AcademicHistory"),
+    },
+    Route {
+        method: "get",
+        path: "/students/{id}/standing",
+        handler: "get_student_standing",
+        tag: "users",
+        summary: "Standings decided for a student at each term close, oldest first; for the student, their advisor and admins",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("StandingRecord"),
+    },
+    Route {
+        method: "get",
+        path: "/students/{id}/holds",
+        handler: "get_holds",
+        tag: "users",
+        summary: "Holds placed on a student, newest first, released ones included; for the student, their advisor and admins",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("Hold"),
+    },
+    Route {
+        method: "get",
+        path: "/account/notifications",
+        handler: "get_notifications",
+        tag: "users",
+        summary: "The signed-in user's notifications, newest first",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("Notification"),
+    },
+    Route {
+        method: "patch",
+        path: "/account",
+        handler: "update_self",
+        tag: "users",
+        summary: "Edit your own profile; omitted headers keep their value",
+        access: Access::Session,
+        params: &[
+            header("username", Type::Str, false, "Display name"),
+            header("email", Type::Str, false, "Must match the allowed email domains"),
+            header("password", Type::Str, false, "Must satisfy the password policy"),
+            header("phone", Type::Str, false, "Normalised to E.164"),
+        ],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "get",
+        path: "/admin",
+        handler: "admin",
+        tag: "users",
+        summary: "Succeeds for admins",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "patch",
+        path: "/admin/users/{id}",
+        handler: "update_user",
+        tag: "users",
+        summary: "Edit any user; omitted headers keep their value",
+        access: Access::Admin,
+        params: &[
+            header("username", Type::Str, false, "Display name"),
+            header("email", Type::Str, false, "Must match the allowed email domains"),
+            header("password", Type::Str, false, "Must satisfy the password policy"),
+            header("phone", Type::Str, false, "Normalised to E.164"),
+            header("role", Type::Str, false, "student, teacher or admin"),
+            header("verified", Type::Bool, false, "Email confirmed"),
+            header("suspended", Type::Bool, false, "Blocks sign-in"),
+            header("forcenewpw", Type::Bool, false, "Require a password change at next sign-in"),
+        ],
+        body: None,
+        returns: Returns::Schema("UserResponse"),
+    },
+    Route {
+        method: "delete",
+        path: "/admin/users/{id}",
+        handler: "delete_user",
+        tag: "users",
+        summary: "Delete a user",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "get",
+        path: "/departments",
+        handler: "get_departments",
+        tag: "departments",
+        summary: "All departments",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("Departments"),
+    },
+    Route {
+        method: "get",
+        path: "/departments/{id}",
+        handler: "get_department",
+        tag: "departments",
+        summary: "One department. The id is read from the `id` header, not the path.",
+        access: Access::Session,
+        params: &[header("id", Type::Int, true, "Department id")],
+        body: None,
+        returns: Returns::Schema("Departments"),
+    },
+    Route {
+        method: "post",
+        path: "/departments",
+        handler: "new_department",
+        tag: "departments",
+        summary: "Create a department",
+        access: Access::Admin,
+        params: &[header("name", Type::Str, true, "Department name")],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "delete",
+        path: "/departments/{id}",
+        handler: "delete_department",
+        tag: "departments",
+        summary: "Delete a department",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/admin/department/{id}",
+        handler: "invite_to_department",
+        tag: "departments",
+        summary: "Assign a teacher to a department",
+        access: Access::Admin,
+        params: &[header("teacher_id", Type::Int, true, "User id of the teacher")],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "delete",
+        path: "/admin/department/{id}",
+        handler: "kick_from_department",
+        tag: "departments",
+        summary: "Remove a teacher from a department",
+        access: Access::Admin,
+        params: &[header("teacher_id", Type::Int, true, "User id of the teacher")],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "get",
+        path: "/courses",
+        handler: "get_courses",
+        tag: "courses",
+        summary: "The course catalogue with teachers and departments",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("CourseCatalog"),
+    },
+    Route {
+        method: "get",
+        path: "/courses/{id}",
+        handler: "get_course",
+        tag: "courses",
+        summary: "One course with its teacher, department and seats this term; the teacher and admins also get the roster",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("CourseDetail"),
+    },
+    Route {
+        method: "post",
+        path: "/courses",
+        handler: "new_course",
+        tag: "courses",
+        summary: "Create a course; teachers may only create their own",
+        access: Access::Session,
+        params: COURSE_FIELDS,
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "patch",
+        path: "/courses/{id}",
+        handler: "update_course",
+        tag: "courses",
+        summary: "Replace a course's details",
+        access: Access::Session,
+        params: COURSE_FIELDS,
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "delete",
+        path: "/courses/{id}",
+        handler: "remove_course",
+        tag: "courses",
+        summary: "Delete a course",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/enroll/{id}",
+        handler: "enroll",
+        tag: "courses",
+        summary: "Enroll the signed-in student in a course",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("UserResponse"),
+    },
+    Route {
+        method: "post",
+        path: "/unenroll/{id}",
+        handler: "unenroll",
+        tag: "courses",
+        summary: "Drop a course",
+        access: Access::Session,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("UserResponse"),
+    },
+    Route {
+        method: "get",
+        path: "/admin/lockouts",
+        handler: "get_lockouts",
+        tag: "admin",
+        summary: "Sign-in throttling state per account and address",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("LoginAttempt"),
+    },
+    Route {
+        method: "delete",
+        path: "/admin/lockouts/{id}",
+        handler: "clear_lockout",
+        tag: "admin",
+        summary: "Lift a lockout",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/admin/terms/{id}/close",
+        handler: "close_term",
+        tag: "admin",
+        summary: "Decide every enrolled student's standing from the term's grades; fails while any grade is missing",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("TermClosing"),
+    },
+    Route {
+        method: "post",
+        path: "/admin/students/{id}/holds",
+        handler: "place_hold",
+        tag: "admin",
+        summary: "Place a hold that keeps the student from registering, from their transcript, or both",
+        access: Access::Admin,
+        params: &[
+            header("kind", Type::Str, true, "fees, documents, advising, conduct or other"),
+            header("reason", Type::Str, true, "Shown to the student"),
+            header("blocks", Type::Str, false, "registration (default), transcripts or both"),
+        ],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/admin/holds/{id}/release",
+        handler: "release_hold",
+        tag: "admin",
+        summary: "Release a hold; it stays on record",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "get",
+        path: "/admin/backups",
+        handler: "list_snapshots",
+        tag: "admin",
+        summary: "Database snapshots, newest first",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::ListOf("Snapshot"),
+    },
+    Route {
+        method: "post",
+        path: "/admin/backups",
+        handler: "take_snapshot",
+        tag: "admin",
+        summary: "Snapshot the live database, then prune by the retention policy",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("Snapshot"),
+    },
+    Route {
+        method: "post",
+        path: "/admin/backups/{name}/verify",
+        handler: "verify_snapshot",
+        tag: "admin",
+        summary: "Integrity and schema version check of a snapshot",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("Verification"),
+    },
+    Route {
+        method: "post",
+        path: "/admin/backups/{name}/restore",
+        handler: "restore_snapshot",
+        tag: "admin",
+        summary: "Replace the database with a verified snapshot. The current state is snapshotted first.",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Message,
+    },
+    Route {
+        method: "post",
+        path: "/admin/import/{kind}",
+        handler: "import_csv",
+        tag: "admin",
+        summary: "Import users, courses or enrollments from CSV; every row or none. 422 carries the report when any row fails.",
+        access: Access::Admin,
+        params: &[query("dry_run", Type::Bool, false, "Validate without writing")],
+        body: Some("text/csv"),
+        returns: Returns::Schema("ImportReport"),
+    },
+    Route {
+        method: "get",
+        path: "/admin/stats",
+        handler: "get_stats",
+        tag: "admin",
+        summary: "Counts of users, students, teachers, courses and departments",
+        access: Access::Admin,
+        params: &[],
+        body: None,
+        returns: Returns::Schema("Statistics"),
+    },
+    Route {
+        method: "get",
+        path: "/admin/export/{table}",
+        handler: "export_table",
+        tag: "admin",
+        summary: "Stream a table. Any other query parameter filters on the column of that name.",
+        access: Access::Admin,
+        params: &[query("format", Type::Str, false, "csv (default), jsonl or xlsx")],
+        body: None,
+        returns: Returns::File(&[
+            "text/csv",
+            "application/x-ndjson",
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ]),
+    },
+];
+
+fn type_name(kind: Type) -> &'static str {
+    match kind {
+        Type::Str => "string",
+        Type::Int => "integer",
+        Type::Bool => "boolean",
+    }
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({"$ref": format!("#/components/schemas/{}", name)})
+}
+
+// `{"type": "object"}` with every listed property required
+fn object(properties: &[(&str, Value)]) -> Value {
+    let names: Vec<&str> = properties.iter().map(|(n, _)| *n).collect();
+    let properties: Map<String, Value> = properties
+        .iter()
+        .map(|(n, v)| (n.to_string(), v.clone()))
+        .collect();
+
+    json!({"type": "object", "properties": properties, "required": names})
+}
+
+fn string() -> Value {
+    json!({"type": "string"})
+}
+
+fn integer() -> Value {
+    json!({"type": "integer"})
+}
+
+fn number() -> Value {
+    json!({"type": "number"})
+}
+
+fn boolean() -> Value {
+    json!({"type": "boolean"})
+}
+
+fn list(items: Value) -> Value {
+    json!({"type": "array", "items": items})
+}
+
+// One entry per DTO that appears in a response body
+pub fn schemas() -> Map<String, Value> {
+    let mut s = Map::new();
+
+    s.insert("Error".into(), {
+        let mut e = object(&[("code", string()), ("error", string())]);
+        e["properties"]["details"] = list(schema_ref("FieldError"));
+        e
+    });
+    s.insert(
+        "FieldError".into(),
+        object(&[
+            ("field", string()),
+            ("code", string()),
+            ("message", string()),
+        ]),
+    );
+    s.insert("Message".into(), object(&[("message", string())]));
+    s.insert("Success".into(), object(&[("success", boolean())]));
+    s.insert("UserView".into(), {
+        let mut u = object(&[
+            ("id", integer()),
+            ("username", string()),
+            ("role", string()),
+        ]);
+        u["properties"]["email"] = string();
+        u
+    });
+    s.insert(
+        "AdminUserView".into(),
+        object(&[
+            ("id", integer()),
+            ("username", string()),
+            ("email", string()),
+            ("phone", string()),
+            ("verified", boolean()),
+            ("suspended", boolean()),
+            ("forcenewpw", boolean()),
+            ("role", string()),
+        ]),
+    );
+    s.insert(
+        "UserResponse".into(),
+        json!({"oneOf": [schema_ref("AdminUserView"), schema_ref("UserView")]}),
+    );
+    s.insert(
+        "Courses".into(),
+        object(&[
+            ("id", integer()),
+            ("teacher_id", integer()),
+            ("course", string()),
+            ("course_nr", string()),
+            ("description", string()),
+            ("cr_cost", integer()),
+            ("timeslots", string()),
+            ("capacity", integer()),
+        ]),
+    );
+    s.insert(
+        "Departments".into(),
+        object(&[("id", integer()), ("name", string())]),
+    );
+    s.insert(
+        "TeacherAccount".into(),
+        object(&[
+            ("id", integer()),
+            ("teacher_id", integer()),
+            ("dept_id", integer()),
+        ]),
+    );
+    s.insert(
+        "StudentAccount".into(),
+        object(&[
+            ("id", integer()),
+            ("student_id", integer()),
+            ("advisor_id", integer()),
+            ("discipline", string()),
+            ("enrollment", string()),
+            ("cgpa", number()),
+            ("can_grad", boolean()),
+            ("cur_credit", integer()),
+            ("cum_credit", integer()),
+        ]),
+    );
+    s.insert(
+        "StudentCourse".into(),
+        object(&[
+            ("student_id", integer()),
+            ("course_id", integer()),
+            ("grade", number()),
+            ("semester", string()),
+        ]),
+    );
+    s.insert(
+        "LoginAttempt".into(),
+        object(&[
+            ("id", integer()),
+            ("scope", string()),
+            ("key", string()),
+            ("failures", integer()),
+            ("last_failure", integer()),
+            ("locked_until", integer()),
+        ]),
+    );
+    s.insert(
+        "Statistics".into(),
+        object(&[
+            ("registered_users", integer()),
+            ("suspended_users", integer()),
+            ("faculty_members", integer()),
+            ("active_students", integer()),
+            ("graduated_students", integer()),
+            ("courses", integer()),
+            ("departments", integer()),
+        ]),
+    );
+    s.insert(
+        "Snapshot".into(),
+        object(&[
+            ("name", string()),
+            ("taken_at", json!({"type": "string", "format": "date-time"})),
+            ("size_bytes", integer()),
+        ]),
+    );
+    s.insert(
+        "Verification".into(),
+        object(&[
+            ("name", string()),
+            ("ok", boolean()),
+            ("schema_version", integer()),
+            ("problems", list(string())),
+        ]),
+    );
+    s.insert(
+        "RowReport".into(),
+        object(&[
+            ("line", integer()),
+            ("ok", boolean()),
+            ("errors", list(schema_ref("FieldError"))),
+        ]),
+    );
+    s.insert(
+        "ImportReport".into(),
+        object(&[
+            (
+                "kind",
+                json!({"type": "string", "enum": ["users", "courses", "enrollments"]}),
+            ),
+            ("dry_run", boolean()),
+            ("committed", boolean()),
+            ("total", integer()),
+            ("failed", integer()),
+            ("rows", list(schema_ref("RowReport"))),
+        ]),
+    );
+    s.insert(
+        "CourseCatalog".into(),
+        object(&[
+            ("courses", list(schema_ref("Courses"))),
+            ("users", list(schema_ref("UserResponse"))),
+            ("teacher_accounts", list(schema_ref("TeacherAccount"))),
+            ("departments", list(schema_ref("Departments"))),
+        ]),
+    );
+    s.insert("CourseDetail".into(), {
+        let mut c = object(&[
+            ("course", schema_ref("Courses")),
+            ("user", schema_ref("UserResponse")),
+            ("teacher_account", schema_ref("TeacherAccount")),
+            ("department", json!({"allOf": [schema_ref("Departments")], "nullable": true})),
+            ("semester", string()),
+            ("enrolled", integer()),
+            ("remaining_seats", json!({"type": "integer", "nullable": true})),
+        ]);
+        c["properties"]["roster"] = list(schema_ref("RosterEntry"));
+        c
+    });
+    s.insert(
+        "RosterEntry".into(),
+        object(&[("student", schema_ref("UserResponse")), ("grade", number())]),
+    );
+    s.insert(
+        "AcademicHistory".into(),
+        object(&[
+            ("student_id", integer()),
+            ("terms", list(schema_ref("TermRecord"))),
+            ("cumulative", schema_ref("Totals")),
+        ]),
+    );
+    s.insert(
+        "TermRecord".into(),
+        object(&[
+            ("term", string()),
+            ("starts_on", json!({"type": "string", "format": "date", "nullable": true})),
+            ("ends_on", json!({"type": "string", "format": "date", "nullable": true})),
+            ("courses", list(schema_ref("CourseRecord"))),
+            ("totals", schema_ref("Totals")),
+            ("cumulative", schema_ref("Totals")),
+            (
+                "standing",
+                json!({"type": "string", "enum": ["good", "deans_list", "probation", "suspension", "in_progress"]}),
+            ),
+        ]),
+    );
+    s.insert(
+        "CourseRecord".into(),
+        object(&[
+            ("course_id", integer()),
+            ("course_nr", string()),
+            ("course", string()),
+            ("credits", integer()),
+            ("grade", json!({"type": "number", "nullable": true})),
+            ("repeated", boolean()),
+            ("replaced", boolean()),
+        ]),
+    );
+    s.insert(
+        "Totals".into(),
+        object(&[
+            ("attempted", integer()),
+            ("earned", integer()),
+            ("in_progress", integer()),
+            ("quality_points", number()),
+            ("gpa", json!({"type": "number", "nullable": true})),
+        ]),
+    );
+    s.insert(
+        "StandingRecord".into(),
+        object(&[
+            ("id", integer()),
+            ("student_id", integer()),
+            ("term", string()),
+            (
+                "standing",
+                json!({"type": "string", "enum": ["good", "deans_list", "probation", "suspension"]}),
+            ),
+            ("term_gpa", number()),
+            ("cumulative_gpa", number()),
+            ("decided_at", integer()),
+        ]),
+    );
+    s.insert(
+        "TermClosing".into(),
+        object(&[
+            ("term", string()),
+            ("students", integer()),
+            ("decisions", list(schema_ref("StandingRecord"))),
+        ]),
+    );
+    s.insert(
+        "Notification".into(),
+        object(&[
+            ("id", integer()),
+            ("user_id", integer()),
+            ("subject", string()),
+            ("body", string()),
+            ("created_at", integer()),
+        ]),
+    );
+    s.insert("Hold".into(), {
+        let mut h = object(&[
+            ("id", integer()),
+            ("student_id", integer()),
+            (
+                "kind",
+                json!({"type": "string", "enum": ["fees", "documents", "advising", "conduct", "other"]}),
+            ),
+            ("reason", string()),
+            ("placed_by", integer()),
+            ("placed_at", integer()),
+            ("blocks_registration", boolean()),
+            ("blocks_transcripts", boolean()),
+            ("released_by", integer()),
+            ("released_at", integer()),
+        ]);
+        h["description"] = json!("`released_by` and `released_at` are 0 while the hold is in force.");
+        h
+    });
+    s.insert("Account".into(), {
+        let mut a = object(&[("user", schema_ref("AdminUserView"))]);
+        a["description"] = json!("Students also get `enrollments`, `standing` and `courses`; teachers get `teacher_account`.");
+        a["properties"]["enrollments"] = list(schema_ref("StudentCourse"));
+        a["properties"]["standing"] = schema_ref("StudentAccount");
+        a["properties"]["courses"] = list(schema_ref("Courses"));
+        a["properties"]["teacher_account"] = schema_ref("TeacherAccount");
+        a
+    });
+    s.insert(
+        "TwoFactorSecret".into(),
+        object(&[("secret", string()), ("otpauth_uri", string())]),
+    );
+    s.insert(
+        "RecoveryCodes".into(),
+        object(&[("recovery_codes", list(string()))]),
+    );
+    s.insert(
+        "TwoFactorPolicy".into(),
+        object(&[("required_roles", list(string()))]),
+    );
+
+    s
+}
+
+fn parameters(route: &Route) -> Vec<Value> {
+    let path_params = route
+        .path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            let kind = match name {
+                "kind" | "table" | "name" => "string",
+                _ => "integer",
+            };
+            json!({"name": name, "in": "path", "required": true, "schema": {"type": kind}})
+        });
+
+    let params = route.params.iter().map(|p| {
+        json!({
+            "name": p.name,
+            "in": if p.location == In::Header { "header" } else { "query" },
+            "required": p.required,
+            "description": p.description,
+            "schema": {"type": type_name(p.kind)},
+        })
+    });
+
+    path_params.chain(params).collect()
+}
+
+fn operation(route: &Route) -> Value {
+    let success = match &route.returns {
+        Returns::Message => json!({"application/json": {"schema": schema_ref("Message")}}),
+        Returns::Schema(name) => json!({"application/json": {"schema": schema_ref(name)}}),
+        Returns::ListOf(name) => json!({"application/json": {"schema": list(schema_ref(name))}}),
+        Returns::File(types) => types
+            .iter()
+            .map(|t| {
+                (
+                    t.to_string(),
+                    json!({"schema": {"type": "string", "format": "binary"}}),
+                )
+            })
+            .collect::<Map<String, Value>>()
+            .into(),
+    };
+
+    let error = json!({"description": "Failed", "content": {"application/json": {"schema": schema_ref("Error")}}});
+    let mut responses = json!({
+        "200": {"description": "Success", "content": success},
+        "default": error,
+    });
+
+    if route.access != Access::Public {
+        responses["401"] = json!({"description": "Not signed in", "content": error["content"]});
+    }
+    if route.access == Access::Admin {
+        responses["403"] = json!({"description": "Admins only", "content": error["content"]});
+    }
+
+    let mut op = json!({
+        "operationId": route.handler,
+        "tags": [route.tag],
+        "summary": route.summary,
+        "parameters": parameters(route),
+        "responses": responses,
+    });
+
+    if route.access == Access::Public {
+        op["security"] = json!([]);
+    }
+
+    if let Some(media_type) = route.body {
+        op["requestBody"] = json!({
+            "required": true,
+            "content": {media_type: {"schema": {"type": "string"}}},
+        });
+    }
+
+    op
+}
+
+pub fn spec() -> Value {
+    let mut paths = Map::new();
+
+    for route in ROUTES {
+        let item = paths
+            .entry(route.path.to_string())
+            .or_insert_with(|| json!({}));
+        item[route.method] = operation(route);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Student Management System",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Request data is passed in headers. Routes that aren't public accept either a `session_token` header from `POST /login`, or `login_email` and `login_password` headers (plus `totp_code` when two-factor authentication is on).",
+        },
+        // The same paths are still served without the prefix, with `Deprecation` and
+        // `Sunset` headers, until the configured sunset date
+        "servers": [{"url": V1}],
+        "security": [{"session": []}, {"email": [], "password": []}],
+        "components": {
+            "securitySchemes": {
+                "session": {"type": "apiKey", "in": "header", "name": "session_token"},
+                "email": {"type": "apiKey", "in": "header", "name": "login_email"},
+                "password": {"type": "apiKey", "in": "header", "name": "login_password"},
+            },
+            "schemas": schemas(),
+        },
+        "paths": paths,
+    })
+}
+
+// Redoc is loaded from its CDN and renders `/api/v1/openapi.json` in the browser
+pub const DOCS_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Student Management System API</title>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+  </head>
+  <body>
+    <redoc spec-url="/api/v1/openapi.json"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/v2.1.5/bundles/redoc.standalone.js"></script>
+  </body>
+</html>
+"#;
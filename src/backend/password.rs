@@ -1,19 +1,25 @@
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    Algorithm, Argon2, Params, Version,
 };
 
+use super::settings::HashingSettings;
+
 pub fn generate_salt() -> SaltString {
     SaltString::generate(&mut OsRng)
 }
 
-pub fn hash(password: &str, salt: SaltString) -> String {
-    let password_hash = Argon2::default()
+pub fn hash(password: &str, salt: SaltString, params: &HashingSettings) -> String {
+    let params = Params::new(params.memory_kib, params.iterations, params.parallelism, None)
+        .expect("Hashing parameters are checked when the settings are loaded.");
+
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
         .hash_password(password.as_bytes(), &salt)
         .unwrap();
     password_hash.to_string()
 }
 
+// The parameters are read from the stored hash, so older hashes keep verifying
 pub fn verify(password_hash: &str, password: &str) -> bool {
     let parsed_hash = PasswordHash::new(password_hash).unwrap();
     Argon2::default()
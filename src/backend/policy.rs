@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Ok, Result};
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+// The `[registration]` section of the config file. Every key is optional and the
+// defaults match the rules the system has always enforced.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistrationPolicy {
+    // An empty list accepts any domain
+    pub email_domains: Vec<String>,
+    pub phone: PhonePolicy,
+    pub password: PasswordPolicy,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhonePolicy {
+    pub required: bool,
+    // Numbers written in national format (leading 0) are prefixed with this code
+    pub default_country_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_special: bool,
+    pub special_characters: String,
+    // A local file with one known-breached password per line
+    pub breached_list: Option<PathBuf>,
+    #[serde(skip)]
+    breached: HashSet<String>,
+}
+
+impl Default for RegistrationPolicy {
+    fn default() -> Self {
+        Self {
+            email_domains: vec![String::from("aubg.edu")],
+            phone: PhonePolicy::default(),
+            password: PasswordPolicy::default(),
+        }
+    }
+}
+
+impl Default for PhonePolicy {
+    fn default() -> Self {
+        Self {
+            required: false,
+            default_country_code: Some(String::from("359")),
+        }
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_special: true,
+            special_characters: String::from("@$!%*?&"),
+            breached_list: None,
+            breached: HashSet::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+// Every rule a submission broke, so clients can point at the offending fields at once
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &str, code: &str, message: &str) {
+        self.errors.push(FieldError {
+            field: field.to_owned(),
+            code: code.to_owned(),
+            message: message.to_owned(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self.into())
+        }
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<&str> = self.errors.iter().map(|e| e.message.as_str()).collect();
+        write!(f, "{}", messages.join(" "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl RegistrationPolicy {
+    // Reads the breached password list, if one is configured
+    pub fn prepare(&mut self) -> Result<()> {
+        if let Some(path) = &self.password.breached_list {
+            let contents = fs::read_to_string(path).map_err(|e| {
+                anyhow!("Cannot read breached password list {}: {}", path.display(), e)
+            })?;
+
+            self.password.breached = contents
+                .lines()
+                .map(|l| l.trim().to_lowercase())
+                .filter(|l| !l.is_empty())
+                .collect();
+        }
+
+        if self.password.min_length > self.password.max_length {
+            return Err(anyhow!(
+                "registration.password.min_length cannot exceed max_length."
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Returns the normalised (lowercase) address
+    pub fn check_email(&self, email: &str, errors: &mut ValidationErrors) -> String {
+        let email = email.trim().to_lowercase();
+        let email_regex = Regex::new(r"^([a-z0-9_+]([a-z0-9_+.]*[a-z0-9_+])?)@([a-z0-9-]+\.)+[a-z]{2,}$")
+            .expect("Email pattern is valid.");
+
+        if email.is_empty() {
+            errors.add("email", "required", "Email cannot be empty.");
+        } else if !email_regex.is_match(&email) {
+            errors.add("email", "invalid_format", "Must be a valid email address.");
+        } else {
+            let domain = email.rsplit('@').next().unwrap_or_default();
+
+            if !self.email_domains.is_empty()
+                && !self.email_domains.iter().any(|d| d.eq_ignore_ascii_case(domain))
+            {
+                errors.add(
+                    "email",
+                    "domain_not_allowed",
+                    &format!(
+                        "Email must belong to one of: {}.",
+                        self.email_domains.join(", ")
+                    ),
+                );
+            }
+        }
+
+        email
+    }
+
+    // Returns the number in E.164 form, or an empty string when none was given
+    pub fn check_phone(&self, phone: &str, errors: &mut ValidationErrors) -> String {
+        let digits: String = phone
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+            .collect();
+
+        if digits.is_empty() {
+            if self.phone.required {
+                errors.add("phone", "required", "Phone number cannot be empty.");
+            }
+            return digits;
+        }
+
+        let normalised = if digits.starts_with('+') {
+            digits
+        } else if let Some(rest) = digits.strip_prefix("00") {
+            format!("+{}", rest)
+        } else if let (Some(code), Some(national)) =
+            (&self.phone.default_country_code, digits.strip_prefix('0'))
+        {
+            format!("+{}{}", code.trim_start_matches('+'), national)
+        } else {
+            format!("+{}", digits)
+        };
+
+        let e164 = Regex::new(r"^\+[1-9][0-9]{6,14}$").expect("E.164 pattern is valid.");
+
+        if !e164.is_match(&normalised) {
+            errors.add(
+                "phone",
+                "invalid_format",
+                "Phone number must be in international format, e.g. +359888123456.",
+            );
+        }
+
+        normalised
+    }
+
+    pub fn check_password(&self, password: &str, errors: &mut ValidationErrors) {
+        let rules = &self.password;
+        let length = password.chars().count();
+
+        if length < rules.min_length {
+            errors.add(
+                "password",
+                "too_short",
+                &format!("Password must be at least {} characters long.", rules.min_length),
+            );
+        }
+
+        if length > rules.max_length {
+            errors.add(
+                "password",
+                "too_long",
+                &format!("Password must be at most {} characters long.", rules.max_length),
+            );
+        }
+
+        if rules.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+            errors.add(
+                "password",
+                "missing_lowercase",
+                "Password must contain at least 1 lowercase letter.",
+            );
+        }
+
+        if rules.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+            errors.add(
+                "password",
+                "missing_uppercase",
+                "Password must contain at least 1 uppercase letter.",
+            );
+        }
+
+        if rules.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            errors.add(
+                "password",
+                "missing_digit",
+                "Password must contain at least 1 number.",
+            );
+        }
+
+        if rules.require_special && !password.chars().any(|c| rules.special_characters.contains(c)) {
+            errors.add(
+                "password",
+                "missing_special",
+                &format!(
+                    "Password must contain at least 1 special character ({}).",
+                    rules.special_characters
+                ),
+            );
+        }
+
+        if rules.breached.contains(&password.to_lowercase()) {
+            errors.add(
+                "password",
+                "breached",
+                "This password has appeared in a data breach. Choose a different one.",
+            );
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use std::cell::RefCell;
+
+use anyhow::{anyhow, Result};
+use postgres::fallible_iterator::FallibleIterator;
+use postgres::types::Type;
+use postgres::{Client, NoTls, Row};
+use serde_json::Value;
+
+use super::error::bad_request;
+use super::filter::Filter;
+use super::storage::{select, RowVisitor, Storage, RECOMPUTE_STANDING, SCHEMA_VERSION};
+
+// Held while the schema is created, so servers starting together don't race each other
+const SCHEMA_LOCK: i64 = 0x5354_5544_5359;
+
+// The same tables as the SQLite schema, with unquoted names that fold to lowercase, and
+// the triggers rewritten as PL/pgSQL functions that behave the same way
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_version (
+        version INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS users (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        username TEXT NOT NULL,
+        password TEXT NOT NULL,
+        email TEXT NOT NULL UNIQUE,
+        phone TEXT,
+        verified BOOLEAN NOT NULL,
+        suspended BOOLEAN NOT NULL,
+        forcenewpw BOOLEAN NOT NULL,
+        role TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS student_account (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        student_id INTEGER NOT NULL UNIQUE REFERENCES users (id),
+        advisor_id INTEGER NOT NULL,
+        discipline TEXT NOT NULL,
+        enrollment TEXT NOT NULL,
+        cgpa DOUBLE PRECISION NOT NULL,
+        can_grad BOOLEAN NOT NULL,
+        cur_credit INTEGER NOT NULL,
+        cum_credit INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS teacher_account (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        teacher_id INTEGER NOT NULL UNIQUE REFERENCES users (id),
+        dept_id INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS courses (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        teacher_id INTEGER NOT NULL REFERENCES users (id),
+        course TEXT NOT NULL,
+        course_nr TEXT NOT NULL,
+        description TEXT,
+        cr_cost INTEGER NOT NULL,
+        timeslots TEXT NOT NULL,
+        capacity INTEGER NOT NULL DEFAULT 0
+    );
+
+    ALTER TABLE courses ADD COLUMN IF NOT EXISTS capacity INTEGER NOT NULL DEFAULT 0;
+
+    CREATE TABLE IF NOT EXISTS student_courses (
+        student_id INTEGER NOT NULL REFERENCES users (id),
+        course_id INTEGER NOT NULL REFERENCES courses (id),
+        grade DOUBLE PRECISION NOT NULL,
+        semester TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS departments (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS sessions (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        user_id INTEGER NOT NULL REFERENCES users (id),
+        token_hash TEXT NOT NULL UNIQUE,
+        created_at BIGINT NOT NULL,
+        expires_at BIGINT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS password_resets (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        user_id INTEGER NOT NULL REFERENCES users (id),
+        token_hash TEXT NOT NULL UNIQUE,
+        expires_at BIGINT NOT NULL,
+        used BOOLEAN NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS two_factor (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        user_id INTEGER NOT NULL UNIQUE REFERENCES users (id),
+        secret TEXT NOT NULL,
+        enabled BOOLEAN NOT NULL,
+        last_step BIGINT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS recovery_codes (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        user_id INTEGER NOT NULL REFERENCES users (id),
+        code_hash TEXT NOT NULL,
+        used BOOLEAN NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS system_settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS login_attempts (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        scope TEXT NOT NULL,
+        key TEXT NOT NULL,
+        failures INTEGER NOT NULL,
+        last_failure BIGINT NOT NULL,
+        locked_until BIGINT NOT NULL,
+        UNIQUE (scope, key)
+    );
+
+    CREATE TABLE IF NOT EXISTS terms (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        starts_on TEXT NOT NULL,
+        ends_on TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS standing_history (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        student_id INTEGER NOT NULL REFERENCES users (id),
+        term TEXT NOT NULL,
+        standing TEXT NOT NULL,
+        term_gpa DOUBLE PRECISION NOT NULL,
+        cumulative_gpa DOUBLE PRECISION NOT NULL,
+        decided_at BIGINT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS notifications (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        user_id INTEGER NOT NULL REFERENCES users (id),
+        subject TEXT NOT NULL,
+        body TEXT NOT NULL,
+        created_at BIGINT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS holds (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        student_id INTEGER NOT NULL REFERENCES users (id),
+        kind TEXT NOT NULL,
+        reason TEXT NOT NULL,
+        placed_by INTEGER NOT NULL,
+        placed_at BIGINT NOT NULL,
+        blocks_registration BOOLEAN NOT NULL,
+        blocks_transcripts BOOLEAN NOT NULL,
+        released_by INTEGER NOT NULL,
+        released_at BIGINT NOT NULL
+    );
+
+    -- SQLite's INSERT OR REPLACE deletes the old account row, so the account starts over
+    -- with a new id whenever a student or teacher is created or changes role
+    CREATE OR REPLACE FUNCTION manage_accounts() RETURNS trigger AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD.role IS NOT DISTINCT FROM NEW.role THEN
+            RETURN NULL;
+        END IF;
+
+        IF NEW.role = 'student' THEN
+            DELETE FROM student_account WHERE student_id = NEW.id;
+            INSERT INTO student_account (student_id, advisor_id, discipline,
+            enrollment, can_grad, cgpa, cur_credit, cum_credit)
+            VALUES (NEW.id, 0, '', '', FALSE, 0.0, 0, 0);
+            DELETE FROM teacher_account WHERE teacher_id = NEW.id;
+        ELSIF NEW.role = 'teacher' THEN
+            DELETE FROM teacher_account WHERE teacher_id = NEW.id;
+            INSERT INTO teacher_account (teacher_id, dept_id) VALUES (NEW.id, 0);
+            DELETE FROM student_account WHERE student_id = NEW.id;
+        ELSIF TG_OP = 'INSERT' AND NEW.role = 'admin' THEN
+            DELETE FROM student_account WHERE student_id = NEW.id;
+            DELETE FROM teacher_account WHERE teacher_id = NEW.id;
+        END IF;
+        RETURN NULL;
+    END
+    $$ LANGUAGE plpgsql;
+
+    DROP TRIGGER IF EXISTS manage_accounts ON users;
+    CREATE TRIGGER manage_accounts
+    AFTER INSERT OR UPDATE ON users
+    FOR EACH ROW EXECUTE FUNCTION manage_accounts();
+
+    -- Runs before the delete: the foreign key checks would otherwise see these rows first
+    CREATE OR REPLACE FUNCTION clear_user_rows() RETURNS trigger AS $$
+    BEGIN
+        DELETE FROM student_account WHERE student_id = OLD.id;
+        DELETE FROM teacher_account WHERE teacher_id = OLD.id;
+        DELETE FROM sessions WHERE user_id = OLD.id;
+        DELETE FROM password_resets WHERE user_id = OLD.id;
+        DELETE FROM two_factor WHERE user_id = OLD.id;
+        DELETE FROM recovery_codes WHERE user_id = OLD.id;
+        DELETE FROM standing_history WHERE student_id = OLD.id;
+        DELETE FROM notifications WHERE user_id = OLD.id;
+        DELETE FROM holds WHERE student_id = OLD.id;
+        RETURN OLD;
+    END
+    $$ LANGUAGE plpgsql;
+
+    DROP TRIGGER IF EXISTS clear_user_rows ON users;
+    CREATE TRIGGER clear_user_rows
+    BEFORE DELETE ON users
+    FOR EACH ROW EXECUTE FUNCTION clear_user_rows();
+
+    CREATE INDEX IF NOT EXISTS student_courses_by_student ON student_courses (student_id);
+    CREATE INDEX IF NOT EXISTS standing_history_by_student ON standing_history (student_id);
+    CREATE INDEX IF NOT EXISTS notifications_by_user ON notifications (user_id);
+    CREATE INDEX IF NOT EXISTS holds_by_student ON holds (student_id);
+
+    -- Before version 3 asking for a course again took another seat; the first enrollment
+    -- is the one kept
+    DELETE FROM student_courses later USING student_courses earlier
+    WHERE later.ctid > earlier.ctid
+        AND later.student_id = earlier.student_id
+        AND later.course_id = earlier.course_id
+        AND later.semester = earlier.semester;
+
+    CREATE UNIQUE INDEX IF NOT EXISTS student_courses_once
+    ON student_courses (student_id, course_id, semester);
+
+    CREATE OR REPLACE FUNCTION update_student_cgpa() RETURNS trigger AS $$
+    DECLARE
+        changed INTEGER;
+    BEGIN
+        IF TG_OP = 'DELETE' THEN
+            changed := OLD.student_id;
+        ELSE
+            changed := NEW.student_id;
+        END IF;
+
+        UPDATE student_account
+        SET cgpa = COALESCE((
+            SELECT SUM(CASE WHEN grade >= 0 THEN grade * cr_cost ELSE 0 END)
+                / NULLIF(SUM(CASE WHEN grade >= 0 THEN cr_cost ELSE 0 END), 0)
+            FROM student_courses
+            JOIN courses ON student_courses.course_id = courses.id
+            WHERE student_courses.student_id = changed
+        ), 0.0),
+        can_grad = COALESCE((
+            SELECT SUM(CASE WHEN grade >= 0 THEN cr_cost ELSE 0 END)
+            FROM student_courses
+            JOIN courses ON student_courses.course_id = courses.id
+            WHERE student_courses.student_id = changed
+        ), 0) >= 120
+        WHERE student_id = changed;
+        RETURN NULL;
+    END
+    $$ LANGUAGE plpgsql;
+
+    DROP TRIGGER IF EXISTS update_student_cgpa ON student_courses;
+    CREATE TRIGGER update_student_cgpa
+    AFTER INSERT OR UPDATE OR DELETE ON student_courses
+    FOR EACH ROW EXECUTE FUNCTION update_student_cgpa();
+"#;
+
+// One connection per `PostgresConnection`. TLS isn't supported, so the server should
+// reach the database over a private network or a local socket.
+pub struct PostgresConnection {
+    // Reads need `&mut Client`, but `Storage` reads through `&self`
+    client: RefCell<Client>,
+}
+
+impl PostgresConnection {
+    pub fn connect(url: &str) -> Result<Self> {
+        let client = Client::connect(url, NoTls)?;
+
+        Ok(Self {
+            client: RefCell::new(client),
+        })
+    }
+}
+
+impl Storage for PostgresConnection {
+    fn execute(&mut self, sql: &str) -> Result<()> {
+        self.client.get_mut().execute(sql, &[])?;
+
+        Ok(())
+    }
+
+    fn query(&self, sql: &str, f: &mut RowVisitor) -> Result<usize> {
+        let mut client = self.client.borrow_mut();
+        let mut rows = client.query_raw(sql, std::iter::empty::<i32>())?;
+        let mut columns: Option<Vec<String>> = None;
+        let mut count = 0;
+
+        while let Some(row) = rows.next()? {
+            let columns = columns.get_or_insert_with(|| {
+                row.columns().iter().map(|c| c.name().to_owned()).collect()
+            });
+            let values = (0..columns.len())
+                .map(|i| value(&row, i))
+                .collect::<Result<Vec<Value>>>()?;
+
+            f(columns, values)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn columns(&self, table: &str) -> Result<Vec<String>> {
+        let stmt = self
+            .client
+            .borrow_mut()
+            .prepare(&format!("SELECT * FROM {} LIMIT 0", table))?;
+
+        Ok(stmt.columns().iter().map(|c| c.name().to_owned()).collect())
+    }
+
+    fn create_tables(&mut self) -> Result<()> {
+        // Checked first, so opening an up-to-date database doesn't take the lock
+        if self.schema_version()? >= SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        // A database from a newer build keeps its version, so readiness checks notice it
+        self.client.get_mut().batch_execute(&format!(
+            "BEGIN;
+            SELECT pg_advisory_xact_lock({lock});
+            {schema}
+            {recompute}
+            DELETE FROM schema_version WHERE version < {version};
+            INSERT INTO schema_version (version)
+            SELECT {version} WHERE NOT EXISTS (SELECT 1 FROM schema_version);
+            COMMIT;",
+            lock = SCHEMA_LOCK,
+            schema = SCHEMA,
+            recompute = RECOMPUTE_STANDING,
+            version = SCHEMA_VERSION
+        ))?;
+
+        Ok(())
+    }
+
+    // Kept in the `schema_version` table; 0 before the schema is created
+    fn schema_version(&self) -> Result<i32> {
+        let mut client = self.client.borrow_mut();
+
+        let exists: bool = client
+            .query_one("SELECT to_regclass('schema_version') IS NOT NULL", &[])?
+            .get(0);
+        if !exists {
+            return Ok(0);
+        }
+
+        let version: Option<i32> = client
+            .query_one("SELECT MAX(version) FROM schema_version", &[])?
+            .get(0);
+
+        Ok(version.unwrap_or(0))
+    }
+
+    fn begin(&mut self) -> Result<()> {
+        self.client.get_mut().batch_execute("BEGIN")?;
+
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.client.get_mut().batch_execute("COMMIT")?;
+
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<()> {
+        self.client.get_mut().batch_execute("ROLLBACK")?;
+
+        Ok(())
+    }
+
+    // Other transactions wait for the rows until this one commits or rolls back
+    fn lock(&mut self, table: &str, filters: &[Filter]) -> Result<()> {
+        let sql = format!("{} FOR UPDATE", select(table, filters, None));
+        self.query(&sql, &mut |_, _| Ok(()))?;
+
+        Ok(())
+    }
+
+    // Fails on read-only servers such as hot standbys
+    fn check_writable(&mut self) -> Result<()> {
+        let client = self.client.get_mut();
+        client.batch_execute("BEGIN")?;
+
+        let written = client.execute(
+            "INSERT INTO system_settings (key, value) VALUES ('readiness_probe', '')
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            &[],
+        );
+
+        client.batch_execute("ROLLBACK")?;
+        written?;
+
+        Ok(())
+    }
+
+    fn integrity_check(&self) -> Result<Vec<String>> {
+        Err(bad_request(
+            "PostgreSQL has no built-in integrity check. Use the amcheck extension.",
+        ))
+    }
+
+    fn vacuum(&mut self) -> Result<()> {
+        self.client.get_mut().batch_execute("VACUUM ANALYZE")?;
+
+        Ok(())
+    }
+}
+
+// Converts a column to what SQLite would return for it, so both backends export the same
+fn value(row: &Row, i: usize) -> Result<Value> {
+    let column = &row.columns()[i];
+
+    let value = match *column.type_() {
+        Type::INT2 => row.try_get::<_, Option<i16>>(i)?.map(Value::from),
+        Type::INT4 => row.try_get::<_, Option<i32>>(i)?.map(Value::from),
+        Type::INT8 => row.try_get::<_, Option<i64>>(i)?.map(Value::from),
+        Type::FLOAT4 => row.try_get::<_, Option<f32>>(i)?.map(|r| Value::from(r as f64)),
+        Type::FLOAT8 => row.try_get::<_, Option<f64>>(i)?.map(Value::from),
+        Type::BOOL => row.try_get::<_, Option<bool>>(i)?.map(|b| Value::from(b as i64)),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+            row.try_get::<_, Option<String>>(i)?.map(Value::from)
+        }
+        ref other => {
+            return Err(anyhow!(
+                "Column {} has type {}, which is not supported.",
+                column.name(),
+                other
+            ))
+        }
+    };
+
+    Ok(value.unwrap_or(Value::Null))
+}
@@ -1,103 +1,249 @@
-use actix_web::{delete, get, patch, post, HttpRequest, HttpResponse, Responder};
+use std::io::Write;
+
+use actix_web::{
+    delete, get, http::header::HeaderValue, patch, post, web, HttpMessage, HttpRequest,
+    HttpResponse, Responder, ResponseError,
+};
+use futures_util::stream;
+use serde_derive::Deserialize;
 use serde_json::{json, Value};
+use tokio::sync::mpsc;
 
-use crate::backend::table_models::{User, TeacherAccount};
+use crate::backend::table_models::{Hold, StudentCourse, User};
 use crate::login_macro as login;
 
 use super::{
+    error::{bad_request, conflict, forbidden, not_found, redacted, unauthenticated, ApiError},
+    export::{ExportFormat, ExportRequest},
     filter::{Filter, UsersFilter},
+    import::ImportKind,
+    openapi,
     server_connection_impl::*,
+    settings::Settings,
     table_models::Courses,
 };
 
+#[derive(Deserialize)]
+pub struct VerifyQuery {
+    token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+// Every handler works on its own connection, configured from the shared settings
+fn connect(req: &HttpRequest) -> ServerConnection {
+    let settings = req
+        .app_data::<web::Data<Settings>>()
+        .expect("Settings must be registered as app data.")
+        .clone()
+        .into_inner();
+
+    let mut conn = ServerConnection::new(settings);
+    conn.set_client_ip(req.peer_addr().map(|a| a.ip().to_string()));
+
+    // Signed in by the auth middleware
+    if let Some(user) = req.extensions().get::<User>() {
+        tracing::Span::current().record("user_id", user.id);
+        conn.set_session(user.clone());
+    }
+
+    conn
+}
+
+// Sends a failure with the status and body its `ApiError` kind calls for
+fn fail(e: impl Into<anyhow::Error>) -> HttpResponse {
+    ApiError::from(e.into()).error_response()
+}
+
+// Header values that aren't visible ASCII, like a password with an umlaut, can't be read
+fn header_text(value: &HeaderValue) -> anyhow::Result<String> {
+    value
+        .to_str()
+        .map(str::to_owned)
+        .map_err(|_| bad_request("Header values must be visible ASCII text."))
+}
+
+// Each endpoint is listed once: `routes` mounts what `HANDLERS` names, so the list the
+// OpenAPI document is checked against is the routing table itself
+macro_rules! endpoints {
+    ($($handler:ident),* $(,)?) => {
+        pub const HANDLERS: &[(&str, fn(&mut web::ServiceConfig))] = &[$(
+            (stringify!($handler), |cfg| {
+                cfg.service($handler);
+            }),
+        )*];
+    };
+}
+
+endpoints! {
+    index,
+    openapi_json,
+    api_docs,
+    get_users,
+    get_students,
+    get_teachers,
+    get_departments,
+    get_department,
+    new_department,
+    delete_department,
+    invite_to_department,
+    kick_from_department,
+    get_courses,
+    get_course,
+    new_course,
+    update_course,
+    remove_course,
+    update_user,
+    delete_user,
+    get_self,
+    get_own_history,
+    get_student_history,
+    get_student_standing,
+    get_holds,
+    get_notifications,
+    update_self,
+    admin,
+    enroll,
+    unenroll,
+    login,
+    logout,
+    register,
+    register_admin,
+    verify,
+    resend_verification,
+    forgot_password,
+    reset_password,
+    enroll_two_factor,
+    confirm_two_factor,
+    regenerate_recovery_codes,
+    disable_two_factor,
+    reset_two_factor,
+    get_two_factor_policy,
+    set_two_factor_policy,
+    get_lockouts,
+    clear_lockout,
+    close_term,
+    place_hold,
+    release_hold,
+    list_snapshots,
+    take_snapshot,
+    verify_snapshot,
+    restore_snapshot,
+    import_csv,
+    get_stats,
+    export_table,
+}
+
+// Every endpoint, for the server and for tests that need the real routing table
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    for (_, mount) in HANDLERS {
+        mount(cfg);
+    }
+}
+
 #[get("/")]
 pub async fn index() -> impl Responder {
     HttpResponse::Ok().json(json!({"success": true}))
 }
 
+#[get("/openapi.json")]
+pub async fn openapi_json() -> impl Responder {
+    HttpResponse::Ok().json(openapi::spec())
+}
+
+#[get("/docs")]
+pub async fn api_docs() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(openapi::DOCS_PAGE)
+}
+
 #[get("/users")]
-pub async fn get_users() -> impl Responder {
-    let conn = ServerConnection::new();
+pub async fn get_users(req: HttpRequest) -> impl Responder {
+    let conn = connect(&req);
     let users = conn.get_users();
     match users {
         Ok(u) => {
-            let json = serde_json::to_string(&u);
+            let json = serde_json::to_string(&conn.view_users(&u));
             match json {
                 Ok(j) => HttpResponse::Ok().body(j),
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Err(e) => fail(e),
             }
         }
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/students")]
-pub async fn get_students() -> impl Responder {
-    let conn = ServerConnection::new();
+pub async fn get_students(req: HttpRequest) -> impl Responder {
+    let conn = connect(&req);
     let students = conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Role(
         "student".to_string(),
     ))]);
     match students {
         Ok(s) => {
-            let json = serde_json::to_string(&s);
+            let json = serde_json::to_string(&conn.view_users(&s));
             match json {
                 Ok(j) => HttpResponse::Ok().body(j),
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Err(e) => fail(e),
             }
         }
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/teachers")]
-pub async fn get_teachers() -> impl Responder {
-    let conn = ServerConnection::new();
+pub async fn get_teachers(req: HttpRequest) -> impl Responder {
+    let conn = connect(&req);
     let teachers = conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Role(
         "teacher".to_string(),
     ))]);
     match teachers {
         Ok(t) => {
-            let json = serde_json::to_string(&t);
+            let json = serde_json::to_string(&conn.view_users(&t));
             match json {
                 Ok(j) => HttpResponse::Ok().body(j),
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Err(e) => fail(e),
             }
         }
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/departments")]
-pub async fn get_departments() -> impl Responder {
-    let conn = ServerConnection::new();
+pub async fn get_departments(req: HttpRequest) -> impl Responder {
+    let conn = connect(&req);
     let departments = conn.get_departments();
     match departments {
         Ok(d) => {
             let json = serde_json::to_string(&d);
             match json {
                 Ok(j) => HttpResponse::Ok().body(j),
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Err(e) => fail(e),
             }
         }
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/departments/{id}")]
 pub async fn get_department(req: HttpRequest) -> impl Responder {
-    let conn = ServerConnection::new();
+    let conn = connect(&req);
     let request_headers = req.headers();
-    let id = match request_headers.get("id") {
-        Some(id) => id,
-        None => return HttpResponse::BadRequest().json(json!({"error": "Missing department id."})),
-    }
-    .to_str()
-    .unwrap();
+    let id = match request_headers.get("id").map(header_text) {
+        Some(Ok(id)) => id,
+        Some(Err(e)) => return fail(e),
+        None => return fail(bad_request("Missing department id.")),
+    };
 
     let id = match id.parse::<i32>() {
         Ok(id) => id,
         Err(_) => {
-            return HttpResponse::BadRequest().json(json!({"error": "Invalid department id."}))
+            return fail(bad_request("Invalid department id."))
         }
     };
 
@@ -107,102 +253,98 @@ pub async fn get_department(req: HttpRequest) -> impl Responder {
             let json = serde_json::to_string(&d);
             match json {
                 Ok(j) => HttpResponse::Ok().body(j),
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Err(e) => fail(e),
             }
         }
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[post("/departments")]
 pub async fn new_department(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
     if !conn.is_admin() {
-        return HttpResponse::Unauthorized().json(json!({"error": "Unauthorized"}));
+        return fail(forbidden("Only admins can do this."));
     }
 
-    let department = request_headers.get("name").unwrap().to_str().unwrap();
+    let department = match request_headers.get("name").map(header_text) {
+        Some(Ok(name)) => name,
+        Some(Err(e)) => return fail(e),
+        None => return fail(bad_request("Missing department name.")),
+    };
 
-    let department = conn.new_department(department);
+    let department = conn.new_department(&department);
     match department {
         Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully created department."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[delete("/departments/{id}")]
 pub async fn delete_department(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
     if !conn.is_admin() {
-        return HttpResponse::Unauthorized().json(json!({"error": "Unauthorized"}));
+        return fail(forbidden("Only admins can do this."));
     }
 
-    let department = req.match_info().get("id").unwrap_or_else(|| "0");
+    let department = req.match_info().get("id").unwrap_or("0");
     let department = department.parse::<i32>().unwrap_or_default();
 
     if department == 0 {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing department id."}));
+        return fail(bad_request("Missing department id."));
     }
 
     let department = conn.get_department(department);
     let department = match department {
         Ok(d) => d,
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     let department = conn.remove_department(department);
     match department {
         Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully deleted department."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[post("/admin/department/{id}")]
 pub async fn invite_to_department(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
     if !conn.is_admin() {
-        return HttpResponse::Unauthorized().json(json!({"error": "Unauthorized"}));
+        return fail(forbidden("Only admins can do this."));
     }
 
-    let department = req.match_info().get("id").unwrap();
+    let department = req.match_info().get("id").unwrap_or("0");
     let department = department.parse::<i32>().unwrap_or_default();
 
     if department == 0 {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing department id."}));
+        return fail(bad_request("Missing department id."));
     }
 
-    let teacher = request_headers.get("teacher_id");
-
-    if teacher.is_none() {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing teacher id."}));
-    }
-
-    let teacher = teacher.unwrap().to_str().unwrap();
+    let teacher = match request_headers.get("teacher_id").map(header_text) {
+        Some(Ok(teacher)) => teacher,
+        Some(Err(e)) => return fail(e),
+        None => return fail(bad_request("Missing teacher id.")),
+    };
     let teacher = teacher.parse::<i32>().unwrap_or_default();
 
     if teacher == 0 {
-        return HttpResponse::BadRequest().json(json!({"error": "Invalid teacher id."}));
+        return fail(bad_request("Invalid teacher id."));
     }
 
     let department = conn.get_department(department);
     let department = match department {
         Ok(d) => d,
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     let teachers = conn.get_teacher_accounts();
@@ -213,11 +355,11 @@ pub async fn invite_to_department(req: HttpRequest) -> impl Responder {
             .collect::<Vec<_>>()
         {
             v if v.is_empty() => {
-                return HttpResponse::BadRequest().json(json!({"error": "Teacher not found."}))
+                return fail(not_found("Teacher not found."))
             }
             v => v[0].to_owned(),
         },
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     teacher.dept_id = department.id;
@@ -227,39 +369,36 @@ pub async fn invite_to_department(req: HttpRequest) -> impl Responder {
     match invitation {
         Ok(_) => HttpResponse::Ok()
             .json(json!({"message": "Successfully invited teacher to department."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[delete("/admin/department/{id}")]
 pub async fn kick_from_department(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
     if !conn.is_admin() {
-        return HttpResponse::Unauthorized().json(json!({"error": "Unauthorized"}));
+        return fail(forbidden("Only admins can do this."));
     }
 
-    let department = req.match_info().get("id").unwrap();
+    let department = req.match_info().get("id").unwrap_or("0");
     let department = department.parse::<i32>().unwrap_or_default();
 
     if department == 0 {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing department id."}));
+        return fail(bad_request("Missing department id."));
     }
 
-    let teacher = request_headers.get("teacher_id");
-    if teacher.is_none() {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing teacher id."}));
-    }
-
-    let teacher = teacher.unwrap().to_str().unwrap();
+    let teacher = match request_headers.get("teacher_id").map(header_text) {
+        Some(Ok(teacher)) => teacher,
+        Some(Err(e)) => return fail(e),
+        None => return fail(bad_request("Missing teacher id.")),
+    };
     let teacher = teacher.parse::<i32>().unwrap_or_default();
 
     if teacher == 0 {
-        return HttpResponse::BadRequest().json(json!({"error": "Invalid teacher id."}));
+        return fail(bad_request("Invalid teacher id."));
     }
 
     let teachers = conn.get_teacher_accounts();
@@ -270,11 +409,11 @@ pub async fn kick_from_department(req: HttpRequest) -> impl Responder {
             .collect::<Vec<_>>()
         {
             v if v.is_empty() => {
-                return HttpResponse::BadRequest().json(json!({"error": "Teacher not found."}))
+                return fail(not_found("Teacher not found."))
             }
             v => v[0].to_owned(),
         },
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     teacher.dept_id = 0;
@@ -284,13 +423,13 @@ pub async fn kick_from_department(req: HttpRequest) -> impl Responder {
     match invitation {
         Ok(_) => HttpResponse::Ok()
             .json(json!({"message": "Successfully kicked teacher from department."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/courses")]
-pub async fn get_courses() -> impl Responder {
-    let conn = ServerConnection::new();
+pub async fn get_courses(req: HttpRequest) -> impl Responder {
+    let conn = connect(&req);
 
     let courses = match conn.search_courses("".to_string()) {
         Ok(c) => {
@@ -298,28 +437,26 @@ pub async fn get_courses() -> impl Responder {
             match json {
                 Ok(j) => j,
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": e.to_string()}))
+                    return fail(e)
                 }
             }
         }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     let users = match conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Role(
         "teacher".to_string(),
     ))]) {
         Ok(t) => {
-            let json = serde_json::to_string(&t);
+            let json = serde_json::to_string(&conn.view_users(&t));
             match json {
                 Ok(j) => j,
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": e.to_string()}))
+                    return fail(e)
                 }
             }
         }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     let departments = match conn.get_departments() {
@@ -328,12 +465,11 @@ pub async fn get_courses() -> impl Responder {
             match json {
                 Ok(j) => j,
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": e.to_string()}))
+                    return fail(e)
                 }
             }
         }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     let teacher_accounts = match conn.get_teacher_accounts() {
@@ -342,12 +478,11 @@ pub async fn get_courses() -> impl Responder {
             match json {
                 Ok(j) => j,
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": e.to_string()}))
+                    return fail(e)
                 }
             }
         }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => return fail(e),
     };
 
     let json_prep = format!(
@@ -357,167 +492,64 @@ pub async fn get_courses() -> impl Responder {
 
     match serde_json::from_str::<Value>(&json_prep) {
         Ok(json3) => HttpResponse::Ok().json(json3),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/courses/{id}")]
 pub async fn get_course(req: HttpRequest) -> impl Responder {
-    let conn = ServerConnection::new();
-    let id = req.match_info().get("id").unwrap_or_else(|| "0");
-
-    if id == "0" {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing course id."}));
-    }
-
-    let course = match conn.search_courses(id.to_string()) {
-        Ok(c) => c[0].to_owned(),
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
-    };
-
-    let user = match conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Role(
-        "teacher".to_string(),
-    ))]) {
-        Ok(t) => {
-            let u = t
-                .iter()
-                .filter_map(|u| {
-                    if course.teacher_id == u.id {
-                        Some(u.to_owned())
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            match u.get(0) {
-                Some(u) => u.to_owned(),
-                None => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": "A teacher with this ID does not exist."}))
-                }
-            }
-        }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
-    };
-
-    let teacher_account = match conn.get_teacher_accounts() {
-        Ok(t) => {
-            let a = t
-                .into_iter()
-                .filter_map(|t| {
-                    if course.teacher_id == t.teacher_id {
-                        Some(t.to_owned())
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            match a.get(0) {
-                Some(a) => a.to_owned(),
-                None => {
-                    return HttpResponse::InternalServerError().json(
-                        json!({"error": "A teacher account with this Teacher ID does not exist."}),
-                    )
-                }
-            }
-        }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+    let conn = connect(&req);
+    let id = match req.match_info().get("id").map(str::parse::<i32>) {
+        Some(Ok(id)) if id != 0 => id,
+        _ => return fail(bad_request("Missing course id.")),
     };
 
-    let departments = match conn.get_departments() {
-        Ok(d) => {
-            let dep = d
-                .into_iter()
-                .filter_map(|d| {
-                    if teacher_account.dept_id == d.id {
-                        Some(d)
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            match dep.get(0) {
-                Some(d) => d.to_owned(),
-                None => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": "A department with such ID does not exist."}))
-                }
-            }
-        }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
-    };
-
-    let course = serde_json::to_string(&course).unwrap();
-    let user = serde_json::to_string(&user).unwrap();
-    let teacher_account = serde_json::to_string(&teacher_account).unwrap();
-    let departments = serde_json::to_string(&departments).unwrap();
-
-    let json_prep = format!(
-        "{{\"course\": {}, \"user\": {}, \"teacher_account\": {}, \"department\": {}}}",
-        course, user, teacher_account, departments
-    );
-
-    match serde_json::from_str::<Value>(&json_prep) {
-        Ok(j) => HttpResponse::Ok().json(j),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+    match conn.get_course_detail(id) {
+        Ok(c) => HttpResponse::Ok().json(c),
+        Err(e) => fail(e),
     }
 }
 
 #[post("/courses")]
 pub async fn new_course(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-    login!(login_email, login_password, conn);
-
-    let course = request_headers.get("name");
-    let description = request_headers.get("description");
-    let course_nr = request_headers.get("course_nr");
-    let teacher_id = request_headers.get("id");
-    let cr_cost = request_headers.get("cr_cost");
-    let timeslots = request_headers.get("timeslots");
-
-    if course.is_none()
-        || teacher_id.is_none()
-        || course_nr.is_none()
-        || cr_cost.is_none()
-        || timeslots.is_none()
-    {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing required data."}));
-    }
-
-    let course = course.unwrap().to_str().unwrap().to_string();
-    let description = description
-        .unwrap()
-        .to_str()
+    login!(request_headers, conn);
+
+    let (Some(course), Some(course_nr), Some(teacher_id), Some(cr_cost), Some(timeslots)) = (
+        request_headers.get("name"),
+        request_headers.get("course_nr"),
+        request_headers.get("id"),
+        request_headers.get("cr_cost"),
+        request_headers.get("timeslots"),
+    ) else {
+        return fail(bad_request("Missing required data."));
+    };
+
+    let [Ok(course), Ok(course_nr), Ok(teacher_id), Ok(cr_cost), Ok(timeslots)] =
+        [course, course_nr, teacher_id, cr_cost, timeslots].map(header_text)
+    else {
+        return fail(bad_request("Header values must be visible ASCII text."));
+    };
+    let description = request_headers
+        .get("description")
+        .and_then(|d| d.to_str().ok())
         .unwrap_or("No description.")
         .to_string();
-    let course_nr = course_nr.unwrap().to_str().unwrap().to_string();
-    let teacher_id = teacher_id
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .parse::<i32>()
-        .unwrap_or(0);
-    let cr_cost = cr_cost
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .parse::<i32>()
-        .unwrap_or(0);
-    let timeslots = timeslots.unwrap().to_str().unwrap().to_string();
+    let teacher_id = teacher_id.parse::<i32>().unwrap_or(0);
+    let cr_cost = cr_cost.parse::<i32>().unwrap_or(0);
+    let capacity = match parse_capacity(request_headers.get("capacity")) {
+        Ok(c) => c.unwrap_or(0),
+        Err(e) => return fail(e),
+    };
 
     if teacher_id == 0 {
-        return HttpResponse::BadRequest().json(json!({"error": "Invalid teacher id."}));
+        return fail(bad_request("Invalid teacher id."));
     }
 
     if cr_cost == 0 {
-        return HttpResponse::BadRequest().json(json!({"error": "Invalid course cost."}));
+        return fail(bad_request("Invalid course cost."));
     }
 
     let course = Courses {
@@ -528,236 +560,237 @@ pub async fn new_course(req: HttpRequest) -> impl Responder {
         course_nr,
         cr_cost,
         timeslots,
+        capacity,
     };
 
     match conn.register_courses(vec![course]) {
         Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully registered course."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[delete("/courses/{id}")]
 pub async fn remove_course(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let id = req.match_info().get("id").unwrap();
+    let id = req.match_info().get("id").unwrap_or_default();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
 
     let find_course = conn.search_courses(id.to_string());
 
     match find_course {
         Ok(c) => {
-            if c.len() == 0 {
-                return HttpResponse::BadRequest().json(json!({"error": "Course not found."}));
+            if c.is_empty() {
+                return fail(not_found("Course not found."));
             }
 
             if c.len() > 1 {
-                return HttpResponse::InternalServerError()
-                    .json(json!({"error": "Multiple courses found."}));
+                return fail(conflict("Multiple courses found."));
             }
 
-            let course = c.get(0).unwrap().clone();
+            let course = c.first().unwrap().clone();
 
             match conn.remove_courses(vec![course]) {
                 Ok(_) => {
                     HttpResponse::Ok().json(json!({"message": "Successfully removed course."}))
                 }
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Err(e) => fail(e),
             }
         }
 
         Err(e) => {
-            return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+            fail(e)
         }
     }
 }
 
 #[patch("/courses/{id}")]
 pub async fn update_course(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let name = request_headers.get("name");
-    let description = request_headers.get("description");
-    let course_nr = request_headers.get("course_nr");
-    let teacher_id = request_headers.get("id");
-    let cr_cost = request_headers.get("cr_cost");
-    let timeslots = request_headers.get("timeslots");
-
-    let id = req.match_info().get("id").unwrap();
-
-    if teacher_id.is_none()
-        || cr_cost.is_none()
-        || timeslots.is_none()
-        || name.is_none()
-        || course_nr.is_none()
-    {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing data."}));
-    }
-
-    let teacher_id = teacher_id.unwrap().to_str().unwrap();
-    let cr_cost = cr_cost.unwrap().to_str().unwrap();
-    let timeslots = timeslots.unwrap().to_str().unwrap();
-    let name = name.unwrap().to_str().unwrap();
-    let course_nr = course_nr.unwrap().to_str().unwrap().to_string();
-    let description = description
-        .unwrap()
-        .to_str()
+    let id = req.match_info().get("id").unwrap_or_default();
+
+    let (Some(name), Some(course_nr), Some(teacher_id), Some(cr_cost), Some(timeslots)) = (
+        request_headers.get("name"),
+        request_headers.get("course_nr"),
+        request_headers.get("id"),
+        request_headers.get("cr_cost"),
+        request_headers.get("timeslots"),
+    ) else {
+        return fail(bad_request("Missing data."));
+    };
+
+    let [Ok(name), Ok(course_nr), Ok(teacher_id), Ok(cr_cost), Ok(timeslots)] =
+        [name, course_nr, teacher_id, cr_cost, timeslots].map(header_text)
+    else {
+        return fail(bad_request("Header values must be visible ASCII text."));
+    };
+    let description = request_headers
+        .get("description")
+        .and_then(|d| d.to_str().ok())
         .unwrap_or("No description.")
         .to_string();
+    let Ok(teacher_id) = teacher_id.parse::<i32>() else {
+        return fail(bad_request("Invalid teacher id."));
+    };
+    let Ok(cr_cost) = cr_cost.parse::<i32>() else {
+        return fail(bad_request("Invalid course cost."));
+    };
+    let capacity = match parse_capacity(request_headers.get("capacity")) {
+        Ok(c) => c,
+        Err(e) => return fail(e),
+    };
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
 
     let find_course = conn.search_courses(id.to_string());
 
     match find_course {
         Ok(c) => {
-            if c.len() == 0 {
-                return HttpResponse::BadRequest().json(json!({"error": "Course not found."}));
+            if c.is_empty() {
+                return fail(not_found("Course not found."));
             }
 
             if c.len() > 1 {
-                return HttpResponse::InternalServerError()
-                    .json(json!({"error": "Multiple courses found."}));
+                return fail(conflict("Multiple courses found."));
             }
 
-            let mut course = c.get(0).unwrap().clone();
+            let mut course = c.first().unwrap().clone();
 
-            course.course = name.to_string();
+            course.course = name;
             course.description = description;
             course.course_nr = course_nr;
-            course.cr_cost = cr_cost.parse::<i32>().unwrap();
-            course.timeslots = timeslots.to_string();
-            course.teacher_id = teacher_id.parse::<i32>().unwrap();
+            course.cr_cost = cr_cost;
+            course.timeslots = timeslots;
+            course.teacher_id = teacher_id;
+            course.capacity = capacity.unwrap_or(course.capacity);
 
             match conn.update_courses(vec![course]) {
                 Ok(_) => {
                     HttpResponse::Ok().json(json!({"message": "Successfully updated course."}))
                 }
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Err(e) => fail(e),
             }
         }
 
         Err(e) => {
-            return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+            fail(e)
         }
     }
 }
 
+// The optional `capacity` header of a course
+fn parse_capacity(header: Option<&HeaderValue>) -> anyhow::Result<Option<i32>> {
+    match header.map(|c| c.to_str().map(str::parse::<i32>)) {
+        None => Ok(None),
+        Some(Ok(Ok(c))) => Ok(Some(c)),
+        _ => Err(bad_request("Invalid capacity.")),
+    }
+}
+
 #[get("/admin")]
 pub async fn admin(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
     if !conn.is_admin() {
-        return HttpResponse::Unauthorized().json(json!({"error": "Unauthorized"}));
+        return fail(forbidden("Only admins can do this."));
     }
 
-    return HttpResponse::Ok().json(json!({"message": "Success"}));
+    HttpResponse::Ok().json(json!({"message": "Success"}))
 }
 
 #[patch("/admin/users/{id}")]
 pub async fn update_user(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
     if !conn.is_admin() {
-        return HttpResponse::Unauthorized().json(json!({"error": "Unauthorized"}));
+        return fail(forbidden("Only admins can do this."));
     }
 
-    let id = match req.match_info().get("id").unwrap().parse::<i32>() {
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
         Ok(i) => i,
-        Err(_) => return HttpResponse::BadRequest().json(json!({"error": "Invalid id."})),
+        Err(_) => return fail(bad_request("Invalid id.")),
     };
 
     let mut lookup_user = match conn.search_users(format!("{}", id)) {
-        Ok(u) => match u.get(0) {
+        Ok(u) => match u.first() {
             Some(u) => u.to_owned(),
-            None => return HttpResponse::BadRequest().json(json!({"error": "User not found."})),
+            None => return fail(not_found("User not found.")),
         },
         Err(e) => {
-            return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+            return fail(e);
         }
     }
     .to_owned();
 
-    let mut username = match request_headers.get("username") {
-        Some(u) => u.to_str().unwrap().to_string(),
-        None => String::new(),
+    let mut username = match request_headers.get("username").map(header_text).transpose() {
+        Ok(u) => u.unwrap_or_default(),
+        Err(e) => return fail(e),
     };
-    let password = match request_headers.get("password") {
-        Some(p) => p.to_str().unwrap().to_string(),
-        None => String::new(),
+    let password = match request_headers.get("password").map(header_text).transpose() {
+        Ok(p) => p.unwrap_or_default(),
+        Err(e) => return fail(e),
     };
-    let mut email = match request_headers.get("email") {
-        Some(e) => e.to_str().unwrap().to_string(),
-        None => String::new(),
+    let mut email = match request_headers.get("email").map(header_text).transpose() {
+        Ok(e) => e.unwrap_or_default(),
+        Err(e) => return fail(e),
     };
-    let mut phone = match request_headers.get("phone") {
-        Some(p) => p.to_str().unwrap().to_string(),
-        None => String::new(),
+    let mut phone = match request_headers.get("phone").map(header_text).transpose() {
+        Ok(p) => p.unwrap_or_default(),
+        Err(e) => return fail(e),
     };
     let verified = match request_headers.get("verified") {
-        Some(v) => match v.to_str().unwrap().parse::<bool>() {
-            Ok(b) => b,
-            Err(_) => {
-                return HttpResponse::BadRequest().json(json!({"error": "Invalid verified."}))
+        Some(v) => match v.to_str().map(str::parse::<bool>) {
+            Ok(Ok(b)) => b,
+            _ => {
+                return fail(bad_request("Invalid verified."))
             }
         },
         None => lookup_user.verified,
     };
 
     let suspended = match request_headers.get("suspended") {
-        Some(s) => match s.to_str().unwrap().parse::<bool>() {
-            Ok(b) => b,
-            Err(_) => {
-                return HttpResponse::BadRequest().json(json!({"error": "Invalid suspended."}))
+        Some(s) => match s.to_str().map(str::parse::<bool>) {
+            Ok(Ok(b)) => b,
+            _ => {
+                return fail(bad_request("Invalid suspended."))
             }
         },
         None => lookup_user.suspended,
     };
 
     let forcenewpw = match request_headers.get("forcenewpw") {
-        Some(f) => match f.to_str().unwrap().parse::<bool>() {
-            Ok(b) => b,
-            Err(_) => {
-                return HttpResponse::BadRequest().json(json!({"error": "Invalid forcenewpw."}))
+        Some(f) => match f.to_str().map(str::parse::<bool>) {
+            Ok(Ok(b)) => b,
+            _ => {
+                return fail(bad_request("Invalid forcenewpw."))
             }
         },
         None => lookup_user.forcenewpw,
     };
 
-    let mut role = match request_headers.get("role") {
-        Some(r) => r.to_str().unwrap().to_string(),
-        None => String::new(),
+    let mut role = match request_headers.get("role").map(header_text).transpose() {
+        Ok(r) => r.unwrap_or_default(),
+        Err(e) => return fail(e),
     };
 
-    if username == "" {
+    if username.is_empty() {
         username = lookup_user.username;
     }
-    if email == "" {
+    if email.is_empty() {
         email = lookup_user.email;
     }
-    if phone == "" {
+    if phone.is_empty() {
         phone = lookup_user.phone;
     }
-    if role == "" {
+    if role.is_empty() {
         role = lookup_user.role;
     }
 
@@ -772,103 +805,80 @@ pub async fn update_user(req: HttpRequest) -> impl Responder {
 
     match conn.update_user(lookup_user.clone()) {
         Ok(_) => {
-            let json = serde_json::to_string(&lookup_user);
+            let json = serde_json::to_string(&conn.view_user(&lookup_user));
             match json {
-                Ok(j) => return HttpResponse::Ok().body(j),
+                Ok(j) => HttpResponse::Ok().body(j),
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": e.to_string()}))
+                    fail(e)
                 }
             }
         }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[delete("/admin/users/{id}")]
 pub async fn delete_user(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
 
-    let login_email = req.headers().get("login_email");
-    let login_password = req.headers().get("login_password");
 
     let id = match req.match_info().get("id") {
         Some(id) => id,
-        None => return HttpResponse::BadRequest().json(json!({"error": "Invalid id."})),
+        None => return fail(bad_request("Invalid id.")),
     };
 
-    login!(login_email, login_password, conn);
+    login!(req.headers(), conn);
 
-    let user = conn.search_users(format!("{}", id)).unwrap()[0].clone();
+    let user = match conn.search_users(id.to_string()) {
+        Ok(u) => match u.first() {
+            Some(u) => u.to_owned(),
+            None => return fail(not_found("User not found.")),
+        },
+        Err(e) => return fail(e),
+    };
 
     match conn.delete_user(user) {
-        Ok(_) => return HttpResponse::Ok().json(json!({"message": "Successfully deleted user."})),
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully deleted user."})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/account")]
 pub async fn get_self(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let email = request_headers.get("login_email");
-    let password = request_headers.get("login_password");
+    login!(request_headers, conn);
 
-    login!(email, password, conn);
-
-    let user_email = email.unwrap().to_str().unwrap();
-    let user = match conn.search_users(format!("{}", user_email)) {
-        Ok(users) => match users.get(0) {
-            Some(u) => u.to_owned(),
-            None => return HttpResponse::InternalServerError().json(json!({"error": "User not found."})),
-        },
+    let user = match conn.current_user() {
+        Ok(u) => u,
         Err(e) => {
-            return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+            return fail(e);
         }
     };
 
     if conn.is_student() {
-        let enrolled_in;
-
-        let enrollments_json = match conn.list_enrollments() {
-            Ok(e) => {
-                enrolled_in = e;
-                match serde_json::to_string(&enrolled_in) {
-                    Ok(j) => j,
-                    Err(e) => {
-                        return HttpResponse::InternalServerError()
-                            .json(json!({"error": e.to_string()}));
-                    }
+        let (enrollments, mut courses): (Vec<StudentCourse>, Vec<Courses>) =
+            match conn.list_enrollments_with_courses() {
+                Ok(e) => e.into_iter().map(|e| (e.enrollment, e.course)).unzip(),
+                Err(e) => {
+                    return fail(e);
                 }
-            }
+            };
+        courses.sort_by_key(|c| c.id);
+        courses.dedup_by_key(|c| c.id);
+
+        let enrollments_json = match serde_json::to_string(&enrollments) {
+            Ok(j) => j,
             Err(e) => {
-                return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+                return fail(e);
             }
         };
 
-        let courses_json = match conn.search_courses("".to_owned()) {
-            Ok(c) => {
-                let c = c
-                    .into_iter()
-                    .filter_map(|c| {
-                        if enrolled_in.iter().any(|e| e.course_id == c.id) {
-                            Some(c)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<Courses>>();
-                match serde_json::to_string(&c) {
-                    Ok(j) => j,
-                    Err(e) => {
-                        return HttpResponse::InternalServerError()
-                            .json(json!({"error": e.to_string()}));
-                    }
-                }
-            }
+        let courses_json = match serde_json::to_string(&courses) {
+            Ok(j) => j,
             Err(e) => {
-                return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+                return fail(e);
             }
         };
 
@@ -876,20 +886,19 @@ pub async fn get_self(req: HttpRequest) -> impl Responder {
             Ok(s) => match serde_json::to_string(&s) {
                 Ok(j) => j,
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": e.to_string()}));
+                    return fail(e);
                 }
             },
 
             Err(e) => {
-                return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+                return fail(e);
             }
         };
 
-        let user_json = match serde_json::to_string(&user) {
+        let user_json = match serde_json::to_string(&conn.view_user(&user)) {
             Ok(j) => j,
             Err(e) => {
-                return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+                return fail(e);
             }
         };
 
@@ -900,43 +909,26 @@ pub async fn get_self(req: HttpRequest) -> impl Responder {
 
         match serde_json::from_str::<Value>(&json_prep) {
             Ok(json3) => HttpResponse::Ok().json(json3),
-            Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+            Err(e) => fail(e),
         }
     }
 
     else if conn.is_teacher() {
-        let user_json = match serde_json::to_string(&user) {
+        let user_json = match serde_json::to_string(&conn.view_user(&user)) {
             Ok(j) => j,
             Err(e) => {
-                return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+                return fail(e);
             }
         };
 
-        let teacher_account;
-
-        let teacher_accounts = match conn.get_teacher_accounts() {
-            Ok(t) => {
-                teacher_account = match t.into_iter().filter_map(|t| {
-                    if t.teacher_id == user.id {
-                        Some(t)
-                    } else {
-                        None
-                    }})
-                    .collect::<Vec<TeacherAccount>>().get(0) {
-                        Some(t) => t.to_owned(),
-                        None => {
-                            return HttpResponse::InternalServerError().json(json!({"error": "A teacher account with this Teacher ID does not exist."}));
-                        },
-                    };
-
-                match serde_json::to_string(&teacher_account) {
-                    Ok(s) => s,
-                    Err(_) => return HttpResponse::InternalServerError().json(json!({"error": "Failed to serialize teacher account."})),
-                }
-            }
+        let teacher_accounts = match conn.get_teacher_account(user.id) {
+            Ok(t) => match serde_json::to_string(&t) {
+                Ok(s) => s,
+                Err(e) => return fail(e),
+            },
 
             Err(e) => {
-                return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+                return fail(e);
             }
         };
 
@@ -944,15 +936,15 @@ pub async fn get_self(req: HttpRequest) -> impl Responder {
 
         match serde_json::from_str::<Value>(&json_prep) {
             Ok(j) => HttpResponse::Ok().json(j),
-            Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+            Err(e) => fail(e),
         }
     }
 
     else {
-        let user_json = match serde_json::to_string(&user) {
+        let user_json = match serde_json::to_string(&conn.view_user(&user)) {
             Ok(j) => j,
             Err(e) => {
-                return HttpResponse::InternalServerError().json(json!({"error": e.to_string()}));
+                return fail(e);
             }
         };
 
@@ -960,59 +952,137 @@ pub async fn get_self(req: HttpRequest) -> impl Responder {
 
         match serde_json::from_str::<Value>(&json_prep) {
             Ok(json3) => HttpResponse::Ok().json(json3),
-            Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+            Err(e) => fail(e),
         }
     
     }
 }
 
-#[patch("/account")]
-pub async fn update_self(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+#[get("/account/history")]
+pub async fn get_own_history(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
+    login!(request_headers, conn);
 
-    login!(login_email, login_password, conn);
+    let user = match conn.current_user() {
+        Ok(u) => u,
+        Err(e) => return fail(e),
+    };
 
-    let mut user = conn
-        .search_users(format!("{}", login_email.unwrap().to_str().unwrap()))
-        .unwrap()[0]
-        .to_owned();
+    match conn.academic_history(user.id) {
+        Ok(h) => HttpResponse::Ok().json(h),
+        Err(e) => fail(e),
+    }
+}
 
-    let username = request_headers.get("username");
-    let email = request_headers.get("email");
-    let password = request_headers.get("password");
-    let phone = request_headers.get("phone");
+#[get("/students/{id}/history")]
+pub async fn get_student_history(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
+    };
+
+    match conn.academic_history(id) {
+        Ok(h) => HttpResponse::Ok().json(h),
+        Err(e) => fail(e),
+    }
+}
+
+#[get("/students/{id}/standing")]
+pub async fn get_student_standing(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
+    };
 
-    let mut username = match username {
-        Some(u) => u.to_str().unwrap().to_string(),
-        None => String::new(),
+    match conn.standing_history(id) {
+        Ok(s) => HttpResponse::Ok().json(s),
+        Err(e) => fail(e),
+    }
+}
+
+#[get("/students/{id}/holds")]
+pub async fn get_holds(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
     };
-    let mut email = match email {
-        Some(e) => e.to_str().unwrap().to_string(),
-        None => String::new(),
+
+    match conn.get_holds(id) {
+        Ok(h) => HttpResponse::Ok().json(h),
+        Err(e) => fail(e),
+    }
+}
+
+#[get("/account/notifications")]
+pub async fn get_notifications(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+
+    match conn.get_notifications() {
+        Ok(n) => HttpResponse::Ok().json(n),
+        Err(e) => fail(e),
+    }
+}
+
+#[patch("/account")]
+pub async fn update_self(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+
+    let mut user = match conn.current_user() {
+        Ok(u) => u,
+        Err(e) => return fail(e),
     };
-    let password = match password {
-        Some(p) => {
+
+    let mut username = match request_headers.get("username").map(header_text).transpose() {
+        Ok(u) => u.unwrap_or_default(),
+        Err(e) => return fail(e),
+    };
+    let mut email = match request_headers.get("email").map(header_text).transpose() {
+        Ok(e) => e.unwrap_or_default(),
+        Err(e) => return fail(e),
+    };
+    let password = match request_headers.get("password").map(header_text).transpose() {
+        Ok(Some(p)) => {
             user.forcenewpw = false;
-            p.to_str().unwrap().to_string()
+            p
         }
-        None => String::new(),
+        Ok(None) => String::new(),
+        Err(e) => return fail(e),
     };
-    let mut phone = match phone {
-        Some(p) => p.to_str().unwrap().to_string(),
-        None => String::new(),
+    let mut phone = match request_headers.get("phone").map(header_text).transpose() {
+        Ok(p) => p.unwrap_or_default(),
+        Err(e) => return fail(e),
     };
 
-    if username == "" {
+    if username.is_empty() {
         username = user.username;
     }
-    if email == "" {
+    if email.is_empty() {
         email = user.email;
     }
-    if phone == "" {
+    if phone.is_empty() {
         phone = user.phone;
     }
 
@@ -1023,143 +1093,127 @@ pub async fn update_self(req: HttpRequest) -> impl Responder {
 
     match conn.update_user(user.clone()) {
         Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully updated."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[post("/enroll/{id}")]
 pub async fn enroll(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
 
-    let user = conn
-        .search_users(format!("{}", login_email.unwrap().to_str().unwrap()))
-        .unwrap()[0]
-        .clone();
-
-    let course_id = req.match_info().get("id");
+    let user = match conn.current_user() {
+        Ok(u) => u,
+        Err(e) => return fail(e),
+    };
 
-    if course_id.is_none() {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing course id"}));
-    }
+    let course_id = match req.match_info().get("id") {
+        Some(id) => id,
+        None => {
+            return fail(bad_request("Missing course id"));
+        }
+    };
 
-    let course_id = course_id.unwrap().to_owned();
+    let course_list = match conn.search_courses(course_id.to_string()) {
+        Ok(c) if c.is_empty() => return fail(not_found("Course not found.")),
+        Ok(c) => c,
+        Err(e) => return fail(e),
+    };
 
-    match conn.enroll_courses(
-        conn.search_courses(format!("{}", course_id))
-            .unwrap()
-            .iter()
-            .filter_map(|c| Some(c.clone()))
-            .collect(),
-    ) {
+    match conn.enroll_courses(course_list) {
         Ok(_) => {
-            let json = serde_json::to_string(&user);
+            let json = serde_json::to_string(&conn.view_user(&user));
             match json {
-                Ok(j) => return HttpResponse::Ok().json(j),
-                Err(_) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": "Failed to serialize user"}))
-                }
+                Ok(j) => HttpResponse::Ok().body(j),
+                Err(e) => fail(e),
             }
         }
 
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[post("/unenroll/{id}")]
 pub async fn unenroll(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let email = request_headers.get("login_email");
-    let password = request_headers.get("login_password");
-
-    login!(email, password, conn);
+    login!(request_headers, conn);
 
-    let user = conn
-        .search_users(format!("{}", email.unwrap().to_str().unwrap()))
-        .unwrap()[0]
-        .to_owned();
+    let user = match conn.current_user() {
+        Ok(u) => u,
+        Err(e) => return fail(e),
+    };
 
     let course_id = match req.match_info().get("id") {
         Some(id) => id,
         None => {
-            return HttpResponse::BadRequest().json(json!({"error": "Missing course id"}));
+            return fail(bad_request("Missing course id"));
         }
     };
 
-    let course_list = conn
-        .search_courses(format!("{}", course_id))
-        .unwrap()
-        .iter()
-        .filter_map(|c| Some(c.clone()))
-        .collect::<Vec<Courses>>();
+    let course_list = match conn.search_courses(course_id.to_string()) {
+        Ok(c) if c.is_empty() => return fail(not_found("Course not found.")),
+        Ok(c) => c,
+        Err(e) => return fail(e),
+    };
 
     match conn.drop_courses(course_list) {
         Ok(_) => {
-            let json = serde_json::to_string(&user);
+            let json = serde_json::to_string(&conn.view_user(&user));
             match json {
-                Ok(j) => return HttpResponse::Ok().body(j),
-                Err(_) => {
-                    return HttpResponse::InternalServerError()
-                        .json(json!({"error": "Failed to serialize user"}))
-                }
+                Ok(j) => HttpResponse::Ok().body(j),
+                Err(e) => fail(e),
             }
         }
 
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[post("/login")]
 pub async fn login(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
     let email = request_headers.get("login_email");
     let password = request_headers.get("login_password");
 
     if email.is_none() || password.is_none() {
-        return HttpResponse::BadRequest().json(json!({"error": "Missing username or password"}));
+        return fail(bad_request("Missing username or password"));
     }
 
-    let email = email.unwrap().to_str().unwrap();
-    let password = password.unwrap().to_str().unwrap();
+    let (email, password) = match (header_text(email.unwrap()), header_text(password.unwrap())) {
+        (Ok(e), Ok(p)) => (e, p),
+        (Err(e), _) | (_, Err(e)) => return fail(e),
+    };
 
-    let user = conn.search_users(format!("{}", email));
+    let totp_code = request_headers
+        .get("totp_code")
+        .map(|c| c.to_str().unwrap_or_default().to_owned());
 
-    match user {
-        Ok(u) => {
-            if u.len() == 0 {
-                return HttpResponse::BadRequest().json(json!({"error": "User not found"}));
-            } else {
-                let user = u.get(0).unwrap();
-
-                match conn.login(email.to_owned(), password.to_owned()) {
-                    Ok(_) => {
-                        let json = serde_json::to_string(&user);
-                        match json {
-                            Ok(j) => return HttpResponse::Ok().body(j),
-                            Err(e) => {
-                                return HttpResponse::InternalServerError()
-                                    .json(json!({"error": e.to_string()}))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        return HttpResponse::InternalServerError()
-                            .json(json!({"error": e.to_string()}))
-                    }
-                }
-            }
-        }
-        Err(e) => return HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+    // Unknown accounts and wrong passwords get the same answer, so accounts can't be probed
+    if let Err(e) = conn.login(email, password, totp_code) {
+        return fail(e);
+    }
+
+    let token = match conn.create_session() {
+        Ok(t) => t,
+        Err(e) => return fail(e),
+    };
+
+    let user = match conn.current_user() {
+        Ok(u) => u,
+        Err(e) => return fail(e),
+    };
+
+    match serde_json::to_string(&conn.view_user(&user)) {
+        Ok(j) => HttpResponse::Ok()
+            .insert_header(("session_token", token))
+            .body(j),
+        Err(e) => fail(e),
     }
 }
 
@@ -1167,19 +1221,28 @@ pub async fn login(req: HttpRequest) -> impl Responder {
 pub async fn logout(req: HttpRequest) -> impl Responder {
     let request_headers = req.headers();
 
+    if let Some(token) = request_headers.get("session_token") {
+        let mut conn = connect(&req);
+
+        return match conn.end_session(token.to_str().unwrap_or_default()) {
+            Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully logged out."})),
+            Err(e) => fail(e),
+        };
+    }
+
     let email = request_headers.get("login_email");
     let password = request_headers.get("login_password");
 
     if email.is_none() || password.is_none() {
         HttpResponse::Ok().json(json!({"message": "Successfully logged out."}))
     } else {
-        HttpResponse::InternalServerError().json(json!({"error": "Failed to logout."}))
+        fail(bad_request("Missing session token."))
     }
 }
 
 #[post("/register")]
 pub async fn register(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
     let username = request_headers.get("username");
@@ -1188,18 +1251,21 @@ pub async fn register(req: HttpRequest) -> impl Responder {
     let phone = request_headers.get("phone");
 
     if username.is_none() || password.is_none() || email.is_none() {
-        return HttpResponse::BadRequest()
-            .json(json!({"error": "Missing username, password, email, or role"}));
+        return fail(bad_request("Missing username, password, email, or role"));
     }
 
-    let username = username.unwrap().to_str().unwrap().to_owned();
-    let password = password.unwrap().to_str().unwrap().to_owned();
-    let email = email.unwrap().to_str().unwrap().to_owned();
-    let phone = phone
-        .is_some_and(|_| true)
-        .then(|| phone.unwrap().to_str().unwrap().to_owned())
-        .or_else(|| Some(String::from("")))
-        .unwrap_or_default();
+    let (username, password, email) = match (
+        header_text(username.unwrap()),
+        header_text(password.unwrap()),
+        header_text(email.unwrap()),
+    ) {
+        (Ok(u), Ok(p), Ok(e)) => (u, p, e),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return fail(e),
+    };
+    let phone = match phone.map(header_text).transpose() {
+        Ok(p) => p.unwrap_or_default(),
+        Err(e) => return fail(e),
+    };
 
     let u = User {
         id: 0,
@@ -1215,13 +1281,230 @@ pub async fn register(req: HttpRequest) -> impl Responder {
 
     match conn.register_user(u) {
         Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully registered."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
+    }
+}
+
+#[get("/verify")]
+pub async fn verify(req: HttpRequest, query: web::Query<VerifyQuery>) -> impl Responder {
+    let mut conn = connect(&req);
+
+    match conn.verify_email(&query.token) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully verified."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/verify/resend")]
+pub async fn resend_verification(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    let email = request_headers.get("login_email");
+    let password = request_headers.get("login_password");
+
+    if email.is_none() || password.is_none() {
+        return fail(bad_request("Missing username or password"));
+    }
+
+    let (email, password) = match (header_text(email.unwrap()), header_text(password.unwrap())) {
+        (Ok(e), Ok(p)) => (e, p),
+        (Err(e), _) | (_, Err(e)) => return fail(e),
+    };
+
+    match conn.resend_verification(email, password) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Verification email sent."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/password/forgot")]
+pub async fn forgot_password(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    let email = match request_headers.get("email") {
+        Some(e) => e.to_str().unwrap_or_default().to_owned(),
+        None => return fail(bad_request("Missing email.")),
+    };
+
+    // Same answer whether or not the account exists
+    match conn.request_password_reset(email) {
+        Ok(_) => HttpResponse::Ok().json(
+            json!({"message": "If an account with this email exists, reset instructions have been sent."}),
+        ),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/password/reset")]
+pub async fn reset_password(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    let token = request_headers.get("token");
+    let password = request_headers.get("password");
+
+    if token.is_none() || password.is_none() {
+        return fail(bad_request("Missing token or password."));
+    }
+
+    let token = token.unwrap().to_str().unwrap_or_default();
+    let password = password.unwrap().to_str().unwrap_or_default().to_owned();
+
+    match conn.reset_password(token, password) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully reset password."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/account/2fa/enroll")]
+pub async fn enroll_two_factor(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    let email = request_headers.get("login_email");
+    let password = request_headers.get("login_password");
+
+    if email.is_none() || password.is_none() {
+        return fail(bad_request("Missing username or password"));
+    }
+
+    let (email, password) = match (header_text(email.unwrap()), header_text(password.unwrap())) {
+        (Ok(e), Ok(p)) => (e, p),
+        (Err(e), _) | (_, Err(e)) => return fail(e),
+    };
+
+    match conn.begin_two_factor_enrollment(email, password) {
+        Ok((secret, uri)) => HttpResponse::Ok().json(json!({"secret": secret, "otpauth_uri": uri})),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/account/2fa/confirm")]
+pub async fn confirm_two_factor(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    let email = request_headers.get("login_email");
+    let password = request_headers.get("login_password");
+    let code = request_headers.get("totp_code");
+
+    if email.is_none() || password.is_none() || code.is_none() {
+        return fail(bad_request("Missing username, password or totp_code."));
+    }
+
+    let (email, password) = match (header_text(email.unwrap()), header_text(password.unwrap())) {
+        (Ok(e), Ok(p)) => (e, p),
+        (Err(e), _) | (_, Err(e)) => return fail(e),
+    };
+    let code = code.unwrap().to_str().unwrap_or_default().to_owned();
+
+    match conn.confirm_two_factor_enrollment(email, password, code) {
+        Ok(codes) => HttpResponse::Ok().json(json!({"recovery_codes": codes})),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/account/2fa/recovery-codes")]
+pub async fn regenerate_recovery_codes(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+
+    match conn.regenerate_recovery_codes() {
+        Ok(codes) => HttpResponse::Ok().json(json!({"recovery_codes": codes})),
+        Err(e) => fail(e),
+    }
+}
+
+#[delete("/account/2fa")]
+pub async fn disable_two_factor(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+
+    let user = match conn.current_user() {
+        Ok(u) => u,
+        Err(e) => return fail(e),
+    };
+
+    match conn.disable_two_factor(user.id) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Two-factor authentication disabled."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[delete("/admin/users/{id}/2fa")]
+pub async fn reset_two_factor(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
+    };
+
+    match conn.disable_two_factor(id) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Two-factor authentication reset."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[get("/admin/2fa")]
+pub async fn get_two_factor_policy(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    match conn.get_two_factor_policy() {
+        Ok(roles) => HttpResponse::Ok().json(json!({"required_roles": roles})),
+        Err(e) => fail(e),
+    }
+}
+
+#[patch("/admin/2fa")]
+pub async fn set_two_factor_policy(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    // Comma separated, e.g. "admin,teacher". Empty clears the requirement.
+    let roles = match request_headers.get("roles") {
+        Some(r) => r
+            .to_str()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|r| !r.trim().is_empty())
+            .map(|r| r.to_owned())
+            .collect::<Vec<_>>(),
+        None => return fail(bad_request("Missing roles.")),
+    };
+
+    match conn.set_two_factor_policy(roles) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully updated two-factor policy."})),
+        Err(e) => fail(e),
     }
 }
 
 #[post("/admin/register")]
 pub async fn register_admin(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
     let username = request_headers.get("username");
@@ -1231,26 +1514,29 @@ pub async fn register_admin(req: HttpRequest) -> impl Responder {
 
     match request_headers.get("access_code") {
         Some(c) => {
-            if c.to_str().unwrap() != "I_BECOME_THY_ADMIN_AND_I_FUCK_YOUR_MOTHER32131!@#@!#@!" {
-                return HttpResponse::BadRequest().json(json!({"error": "Invalid access code."}));
+            if c.to_str().unwrap_or_default() != "I_BECOME_THY_ADMIN_AND_I_FUCK_YOUR_MOTHER32131!@#@!#@!" {
+                return fail(bad_request("Invalid access code."));
             }
         }
-        None => return HttpResponse::BadRequest().json(json!({"error": "Missing access code."})),
+        None => return fail(bad_request("Missing access code.")),
     };
 
-    if username.is_none() || password.is_none() || email.is_none() {
-        return HttpResponse::BadRequest()
-            .json(json!({"error": "Missing username, password or email."}));
-    }
+    let (Some(username), Some(password), Some(email)) = (username, password, email) else {
+        return fail(bad_request("Missing username, password or email."));
+    };
 
-    let username = username.unwrap().to_str().unwrap().to_owned();
-    let password = password.unwrap().to_str().unwrap().to_owned();
-    let email = email.unwrap().to_str().unwrap().to_owned();
-    let phone = phone
-        .is_some_and(|_| true)
-        .then(|| phone.unwrap().to_str().unwrap().to_owned())
-        .or_else(|| Some(String::from("")))
-        .unwrap_or_default();
+    let (username, password, email) = match (
+        header_text(username),
+        header_text(password),
+        header_text(email),
+    ) {
+        (Ok(u), Ok(p), Ok(e)) => (u, p, e),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return fail(e),
+    };
+    let phone = match phone.map(header_text).transpose() {
+        Ok(p) => p.unwrap_or_default(),
+        Err(e) => return fail(e),
+    };
 
     let u = User {
         id: 0,
@@ -1266,21 +1552,19 @@ pub async fn register_admin(req: HttpRequest) -> impl Responder {
 
     match conn.register_user(u) {
         Ok(_) => HttpResponse::Ok().json(json!({"message": "Successfully registered."})),
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 #[get("/admin/stats")]
 pub async fn get_stats(req: HttpRequest) -> impl Responder {
-    let mut conn = ServerConnection::new();
+    let mut conn = connect(&req);
     let request_headers = req.headers();
 
-    let login_email = request_headers.get("login_email");
-    let login_password = request_headers.get("login_password");
-    login!(login_email, login_password, conn);
+    login!(request_headers, conn);
 
     if !conn.is_admin() {
-        return HttpResponse::Unauthorized().json(json!({"error": "Unauthorized"}));
+        return fail(forbidden("Only admins can do this."));
     }
 
     let stats = conn.generate_statistics();
@@ -1289,38 +1573,402 @@ pub async fn get_stats(req: HttpRequest) -> impl Responder {
         Ok(s) => {
             let json = serde_json::to_string(&s);
             match json {
-                Ok(j) => HttpResponse::Ok().json(j),
-                Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+                Ok(j) => HttpResponse::Ok().body(j),
+                Err(e) => fail(e),
             }
         }
-        Err(e) => HttpResponse::InternalServerError().json(json!({"error": e.to_string()})),
+        Err(e) => fail(e),
     }
 }
 
 // So that I don't have to repeat myself over and over again
 #[macro_export]
 macro_rules! login_macro {
-    ($login_email:expr, $login_password:expr, $conn:expr) => {
-        {
-            match ($login_email, $login_password) {
-                (Some(a), Some(b)) => {
-                    let username = a.to_str().unwrap().to_owned();
-                    let password = b.to_str().unwrap().to_owned();
-
-                    match $conn.login(username, password) {
+    ($request_headers:expr, $conn:expr) => {
+        // Already signed in when the request came through the auth middleware
+        if $conn.current_user().is_err() {
+            let headers = $request_headers;
+
+            match (
+                headers.get("session_token"),
+                headers.get("login_email"),
+                headers.get("login_password"),
+            ) {
+                (Some(t), _, _) => {
+                    match $conn.resume_session(t.to_str().unwrap_or_default()) {
                         Ok(_) => {},
                         Err(_) => {
-                            return HttpResponse::BadRequest().json(json!({"error": "Invalid login credentials."}));
+                            return fail(unauthenticated("Invalid or expired session."));
                         }
                     }
                 },
-                (_, None) => {
-                    return HttpResponse::BadRequest().json(json!({"error": "Missing login password."}));
+                (None, Some(a), Some(b)) => {
+                    let (username, password) = match (header_text(a), header_text(b)) {
+                        (Ok(u), Ok(p)) => (u, p),
+                        (Err(e), _) | (_, Err(e)) => return fail(e),
+                    };
+                    let totp_code = headers
+                        .get("totp_code")
+                        .map(|c| c.to_str().unwrap_or_default().to_owned());
+
+                    match $conn.login(username, password, totp_code) {
+                        Ok(_) => {},
+                        Err(e) => {
+                            return fail(e);
+                        }
+                    }
                 },
-                (None, _) => {
-                    return HttpResponse::BadRequest().json(json!({"error": "Missing login email."}));
+                (None, _, None) => {
+                    return fail(unauthenticated("Missing login password."));
                 },
+                (None, None, _) => {
+                    return fail(unauthenticated("Missing login email."));
+                },
+            }
+        }
+    }
+}
+
+#[get("/admin/lockouts")]
+pub async fn get_lockouts(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    match conn.get_lockouts() {
+        Ok(l) => HttpResponse::Ok().json(l),
+        Err(e) => fail(e),
+    }
+}
+
+#[delete("/admin/lockouts/{id}")]
+pub async fn clear_lockout(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
+    };
+
+    match conn.clear_lockout(id) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Lockout cleared."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/admin/terms/{id}/close")]
+pub async fn close_term(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
+    };
+
+    match conn.close_term(id) {
+        Ok(c) => HttpResponse::Ok().json(c),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/admin/students/{id}/holds")]
+pub async fn place_hold(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let student_id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
+    };
+
+    let text = |header: Option<&HeaderValue>| {
+        header
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    let kind = text(request_headers.get("kind")).to_lowercase();
+    let reason = text(request_headers.get("reason"));
+    let (blocks_registration, blocks_transcripts) =
+        match text(request_headers.get("blocks")).as_str() {
+            "" | "registration" => (true, false),
+            "transcripts" => (false, true),
+            "both" => (true, true),
+            _ => {
+                return fail(bad_request(
+                    "blocks must be registration, transcripts or both.",
+                ))
             }
+        };
+
+    let hold = Hold {
+        id: 0,
+        student_id,
+        kind,
+        reason,
+        placed_by: 0,
+        placed_at: 0,
+        blocks_registration,
+        blocks_transcripts,
+        released_by: 0,
+        released_at: 0,
+    };
+
+    match conn.place_hold(hold) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Hold placed."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/admin/holds/{id}/release")]
+pub async fn release_hold(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let id = match req.match_info().get("id").unwrap_or_default().parse::<i32>() {
+        Ok(i) => i,
+        Err(_) => return fail(bad_request("Invalid id.")),
+    };
+
+    match conn.release_hold(id) {
+        Ok(_) => HttpResponse::Ok().json(json!({"message": "Hold released."})),
+        Err(e) => fail(e),
+    }
+}
+
+#[get("/admin/backups")]
+pub async fn list_snapshots(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    match conn.list_snapshots() {
+        Ok(s) => HttpResponse::Ok().json(s),
+        Err(e) => fail(e),
+    }
+}
+
+// Runs while the server keeps serving; older snapshots are pruned afterwards
+#[post("/admin/backups")]
+pub async fn take_snapshot(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    match web::block(move || conn.take_snapshot()).await {
+        Ok(Ok(s)) => HttpResponse::Created().json(s),
+        Ok(Err(e)) => fail(e),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/admin/backups/{name}/verify")]
+pub async fn verify_snapshot(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let name = req.match_info().get("name").unwrap_or_default().to_owned();
+
+    match web::block(move || conn.verify_snapshot(&name)).await {
+        Ok(Ok(v)) => HttpResponse::Ok().json(v),
+        Ok(Err(e)) => fail(e),
+        Err(e) => fail(e),
+    }
+}
+
+#[post("/admin/backups/{name}/restore")]
+pub async fn restore_snapshot(req: HttpRequest) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let name = req.match_info().get("name").unwrap_or_default().to_owned();
+    let restored = name.clone();
+
+    match web::block(move || conn.restore_snapshot(&name)).await {
+        Ok(Ok(previous)) => HttpResponse::Ok().json(json!({
+            "message": format!(
+                "Restored {}. The previous state was saved as {}.",
+                restored, previous.name
+            )
+        })),
+        Ok(Err(e)) => fail(e),
+        Err(e) => fail(e),
+    }
+}
+
+// The request body is the CSV file itself. Nothing is written unless every row is valid.
+#[post("/admin/import/{kind}")]
+pub async fn import_csv(
+    req: HttpRequest,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
+    }
+
+    let kind = match req.match_info().get("kind").unwrap_or_default().parse::<ImportKind>() {
+        Ok(k) => k,
+        Err(e) => return fail(e),
+    };
+
+    match conn.import_csv(kind, body.as_ref(), query.dry_run) {
+        Ok(report) if report.is_clean() => HttpResponse::Ok().json(report),
+        Ok(report) => HttpResponse::UnprocessableEntity().json(report),
+        Err(e) => fail(e),
+    }
+}
+
+// Sends whatever an export writes to the response stream in chunks, so the
+// connection's memory use doesn't grow with the size of the table.
+struct ChannelWriter {
+    buffer: Vec<u8>,
+    tx: mpsc::Sender<Result<web::Bytes, std::io::Error>>,
+}
+
+const EXPORT_CHUNK: usize = 64 * 1024;
+
+impl ChannelWriter {
+    fn send(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = web::Bytes::from(std::mem::take(&mut self.buffer));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Client went away."))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= EXPORT_CHUNK {
+            self.send()?;
         }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send()
+    }
+}
+
+// `GET /admin/export/users?format=jsonl&role=student` — every query parameter other
+// than `format` filters on the column of the same name.
+#[get("/admin/export/{table}")]
+pub async fn export_table(
+    req: HttpRequest,
+    query: web::Query<Vec<(String, String)>>,
+) -> impl Responder {
+    let mut conn = connect(&req);
+    let request_headers = req.headers();
+
+    login!(request_headers, conn);
+    if !conn.is_admin() {
+        return fail(forbidden("Only admins can do this."));
     }
+
+    let mut format = ExportFormat::Csv;
+    let mut filters = Vec::new();
+
+    for (key, value) in query.into_inner() {
+        if key == "format" {
+            format = match value.parse() {
+                Ok(f) => f,
+                Err(e) => {
+                    return fail(e)
+                }
+            };
+        } else {
+            filters.push((key, value));
+        }
+    }
+
+    let name = req.match_info().get("table").unwrap_or_default();
+    let request = match ExportRequest::parse(name, format, &filters) {
+        Ok(r) => r,
+        Err(e) => return fail(e),
+    };
+
+    let file_name = request.file_name();
+    let (tx, rx) = mpsc::channel(4);
+
+    let span = tracing::Span::current();
+
+    actix_web::rt::task::spawn_blocking(move || {
+        let _request = span.enter();
+        let mut out = ChannelWriter {
+            buffer: Vec::with_capacity(EXPORT_CHUNK),
+            tx: tx.clone(),
+        };
+
+        // Headers are already sent by now; failing the body is the only way left to report it
+        if let Err(e) = conn.export(&request, &mut out) {
+            tracing::error!(error = %redacted(&e), "export failed mid-stream");
+            let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+        }
+    });
+
+    let body = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", file_name),
+        ))
+        .streaming(body)
 }
@@ -0,0 +1,414 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use serde_derive::Serialize;
+
+use super::history::PASSING;
+use super::policy::ValidationErrors;
+use super::table_models::{Courses, Departments, StudentAccount, Term, User};
+
+// A believable university for demos and load tests: departments with their teachers,
+// courses that never double-book a teacher, and students who have taken a full load every
+// term since they enrolled. The same plan always generates the same rows.
+#[derive(Debug, Clone)]
+pub struct SeedPlan {
+    pub seed: u64,
+    pub departments: usize,
+    pub teachers: usize,
+    pub courses: usize,
+    pub students: usize,
+    pub terms: usize,
+    pub courses_per_term: usize,
+    // The first term is the fall term of this year; the last term is still in progress
+    pub first_year: i32,
+    // Every generated account signs in with it
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeedReport {
+    pub departments: usize,
+    pub teachers: usize,
+    pub courses: usize,
+    pub students: usize,
+    pub terms: usize,
+    pub enrollments: usize,
+}
+
+// Rows refer to each other by their index in `Dataset`; ids are assigned when written
+pub struct Dataset {
+    pub departments: Vec<Departments>,
+    pub terms: Vec<Term>,
+    pub teachers: Vec<SeededTeacher>,
+    pub courses: Vec<SeededCourse>,
+    pub students: Vec<SeededStudent>,
+}
+
+pub struct SeededTeacher {
+    pub user: User,
+    pub department: usize,
+}
+
+pub struct SeededCourse {
+    pub course: Courses,
+    pub teacher: usize,
+}
+
+pub struct SeededStudent {
+    pub user: User,
+    // Everything but the ids and the cgpa, which the enrollment triggers work out
+    pub account: StudentAccount,
+    pub advisor: usize,
+    pub enrollments: Vec<SeededEnrollment>,
+}
+
+pub struct SeededEnrollment {
+    pub course: usize,
+    pub term: usize,
+    // -1 in the term that is still in progress
+    pub grade: f32,
+}
+
+impl Default for SeedPlan {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            departments: 8,
+            teachers: 40,
+            courses: 160,
+            students: 1000,
+            terms: 4,
+            courses_per_term: 4,
+            first_year: 2024,
+            password: String::from("Seed-Passw0rd!"),
+        }
+    }
+}
+
+impl SeedPlan {
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = ValidationErrors::default();
+
+        if self.departments == 0 {
+            errors.add("departments", "too_small", "At least one department is needed.");
+        }
+
+        if self.teachers < self.departments {
+            errors.add("teachers", "too_small", "Every department needs a teacher.");
+        }
+
+        if self.terms == 0 {
+            errors.add("terms", "too_small", "At least one term is needed.");
+        }
+
+        if self.courses_per_term == 0 || self.courses_per_term > TIMESLOTS.len() {
+            errors.add(
+                "courses_per_term",
+                "out_of_range",
+                &format!("Must be between 1 and {}.", TIMESLOTS.len()),
+            );
+        }
+
+        // Nobody takes a course twice
+        if self.courses < self.courses_per_term * self.terms {
+            errors.add(
+                "courses",
+                "too_small",
+                "Needs at least courses_per_term * terms courses.",
+            );
+        }
+
+        if !(1900..=9000).contains(&self.first_year) {
+            errors.add("first_year", "out_of_range", "Must be a year between 1900 and 9000.");
+        }
+
+        errors.into_result()
+    }
+}
+
+// `password` is the hash stored for every account; emails end in `@domain`
+pub fn generate(plan: &SeedPlan, domain: &str, password: &str) -> Dataset {
+    let mut rng = Rng(plan.seed);
+
+    let departments: Vec<Departments> = (0..plan.departments)
+        .map(|d| Departments {
+            id: 0,
+            name: department(d).0,
+        })
+        .collect();
+
+    let terms: Vec<Term> = (0..plan.terms).map(|t| term(plan.first_year, t)).collect();
+
+    // Teacher `t` works in department `t % departments`
+    let teachers: Vec<SeededTeacher> = (0..plan.teachers)
+        .map(|t| SeededTeacher {
+            user: person(&mut rng, t, "teacher", domain, password),
+            department: t % plan.departments,
+        })
+        .collect();
+
+    let mut booked: Vec<HashSet<usize>> = vec![HashSet::new(); plan.teachers];
+    let courses: Vec<SeededCourse> = (0..plan.courses)
+        .map(|c| {
+            let d = c % plan.departments;
+            let (name, code) = department(d);
+            let n = c / plan.departments;
+            let level = n % LEVELS.len();
+            let teacher = teacher_in(&mut rng, d, plan);
+
+            // The teacher's first free slot after a random one; only a teacher with more
+            // courses than there are slots gets two at the same time
+            let start = rng.below(TIMESLOTS.len());
+            let slot = (0..TIMESLOTS.len())
+                .map(|i| (start + i) % TIMESLOTS.len())
+                .find(|s| !booked[teacher].contains(s))
+                .unwrap_or(start);
+            booked[teacher].insert(slot);
+
+            let (title, description) = LEVELS[level];
+            SeededCourse {
+                course: Courses {
+                    id: 0,
+                    teacher_id: 0,
+                    course: title[(n / LEVELS.len()) % title.len()].replace("{}", &name),
+                    course_nr: format!("{} {}{:02}", code, level + 1, n / LEVELS.len() + 1),
+                    description: format!("{} course offered by the department of {}.", description, name),
+                    cr_cost: *rng.pick(&CREDITS),
+                    timeslots: String::from(TIMESLOTS[slot]),
+                    capacity: 0,
+                },
+                teacher,
+            }
+        })
+        .collect();
+
+    let by_department: Vec<Vec<usize>> = (0..plan.departments)
+        .map(|d| (d..plan.courses).step_by(plan.departments).collect())
+        .collect();
+
+    let students = (0..plan.students)
+        .map(|s| {
+            let d = rng.below(plan.departments);
+            let advisor = teacher_in(&mut rng, d, plan);
+            let entry = rng.below(plan.terms);
+            // A student's usual grade; each course lands somewhere around it
+            let ability = 1.5 + 2.5 * rng.unit();
+
+            let mut taken = HashSet::new();
+            let mut enrollments = Vec::new();
+            let (mut cur_credit, mut cum_credit) = (0, 0);
+
+            for t in entry..plan.terms {
+                let mut slots = HashSet::new();
+                let mut chosen = 0;
+
+                // Mostly courses of their own department, never two at the same time
+                for _ in 0..plan.courses_per_term * 50 {
+                    if chosen == plan.courses_per_term {
+                        break;
+                    }
+
+                    let own = &by_department[d];
+                    let c = if !own.is_empty() && rng.unit() < 0.6 {
+                        *rng.pick(own)
+                    } else {
+                        rng.below(plan.courses)
+                    };
+                    let slot = &courses[c].course.timeslots;
+
+                    if taken.contains(&c) || slots.contains(slot) {
+                        continue;
+                    }
+                    taken.insert(c);
+                    slots.insert(slot.clone());
+                    chosen += 1;
+
+                    let credits = courses[c].course.cr_cost;
+                    let grade = if t + 1 == plan.terms {
+                        cur_credit += credits;
+                        -1.0
+                    } else {
+                        let noise = (rng.unit() + rng.unit() + rng.unit() - 1.5) * 1.2;
+                        let grade = nearest_grade(ability + noise);
+                        if grade >= PASSING {
+                            cum_credit += credits;
+                        }
+                        grade
+                    };
+
+                    enrollments.push(SeededEnrollment {
+                        course: c,
+                        term: t,
+                        grade,
+                    });
+                }
+            }
+
+            SeededStudent {
+                user: person(&mut rng, plan.teachers + s, "student", domain, password),
+                account: StudentAccount {
+                    id: 0,
+                    student_id: 0,
+                    advisor_id: 0,
+                    discipline: departments[d].name.clone(),
+                    enrollment: terms[entry].name.clone(),
+                    cgpa: 0.0,
+                    can_grad: false,
+                    cur_credit,
+                    cum_credit,
+                },
+                advisor,
+                enrollments,
+            }
+        })
+        .collect();
+
+    Dataset {
+        departments,
+        terms,
+        teachers,
+        courses,
+        students,
+    }
+}
+
+const DEPARTMENTS: [(&str, &str); 14] = [
+    ("Computer Science", "COS"),
+    ("Mathematics", "MAT"),
+    ("Physics", "PHY"),
+    ("Economics", "ECO"),
+    ("Business Administration", "BUS"),
+    ("Political Science", "POS"),
+    ("History", "HTY"),
+    ("Literature", "LIT"),
+    ("Psychology", "PSY"),
+    ("Biology", "BIO"),
+    ("Chemistry", "CHM"),
+    ("Philosophy", "PHI"),
+    ("Journalism", "JMC"),
+    ("Fine Arts", "ART"),
+];
+
+// Course titles and the description's opening word, by level
+const LEVELS: [(&[&str], &str); 4] = [
+    (&["Introduction to {}", "Foundations of {}"], "An introductory"),
+    (&["Methods in {}", "Topics in {}"], "An intermediate"),
+    (&["Advanced {}", "Theory of {}"], "An advanced"),
+    (&["Seminar in {}", "Research in {}"], "A senior"),
+];
+
+const TIMESLOTS: [&str; 12] = [
+    "MWF 08:00", "MWF 09:00", "MWF 10:00", "MWF 11:00", "MWF 13:00", "MWF 14:00",
+    "TR 08:00", "TR 09:30", "TR 11:00", "TR 13:00", "TR 14:30", "TR 16:00",
+];
+
+// Mostly three-credit courses
+const CREDITS: [i32; 10] = [3, 3, 3, 3, 3, 3, 4, 4, 2, 1];
+
+const GRADES: [f32; 11] = [4.0, 3.7, 3.3, 3.0, 2.7, 2.3, 2.0, 1.7, 1.3, 1.0, 0.0];
+
+const FIRST_NAMES: [&str; 32] = [
+    "Ana", "Boris", "Clara", "Daniel", "Elena", "Filip", "Gabriela", "Hristo", "Irina", "Jakub",
+    "Katerina", "Luka", "Maria", "Nikola", "Olga", "Petar", "Radka", "Stefan", "Teodora",
+    "Viktor", "Yana", "Zlatan", "Aylin", "Emre", "Mira", "Ognyan", "Sofia", "Tudor", "Vesna",
+    "Andrei", "Lea", "Marko",
+];
+
+const LAST_NAMES: [&str; 32] = [
+    "Ivanova", "Petrov", "Georgieva", "Dimitrov", "Nikolova", "Stoyanov", "Todorova",
+    "Popescu", "Kovacs", "Novak", "Horvat", "Yilmaz", "Kaya", "Marin", "Pavlova", "Angelov",
+    "Vasileva", "Hristov", "Kolar", "Dragan", "Stan", "Lazarova", "Mihaylov", "Rusu", "Demir",
+    "Kostova", "Iliev", "Bogdan", "Marinova", "Atanasov", "Zhekova", "Radev",
+];
+
+// Departments past the end of the list get a number: "Physics 2", "PHY2"
+fn department(d: usize) -> (String, String) {
+    let (name, code) = DEPARTMENTS[d % DEPARTMENTS.len()];
+
+    match d / DEPARTMENTS.len() {
+        0 => (name.to_owned(), code.to_owned()),
+        round => (format!("{} {}", name, round + 1), format!("{}{}", code, round + 1)),
+    }
+}
+
+// Fall terms run September to December, spring terms January to May
+fn term(first_year: i32, t: usize) -> Term {
+    let year = first_year + (t as i32 + 1) / 2;
+
+    let (name, starts_on, ends_on) = if t.is_multiple_of(2) {
+        ("Fall", format!("{}-09-01", year), format!("{}-12-20", year))
+    } else {
+        ("Spring", format!("{}-01-15", year), format!("{}-05-20", year))
+    };
+
+    Term {
+        id: 0,
+        name: format!("{} {}", name, year),
+        starts_on,
+        ends_on,
+    }
+}
+
+// `n` numbers everyone, teachers first, which keeps the emails unique
+fn person(rng: &mut Rng, n: usize, role: &str, domain: &str, password: &str) -> User {
+    let first = rng.pick(&FIRST_NAMES);
+    let last = rng.pick(&LAST_NAMES);
+
+    User {
+        id: 0,
+        username: format!("{} {}", first, last),
+        password: password.to_owned(),
+        email: format!("{}.{}{}@{}", first, last, n + 1, domain).to_lowercase(),
+        phone: format!("+35988{:07}", rng.below(10_000_000)),
+        verified: true,
+        suspended: false,
+        forcenewpw: false,
+        role: role.to_owned(),
+    }
+}
+
+// Teachers `d`, `d + departments`, … work in department `d`
+fn teacher_in(rng: &mut Rng, d: usize, plan: &SeedPlan) -> usize {
+    let count = (plan.teachers - d).div_ceil(plan.departments);
+
+    d + plan.departments * rng.below(count)
+}
+
+fn nearest_grade(points: f64) -> f32 {
+    GRADES
+        .iter()
+        .copied()
+        .min_by(|a, b| {
+            (*a as f64 - points)
+                .abs()
+                .total_cmp(&(*b as f64 - points).abs())
+        })
+        .unwrap_or(0.0)
+}
+
+// SplitMix64: small, and the same on every platform and release, unlike library generators
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // In [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
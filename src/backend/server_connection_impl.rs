@@ -1,15 +1,31 @@
+use super::backup::{self, Snapshot, Verification};
 use super::db_driver::*;
+use super::error::{bad_request, conflict, forbidden, not_found, unauthenticated};
+use super::export::{self, ExportRequest};
 use super::filter::*;
+use super::history::{self, AcademicHistory, Standing, StandingRules};
+use super::import::{self, CourseRow, EnrollmentRow, ImportKind, ImportReport, UserRow};
+use super::mailer::{self, Email};
+use super::monitoring::{self, Usage};
 use super::password;
+use super::policy::ValidationErrors;
+use super::seed::{self, Dataset, SeedPlan, SeedReport};
+use super::settings::{DatabaseSettings, Settings};
+use super::storage::SCHEMA_VERSION;
 use super::table_models::*;
+use super::tokens;
+use super::totp;
+use super::views::{CourseDetail, RosterEntry, TermClosing, UserResponse};
 
-use anyhow::anyhow;
-use anyhow::Ok;
 use anyhow::Result;
 use chrono::Datelike;
-use regex::Regex;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Statistics {
@@ -22,18 +38,109 @@ pub struct Statistics {
     pub departments: i32,
 }
 
+const REQUIRE_2FA_ROLES: &str = "require_2fa_roles";
+const HOLD_KINDS: &[&str] = &["fees", "documents", "advising", "conduct", "other"];
+const LOCKOUT_ACCOUNT: &str = "account";
+const LOCKOUT_IP: &str = "ip";
+const INVALID_CREDENTIALS: &str = "Invalid email or password.";
+
 pub struct ServerConnection {
     db: DbDriver,
     session: Option<User>,
+    settings: Arc<Settings>,
+    client_ip: Option<String>,
 }
 
 // Public methods
 impl ServerConnection {
-    pub fn new() -> Self {
+    pub fn new(settings: Arc<Settings>) -> Self {
         Self {
-            db: DbDriver::init(),
+            db: DbDriver::init(&settings.database),
+            session: None,
+            settings,
+            client_ip: None,
+        }
+    }
+
+    // Like `new`, but reports a database that cannot be opened instead of panicking
+    pub fn open(settings: Arc<Settings>) -> Result<Self> {
+        Ok(Self {
+            db: DbDriver::open(&settings.database)?,
+            session: None,
+            settings,
+            client_ip: None,
+        })
+    }
+
+    // Works on a fresh database of its own that goes away with the connection, for tests
+    // and tooling. `settings.database` is replaced.
+    pub fn in_memory(settings: &Settings) -> Result<Self> {
+        let mut settings = settings.clone();
+        settings.database = DatabaseSettings {
+            path: PathBuf::from(DatabaseSettings::IN_MEMORY),
+            url: None,
+        };
+
+        Ok(Self {
+            db: DbDriver::in_memory()?,
             session: None,
+            settings: Arc::new(settings),
+            client_ip: None,
+        })
+    }
+
+    // Readiness: the database already has the schema this build expects and takes writes.
+    // Uses a connection of its own that doesn't migrate, so opening it can't make the
+    // check pass.
+    pub fn check_ready(settings: &Settings) -> Result<()> {
+        let mut db = DbDriver::open_as_is(&settings.database)?;
+
+        let version = db.schema_version()?;
+        if version != SCHEMA_VERSION {
+            return Err(anyhow::anyhow!(
+                "Database schema is at version {}, this build expects {}.",
+                version,
+                SCHEMA_VERSION
+            ));
         }
+
+        db.check_writable()
+    }
+
+    // Figures for `/metrics`; unlike the statistics, available without signing in
+    pub fn usage(&self) -> Result<Usage> {
+        let now = chrono::Utc::now().timestamp();
+
+        Ok(Usage {
+            active_sessions: self.db.count(
+                "SESSIONS",
+                &[Filter::Sessions(SessionsFilter::ExpiresAfter(now))],
+            )?,
+            enrollments: self.db.count("STUDENT_COURSES", &[])?,
+        })
+    }
+
+    // An admin connection for local tooling such as `studentctl`, which works on the
+    // database directly instead of signing in over HTTP.
+    pub fn system(settings: Arc<Settings>) -> Self {
+        Self::new(settings).into_system()
+    }
+
+    // Acts as the system admin from here on, whoever was signed in
+    pub fn into_system(mut self) -> Self {
+        self.session = Some(User {
+            id: 0,
+            username: String::from("system"),
+            password: String::new(),
+            email: String::new(),
+            phone: String::new(),
+            verified: true,
+            suspended: false,
+            forcenewpw: false,
+            role: String::from("admin"),
+        });
+
+        self
     }
 
     // fetch all users from the database
@@ -71,81 +178,520 @@ impl ServerConnection {
     }
 
     pub fn register_user(&mut self, user: User) -> Result<()> {
-        if !self.session.is_none() {
-            return Err(anyhow!("Must be signed out."));
+        if self.session.is_some() && !self.is_admin() {
+            return Err(forbidden("Must be signed out."));
         }
 
-        let email_regex = Regex::new(r"^([a-z0-9_+]([a-z0-9_+.]*[a-z0-9_+])?)@aubg\.edu$")?;
-        let phone_regex = Regex::new(r#"^\+?[0-9]{2}[-. ]?[0-9]{4}[-. ]?[0-9]{4}$"#)?;
-        let password_rules = user.password.len() >= 8
-            && user.password.chars().any(|c| c.is_ascii_lowercase())
-            && user.password.chars().any(|c| c.is_ascii_uppercase())
-            && user.password.chars().any(|c| c.is_ascii_digit())
-            && user.password.chars().any(|c| "@$!%*?&".contains(c));
+        let mut user = user.to_owned();
+        let mut errors = ValidationErrors::default();
 
-        if self
-            .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(
-                user.email.to_lowercase().clone(),
-            ))])?
-            .len()
-            > 0
-        {
-            return Err(anyhow!("A user with this email already exists."));
+        self.check_new_user(&mut user, &mut errors)?;
+        self.settings
+            .registration
+            .check_password(&user.password, &mut errors);
+        errors.into_result()?;
+
+        let salt = password::generate_salt();
+        user.password = password::hash(&user.password, salt, &self.settings.auth.hashing);
+
+        let needs_verification = !user.verified;
+        let email = user.email.clone();
+        let role = user.role.clone();
+
+        self.db.insert(vec![ReceiverType::User(user)])?;
+        tracing::info!(%email, %role, "user registered");
+
+        if needs_verification {
+            let binding = self.get_users_by_filters(vec![Filter::Users(UsersFilter::Email(email))])?;
+            let user = binding.first().ok_or_else(|| not_found("User not found."))?;
+
+            // The account exists either way; `/verify/resend` sends another link
+            if let Err(e) = self.send_verification(user) {
+                tracing::warn!(email = %user.email, error = %e, "verification email not sent");
+            }
         }
 
-        if user.username.is_empty() {
-            return Err(anyhow!("Account name cannot be empty."));
+        Ok(())
+    }
+
+    // Confirms the account a verification token was issued for
+    pub fn verify_email(&mut self, token: &str) -> Result<()> {
+        let secret = &self.settings.auth.token_secret;
+        let subject = tokens::verify(secret, tokens::VERIFY_EMAIL, token)?;
+
+        let (id, email) = subject
+            .split_once(':')
+            .ok_or_else(|| bad_request("Invalid or expired token."))?;
+        let id = id
+            .parse::<i32>()
+            .map_err(|_| bad_request("Invalid or expired token."))?;
+
+        let binding = self.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(id))])?;
+        let mut user = binding
+            .first()
+            .ok_or_else(|| not_found("User not found."))?
+            .to_owned();
+
+        // The address changed after the link was sent
+        if user.email != email {
+            return Err(bad_request("Invalid or expired token."));
         }
 
-        if !email_regex.is_match(&user.email) {
-            return Err(anyhow!("Must be a valid AUBG email."));
+        if user.verified {
+            return Ok(());
         }
 
-        if !phone_regex.is_match(&user.phone) && !user.phone.is_empty() {
-            return Err(anyhow!("Invalid phone number."));
+        user.verified = true;
+        user.password = String::new(); // leaves the stored hash untouched
+
+        self.db.update(vec![ReceiverType::User(user)])?;
+
+        Ok(())
+    }
+
+    pub fn resend_verification(&mut self, email: String, password: String) -> Result<()> {
+        let user = self.check_password(email, password)?;
+
+        if user.verified {
+            return Err(conflict("User is already verified."));
         }
 
-        if !password_rules {
-            return Err(anyhow!(
-                "The password does not meet the following criteria:\n
-            - Must be at least 8 characters long\n
-            - Must contain at least 1 uppercase letter\n
-            - Must contain at least 1 lowercase letter\n
-            - Must contain at least 1 number\n
-            - Must contain at least 1 special character (@, $, !, %, *, ?, &)\n"
-            ));
+        self.send_verification(&user)
+    }
+
+    // `second_factor` is either a TOTP code or an unused recovery code
+    pub fn login(
+        &mut self,
+        email: String,
+        password: String,
+        second_factor: Option<String>,
+    ) -> Result<()> {
+        let account = email.to_lowercase();
+        let user = self.check_password(email, password)?;
+
+        if user.forcenewpw {
+            return Err(forbidden("User must change password."));
         }
 
-        let mut user = user.to_owned();
+        if self.settings.auth.require_verification && !user.verified {
+            return Err(forbidden("Email address has not been verified."));
+        }
 
-        let salt = password::generate_salt();
-        user.password = password::hash(&user.password, salt);
+        match self.find_two_factor(user.id)? {
+            Some(two_factor) if two_factor.enabled => {
+                let code = second_factor
+                    .ok_or_else(|| unauthenticated("Two-factor authentication code required."))?;
 
-        self.db.insert(vec![ReceiverType::User(user)])?;
+                // Wrong codes count towards the lockout, otherwise the six digits could be guessed
+                if let Err(e) = self.check_second_factor(two_factor, &code) {
+                    self.record_failed_login(&account)?;
+                    return Err(e);
+                }
+            }
+            _ => {
+                if self.two_factor_required_for(&user.role)? {
+                    return Err(forbidden(
+                        "Two-factor authentication must be set up for this account."
+                    ));
+                }
+            }
+        }
 
+        self.clear_failed_logins(&account)?;
+        tracing::info!(user_id = user.id, "signed in");
+        self.session = Some(user.to_owned());
         Ok(())
     }
 
-    pub fn login(&mut self, email: String, password: String) -> Result<()> {
-        let binding = self.get_users_by_filters(vec![Filter::Users(UsersFilter::Email(email))])?;
-        let user = binding.get(0).ok_or_else(|| anyhow!("User not found."))?; // if none, user not found
+    // Sign-in attempts are tracked per address as well, see `record_failed_login`
+    pub fn set_client_ip(&mut self, ip: Option<String>) {
+        self.client_ip = ip;
+    }
 
-        // If the user is suspended, they cannot login
-        if user.suspended {
-            return Err(anyhow!("User is suspended."));
+    pub fn get_lockouts(&self) -> Result<Vec<LoginAttempt>> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can view lockouts."));
         }
 
-        if user.forcenewpw {
-            return Err(anyhow!("User must change password."));
+        let findings = self.db.find(Table::LoginAttempts, vec![], None)?;
+
+        let attempts = findings
+            .into_iter()
+            .filter_map(|x| {
+                if let ReceiverType::LoginAttempt(attempt) = x {
+                    Some(attempt)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(attempts)
+    }
+
+    pub fn clear_lockout(&mut self, id: i32) -> Result<()> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can clear lockouts."));
+        }
+
+        let findings = self.db.find(
+            Table::LoginAttempts,
+            vec![Filter::LoginAttempts(LoginAttemptsFilter::Id(id))],
+            None,
+        )?;
+
+        if findings.is_empty() {
+            return Err(not_found("Lockout not found."));
+        }
+
+        tracing::info!(lockout_id = id, "lockout cleared");
+        self.db.delete(findings)
+    }
+
+    // Starts (or restarts) TOTP enrollment. Authenticates with the password alone so that
+    // accounts required to use 2FA can still reach this step.
+    pub fn begin_two_factor_enrollment(
+        &mut self,
+        email: String,
+        password: String,
+    ) -> Result<(String, String)> {
+        let user = self.check_password(email, password)?;
+
+        let secret = totp::generate_secret();
+        let uri = totp::otpauth_uri(&secret, &user.email);
+
+        match self.find_two_factor(user.id)? {
+            Some(two_factor) if two_factor.enabled => {
+                return Err(conflict("Two-factor authentication is already enabled."));
+            }
+            Some(mut two_factor) => {
+                two_factor.secret = secret.clone();
+                two_factor.last_step = 0;
+                self.db.update(vec![ReceiverType::TwoFactor(two_factor)])?;
+            }
+            None => {
+                let two_factor = TwoFactor {
+                    id: 0,
+                    user_id: user.id,
+                    secret: secret.clone(),
+                    enabled: false,
+                    last_step: 0,
+                };
+                self.db.insert(vec![ReceiverType::TwoFactor(two_factor)])?;
+            }
+        }
+
+        Ok((secret, uri))
+    }
+
+    // Enables TOTP once the user proves their app is set up, and hands out recovery codes.
+    // The codes are only ever shown here; we keep their hashes.
+    pub fn confirm_two_factor_enrollment(
+        &mut self,
+        email: String,
+        password: String,
+        code: String,
+    ) -> Result<Vec<String>> {
+        let user = self.check_password(email, password)?;
+
+        let mut two_factor = match self.find_two_factor(user.id)? {
+            Some(t) if !t.enabled => t,
+            Some(_) => return Err(conflict("Two-factor authentication is already enabled.")),
+            None => return Err(conflict("Two-factor enrollment has not been started.")),
+        };
+
+        let step = totp::verify(&two_factor.secret, &code, two_factor.last_step)?
+            .ok_or_else(|| unauthenticated("Invalid two-factor authentication code."))?;
+
+        two_factor.enabled = true;
+        two_factor.last_step = step;
+        self.db.update(vec![ReceiverType::TwoFactor(two_factor)])?;
+
+        self.replace_recovery_codes(user.id)
+    }
+
+    pub fn regenerate_recovery_codes(&mut self) -> Result<Vec<String>> {
+        let user = self.current_user()?;
+
+        match self.find_two_factor(user.id)? {
+            Some(t) if t.enabled => self.replace_recovery_codes(user.id),
+            _ => Err(conflict("Two-factor authentication is not enabled.")),
+        }
+    }
+
+    // Users may turn off their own 2FA; admins may reset anyone's (lost device)
+    pub fn disable_two_factor(&mut self, user_id: i32) -> Result<()> {
+        if let Some(session) = &self.session {
+            if session.id != user_id && session.role.to_lowercase() != "admin" {
+                return Err(forbidden(
+                    "You do not have permission to change two-factor settings for this user."
+                ));
+            }
+        } else {
+            return Err(unauthenticated("Must be signed in."));
+        }
+
+        if let Some(two_factor) = self.find_two_factor(user_id)? {
+            self.db.delete(vec![ReceiverType::TwoFactor(two_factor)])?;
         }
 
-        // check hash for validity and then compare both server and client password hashes
-        if password::verify(&user.password, &password) {
-            self.session = Some(user.to_owned());
-            Ok(())
+        let codes = self.db.find(
+            Table::RecoveryCodes,
+            vec![Filter::RecoveryCodes(RecoveryCodesFilter::UserId(user_id))],
+            None,
+        )?;
+        self.db.delete(codes)?;
+
+        Ok(())
+    }
+
+    pub fn get_two_factor_policy(&self) -> Result<Vec<String>> {
+        let findings = self.db.find(
+            Table::SystemSettings,
+            vec![Filter::SystemSettings(SystemSettingsFilter::Key(
+                REQUIRE_2FA_ROLES.to_string(),
+            ))],
+            None,
+        )?;
+
+        let roles = match findings.into_iter().next() {
+            Some(ReceiverType::SystemSetting(s)) => s
+                .value
+                .split(',')
+                .map(|r| r.trim().to_lowercase())
+                .filter(|r| !r.is_empty())
+                .collect(),
+            _ => vec![],
+        };
+
+        Ok(roles)
+    }
+
+    // Roles that must have 2FA enabled to sign in
+    pub fn set_two_factor_policy(&mut self, roles: Vec<String>) -> Result<()> {
+        if let Some(session) = &self.session {
+            match session.role.to_lowercase().as_str() {
+                "admin" => {
+                    let roles = roles
+                        .iter()
+                        .map(|r| r.trim().to_lowercase())
+                        .collect::<Vec<_>>();
+
+                    if let Some(r) = roles
+                        .iter()
+                        .find(|r| !["admin", "teacher", "student"].contains(&r.as_str()))
+                    {
+                        return Err(bad_request(format!("Unknown role: {}", r)));
+                    }
+
+                    let setting = SystemSetting {
+                        key: REQUIRE_2FA_ROLES.to_string(),
+                        value: roles.join(","),
+                    };
+                    self.db.insert(vec![ReceiverType::SystemSetting(setting)])?;
+                    tracing::info!(roles = %roles.join(","), "two-factor policy changed");
+
+                    Ok(())
+                }
+                _ => Err(forbidden("Only admins can change the two-factor policy.")),
+            }
         } else {
-            Err(anyhow!("Invalid username or password."))
+            Err(unauthenticated("Must be signed in."))
+        }
+    }
+
+    // Issues a session token for the signed in user
+    pub fn create_session(&mut self) -> Result<String> {
+        let user_id = match &self.session {
+            Some(s) => s.id,
+            None => return Err(unauthenticated("Must be signed in.")),
+        };
+
+        let token = tokens::generate_opaque();
+        let now = chrono::Utc::now().timestamp();
+
+        let session = Session {
+            id: 0,
+            user_id,
+            token_hash: tokens::digest(&token),
+            created_at: now,
+            expires_at: now + self.settings.auth.session_ttl_hours * 60 * 60,
+        };
+        self.db.insert(vec![ReceiverType::Session(session)])?;
+
+        Ok(token)
+    }
+
+    // Signs in with a token from `create_session` instead of a password
+    pub fn resume_session(&mut self, token: &str) -> Result<()> {
+        let findings = self.db.find(
+            Table::Sessions,
+            vec![Filter::Sessions(SessionsFilter::TokenHash(tokens::digest(token)))],
+            None,
+        )?;
+
+        let session = match findings.into_iter().next() {
+            Some(ReceiverType::Session(s)) => s,
+            _ => return Err(unauthenticated("Invalid or expired session.")),
+        };
+
+        if session.expires_at < chrono::Utc::now().timestamp() {
+            self.db.delete(vec![ReceiverType::Session(session)])?;
+            return Err(unauthenticated("Invalid or expired session."));
+        }
+
+        let binding =
+            self.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(session.user_id))])?;
+        let user = binding.first().ok_or_else(|| not_found("User not found."))?;
+
+        if user.suspended {
+            return Err(forbidden("User is suspended."));
+        }
+
+        self.session = Some(user.to_owned());
+
+        Ok(())
+    }
+
+    pub fn end_session(&mut self, token: &str) -> Result<()> {
+        let findings = self.db.find(
+            Table::Sessions,
+            vec![Filter::Sessions(SessionsFilter::TokenHash(tokens::digest(token)))],
+            None,
+        )?;
+
+        if findings.is_empty() {
+            return Err(unauthenticated("Invalid or expired session."));
+        }
+
+        self.db.delete(findings)?;
+        tracing::info!(user_id = self.session.as_ref().map(|u| u.id), "signed out");
+        self.session = None;
+
+        Ok(())
+    }
+
+    pub fn current_user(&self) -> Result<User> {
+        self.session
+            .clone()
+            .ok_or_else(|| unauthenticated("Must be signed in."))
+    }
+
+    // For callers that already authenticated the user, such as the auth middleware
+    pub(crate) fn set_session(&mut self, user: User) {
+        self.session = Some(user);
+    }
+
+    // How an account is shown to whoever is signed in on this connection
+    pub fn view_user(&self, user: &User) -> UserResponse {
+        UserResponse::for_viewer(self.session.as_ref(), user)
+    }
+
+    pub fn view_users(&self, users: &[User]) -> Vec<UserResponse> {
+        users.iter().map(|u| self.view_user(u)).collect()
+    }
+
+    // Sends a reset link if the account exists. Callers should not reveal whether it did.
+    pub fn request_password_reset(&mut self, email: String) -> Result<()> {
+        let binding = self.get_users_by_filters(vec![Filter::Users(UsersFilter::Email(
+            email.trim().to_lowercase(),
+        ))])?;
+        let user = match binding.first() {
+            Some(u) => u.to_owned(),
+            None => return Ok(()),
+        };
+
+        let token = tokens::generate_opaque();
+        let reset = PasswordReset {
+            id: 0,
+            user_id: user.id,
+            token_hash: tokens::digest(&token),
+            expires_at: chrono::Utc::now().timestamp() + self.settings.auth.reset_ttl_minutes * 60,
+            used: false,
+        };
+        self.db.insert(vec![ReceiverType::PasswordReset(reset)])?;
+
+        let email = Email {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hello {},\n\nUse the token below to choose a new password:\n\n{}\n\nThe token expires in {} minutes and can only be used once. If you did not ask for a reset, you can ignore this message.",
+                user.username, token, self.settings.auth.reset_ttl_minutes
+            ),
+        };
+
+        // Failing here would tell the caller the address has an account
+        if let Err(e) = mailer::from_settings(&self.settings).and_then(|m| m.send(&email)) {
+            tracing::warn!(email = %user.email, error = %e, "password reset email not sent");
+        }
+
+        Ok(())
+    }
+
+    // Consumes a reset token, sets the new password and signs the user out everywhere
+    pub fn reset_password(&mut self, token: &str, new_password: String) -> Result<()> {
+        let findings = self.db.find(
+            Table::PasswordResets,
+            vec![Filter::PasswordResets(PasswordResetsFilter::TokenHash(
+                tokens::digest(token),
+            ))],
+            None,
+        )?;
+
+        let reset = match findings.into_iter().next() {
+            Some(ReceiverType::PasswordReset(r)) => r,
+            _ => return Err(bad_request("Invalid or expired token.")),
+        };
+
+        if reset.used || reset.expires_at < chrono::Utc::now().timestamp() {
+            return Err(bad_request("Invalid or expired token."));
         }
+
+        let mut errors = ValidationErrors::default();
+        self.settings
+            .registration
+            .check_password(&new_password, &mut errors);
+        errors.into_result()?;
+
+        let binding =
+            self.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(reset.user_id))])?;
+        let mut user = binding
+            .first()
+            .ok_or_else(|| not_found("User not found."))?
+            .to_owned();
+
+        let salt = password::generate_salt();
+        user.password = password::hash(&new_password, salt, &self.settings.auth.hashing);
+        user.forcenewpw = false;
+        self.db.update(vec![ReceiverType::User(user.clone())])?;
+
+        // Burn this token and any other outstanding ones for the account
+        let outstanding = self
+            .db
+            .find(
+                Table::PasswordResets,
+                vec![
+                    Filter::PasswordResets(PasswordResetsFilter::UserId(user.id)),
+                    Filter::PasswordResets(PasswordResetsFilter::Used(false)),
+                ],
+                None,
+            )?
+            .into_iter()
+            .filter_map(|x| {
+                if let ReceiverType::PasswordReset(mut r) = x {
+                    r.used = true;
+                    Some(ReceiverType::PasswordReset(r))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        self.db.update(outstanding)?;
+
+        self.revoke_sessions(user.id)?;
+        tracing::info!(user_id = user.id, "password reset");
+
+        Ok(())
     }
 
     pub fn update_user(&mut self, user: User) -> Result<()> {
@@ -155,7 +701,7 @@ impl ServerConnection {
                 _ => self.update_user_as_student(user)?,
             }
         } else {
-            return Err(anyhow!("Must be signed in."));
+            return Err(unauthenticated("Must be signed in."));
         }
         Ok(())
     }
@@ -165,31 +711,82 @@ impl ServerConnection {
             match s.role.to_lowercase().as_str() {
                 "admin" => {
                     if user.id != s.id {
+                        self.check_user_removable(&user)?;
                         self.db.delete(vec![ReceiverType::User(user.clone())])?;
+                        tracing::info!(user_id = user.id, by = s.id, "user deleted");
 
                         Ok(())
                     } else {
-                        Err(anyhow!(
+                        Err(forbidden(
                             "You cannot delete your own account as an administrator."
                         ))
                     }
                 }
                 _ => {
                     if user.id == s.id {
+                        self.check_user_removable(&user)?;
                         self.db.delete(vec![ReceiverType::User(user.clone())])?;
+                        tracing::info!(user_id = user.id, "account deleted by its owner");
 
                         Ok(())
                     } else {
-                        Err(anyhow!("You do not have permission to delete this user."))
+                        Err(forbidden("You do not have permission to delete this user."))
                     }
                 }
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
+        }
+    }
+
+    // Enrollments and taught courses are academic records, so they have to be dropped or
+    // handed over before the account they belong to can go
+    fn check_user_removable(&self, user: &User) -> Result<()> {
+        let enrollments = self.db.count(
+            "STUDENT_COURSES",
+            &[Filter::StudentCourses(StudentCoursesFilter::StudentId(user.id))],
+        )?;
+        if enrollments > 0 {
+            return Err(conflict(
+                "The user is still enrolled in courses. Drop the enrollments first.",
+            ));
+        }
+
+        let taught = self.db.count(
+            "COURSES",
+            &[Filter::Courses(CoursesFilter::TeacherId(user.id))],
+        )?;
+        if taught > 0 {
+            return Err(conflict(
+                "The user still teaches courses. Reassign or remove them first.",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_courses_removable(&self, courses: &[Courses]) -> Result<()> {
+        for course in courses {
+            let enrollments = self.db.count(
+                "STUDENT_COURSES",
+                &[Filter::StudentCourses(StudentCoursesFilter::CourseId(course.id))],
+            )?;
+            if enrollments > 0 {
+                return Err(conflict(format!(
+                    "Course {} still has enrollments. No action was taken.",
+                    course.id
+                )));
+            }
         }
+
+        Ok(())
     }
 
     pub fn register_courses(&mut self, courses: Vec<Courses>) -> Result<()> {
+        if courses.iter().any(|c| c.capacity < 0) {
+            return Err(bad_request("Capacity cannot be negative."));
+        }
+
         if let Some(s) = &self.session {
             match s.role.to_lowercase().as_str() {
                 "admin" => {
@@ -208,7 +805,7 @@ impl ServerConnection {
                         .filter_map(|x| {
                             if x.teacher_id != s.id {
                                 Some(
-                                    anyhow!(
+                                    forbidden(
                                         "You do not have permission to register courses on someone else's behalf."
                                     )
                                 )
@@ -218,9 +815,9 @@ impl ServerConnection {
                         })
                         .collect::<Vec<_>>();
 
-                    if errors.len() > 0 {
+                    if !errors.is_empty() {
                         return Err(
-                            anyhow!(
+                            forbidden(
                                 "You do not have permission to register courses on someone else's behalf. No action was taken."
                             )
                         );
@@ -235,10 +832,10 @@ impl ServerConnection {
 
                     Ok(())
                 }
-                _ => Err(anyhow!("You do not have permission to register courses.")),
+                _ => Err(forbidden("You do not have permission to register courses.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -251,6 +848,7 @@ impl ServerConnection {
                         .map(|x| ReceiverType::Course(x.to_owned()))
                         .collect();
 
+                    self.check_courses_removable(&courses)?;
                     self.db.delete(upcast)?;
 
                     Ok(())
@@ -260,15 +858,15 @@ impl ServerConnection {
                         .iter()
                         .filter_map(|x| {
                             if x.teacher_id != session.id {
-                                Some(anyhow!("You do not have permission to remove this course."))
+                                Some(forbidden("You do not have permission to remove this course."))
                             } else {
                                 None
                             }
                         })
                         .collect::<Vec<_>>();
 
-                    if errors.len() > 0 {
-                        return Err(anyhow!(
+                    if !errors.is_empty() {
+                        return Err(forbidden(
                             "Some courses to not belong to you. No action was taken."
                         ));
                     }
@@ -278,18 +876,23 @@ impl ServerConnection {
                         .map(|x| ReceiverType::Course(x.to_owned()))
                         .collect();
 
+                    self.check_courses_removable(&courses)?;
                     self.db.delete(upcast)?;
 
                     Ok(())
                 }
-                _ => Err(anyhow!("You do not have permission to remove courses.")),
+                _ => Err(forbidden("You do not have permission to remove courses.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
     pub fn update_courses(&mut self, courses: Vec<Courses>) -> Result<()> {
+        if courses.iter().any(|c| c.capacity < 0) {
+            return Err(bad_request("Capacity cannot be negative."));
+        }
+
         if let Some(session) = &self.session {
             match session.role.to_lowercase().as_str() {
                 "admin" => {
@@ -307,15 +910,15 @@ impl ServerConnection {
                         .iter()
                         .filter_map(|x| {
                             if x.teacher_id != session.id {
-                                Some(anyhow!("You do not have permission to update this course."))
+                                Some(forbidden("You do not have permission to update this course."))
                             } else {
                                 None
                             }
                         })
                         .collect::<Vec<_>>();
 
-                    if errors.len() > 0 {
-                        return Err(anyhow!(
+                    if !errors.is_empty() {
+                        return Err(forbidden(
                             "Some courses to not belong to you. No action was taken."
                         ));
                     }
@@ -329,10 +932,10 @@ impl ServerConnection {
 
                     Ok(())
                 }
-                _ => Err(anyhow!("You do not have permission to update courses.")),
+                _ => Err(forbidden("You do not have permission to update courses.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -361,12 +964,17 @@ impl ServerConnection {
         Ok(users)
     }
 
+    // Every course for an empty query, otherwise the course with exactly that id
     pub fn search_courses(&self, query: String) -> Result<Vec<Courses>> {
-        let findings = self.db.find(
-            Table::Courses,
-            vec![],
-            None,
-        )?;
+        let filters = match query.trim() {
+            "" => vec![],
+            id => match id.parse() {
+                Ok(id) => vec![Filter::Courses(CoursesFilter::Id(id))],
+                Err(_) => return Ok(vec![]),
+            },
+        };
+
+        let findings = self.db.find(Table::Courses, filters, None)?;
 
         let courses = findings
             .into_iter()
@@ -377,14 +985,59 @@ impl ServerConnection {
                     None
                 }
             })
-            .filter(|x| {
-                x.id.to_string().contains(&query)
-            })
             .collect();
 
         Ok(courses)
     }
 
+    // A course with its teacher and their department, in one query
+    pub fn get_course_with_teacher(&self, id: i32) -> Result<CourseWithTeacher> {
+        self.db
+            .courses_with_teachers(vec![Filter::Courses(CoursesFilter::Id(id))])?
+            .pop()
+            .ok_or_else(|| not_found("Course not found."))
+    }
+
+    // A course with its instructor and this term's enrollment. The instructor and admins
+    // also get the roster.
+    pub fn get_course_detail(&self, id: i32) -> Result<CourseDetail> {
+        let found = self.get_course_with_teacher(id)?;
+        let semester = self.current_semester();
+
+        let mut enrolled = self.db.enrollments_with_students(vec![
+            Filter::StudentCourses(StudentCoursesFilter::CourseId(id)),
+            Filter::StudentCourses(StudentCoursesFilter::Semester(semester.clone())),
+        ])?;
+        enrolled.sort_by(|a, b| a.student.username.cmp(&b.student.username));
+
+        let sees_roster = match &self.session {
+            Some(s) => s.role.to_lowercase() == "admin" || s.id == found.course.teacher_id,
+            None => false,
+        };
+
+        Ok(CourseDetail {
+            user: self.view_user(&found.teacher),
+            teacher_account: found.teacher_account,
+            department: found.department,
+            enrolled: enrolled.len(),
+            remaining_seats: match found.course.capacity {
+                0 => None,
+                capacity => Some((capacity - enrolled.len() as i32).max(0)),
+            },
+            roster: sees_roster.then(|| {
+                enrolled
+                    .iter()
+                    .map(|e| RosterEntry {
+                        student: self.view_user(&e.student),
+                        grade: e.enrollment.grade,
+                    })
+                    .collect()
+            }),
+            semester,
+            course: found.course,
+        })
+    }
+
     pub fn get_departments(&self) -> Result<Vec<Departments>> {
         let findings = self.db.find(
             Table::Departments,
@@ -424,7 +1077,7 @@ impl ServerConnection {
             })
             .collect();
 
-        let department = departments.get(0).ok_or_else(|| anyhow!("Department not found."))?;
+        let department = departments.first().ok_or_else(|| not_found("Department not found."))?;
 
         Ok(department.to_owned())
     }
@@ -441,10 +1094,10 @@ impl ServerConnection {
 
                     Ok(())
                 }
-                _ => Err(anyhow!("Only admins can create departments.")),
+                _ => Err(forbidden("Only admins can create departments.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -456,10 +1109,10 @@ impl ServerConnection {
 
                     Ok(())
                 }
-                _ => Err(anyhow!("Only admins can remove departments.")),
+                _ => Err(forbidden("Only admins can remove departments.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -484,6 +1137,25 @@ impl ServerConnection {
         Ok(teacher_accounts)
     }
 
+    pub fn get_teacher_account(&self, teacher_id: i32) -> Result<TeacherAccount> {
+        let findings = self.db.find(
+            Table::TeacherAccount,
+            vec![Filter::TeacherAccount(TeacherAccountFilter::TeacherId(teacher_id))],
+            None,
+        )?;
+
+        findings
+            .into_iter()
+            .find_map(|x| {
+                if let ReceiverType::TeacherAccount(teacher_account) = x {
+                    Some(teacher_account)
+                } else {
+                    None
+                }
+            })
+            .ok_or_else(|| not_found("A teacher account with this Teacher ID does not exist."))
+    }
+
     pub fn update_teacher_account(&mut self, teacher_account: TeacherAccount) -> Result<()> {
         if let Some(session) = &self.session {
             match session.role.to_lowercase().as_str() {
@@ -491,10 +1163,10 @@ impl ServerConnection {
                     self.db.update(vec![ReceiverType::TeacherAccount(teacher_account)])?;
                     Ok(())
                 }
-                _ => Err(anyhow!("Only admins can update teacher accounts.")),
+                _ => Err(forbidden("Only admins can update teacher accounts.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -508,7 +1180,7 @@ impl ServerConnection {
                             let c = self.transmute_course_to_student_course(x.to_owned());
                             if c.student_id != session.id {
                                 Some(
-                                    anyhow!(
+                                    forbidden(
                                         "You do not have permission to register courses on someone else's behalf."
                                     )
                                 )
@@ -518,15 +1190,15 @@ impl ServerConnection {
                         })
                         .collect::<Vec<_>>();
 
-                    if errors.len() > 0 {
+                    if !errors.is_empty() {
                         return Err(
-                            anyhow!(
+                            forbidden(
                                 "You do not have permission to register courses on someone else's behalf. No action was taken."
                             )
                         );
                     }
 
-                    let upcast = courses
+                    let upcast: Vec<ReceiverType> = courses
                         .iter()
                         .map(|x| {
                             ReceiverType::StudentCourse(
@@ -534,15 +1206,72 @@ impl ServerConnection {
                             )
                         })
                         .collect();
+                    if let Some(hold) = self
+                        .active_holds(session.id)?
+                        .into_iter()
+                        .find(|h| h.blocks_registration)
+                    {
+                        return Err(forbidden(format!(
+                            "A {} hold blocks your registration: {}",
+                            hold.kind, hold.reason
+                        )));
+                    }
 
-                    self.db.insert(upcast)?;
+                    let semester = self.current_semester();
+                    self.check_standing(session.id, &courses, &semester)?;
+
+                    // Counted inside the transaction with the course rows locked, so two
+                    // students can't both take the last seat
+                    let student_id = session.id;
+                    self.db.transaction(|db| {
+                        let mut ids: Vec<i32> = courses.iter().map(|c| c.id).collect();
+                        ids.sort_unstable();
+                        for id in ids {
+                            db.lock(
+                                Table::Courses.name(),
+                                &[Filter::Courses(CoursesFilter::Id(id))],
+                            )?;
+                        }
+
+                        for course in &courses {
+                            let enrolled = db.count(
+                                Table::StudentCourses.name(),
+                                &[
+                                    Filter::StudentCourses(StudentCoursesFilter::StudentId(student_id)),
+                                    Filter::StudentCourses(StudentCoursesFilter::CourseId(course.id)),
+                                    Filter::StudentCourses(StudentCoursesFilter::Semester(semester.clone())),
+                                ],
+                            )?;
+
+                            if enrolled > 0 {
+                                return Err(conflict(format!(
+                                    "You are already enrolled in {}.",
+                                    course.course_nr
+                                )));
+                            }
+                        }
+
+                        for course in courses.iter().filter(|c| c.capacity > 0) {
+                            let taken = db.count(
+                                Table::StudentCourses.name(),
+                                &[
+                                    Filter::StudentCourses(StudentCoursesFilter::CourseId(course.id)),
+                                    Filter::StudentCourses(StudentCoursesFilter::Semester(semester.clone())),
+                                ],
+                            )?;
+
+                            if taken >= course.capacity as i64 {
+                                return Err(conflict(format!("{} is full.", course.course_nr)));
+                            }
+                        }
 
-                    Ok(())
+                        db.insert(upcast)
+                    })
                 }
-                _ => Err(anyhow!("You do not have permission to enroll courses.")),
+                _ => Err(forbidden("You do not have permission to enroll courses.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -569,42 +1298,238 @@ impl ServerConnection {
 
                     Ok(courses)
                 }
-                _ => Err(anyhow!("You are not a student.")),
+                _ => Err(forbidden("You are not a student.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
-    pub fn get_student_standing(&self) -> Result<StudentAccount> {
+    // The signed in student's enrollments, each with its course
+    pub fn list_enrollments_with_courses(&self) -> Result<Vec<EnrollmentWithCourse>> {
         if let Some(session) = &self.session {
             match session.role.to_lowercase().as_str() {
-                "student" => {
-                    let findings = self.db.find(
-                        Table::StudentAccount,
-                        vec![Filter::StudentAccount(StudentAccountFilter::StudentId(
-                        session.id
-                        ))],
-                        None
-                    );
-
-                    let student = findings?
+                "student" => self.db.enrollments_with_courses(vec![Filter::StudentCourses(
+                    StudentCoursesFilter::StudentId(session.id),
+                )]),
+                _ => Err(forbidden("You are not a student.")),
+            }
+        } else {
+            Err(unauthenticated("Must be signed in."))
+        }
+    }
+
+    // Open to the student themselves, their advisor and admins. It is the student's
+    // transcript, so a transcript hold keeps it from them but not from staff.
+    pub fn academic_history(&self, student_id: i32) -> Result<AcademicHistory> {
+        self.check_student_access(student_id, "history")?;
+
+        if self.session.as_ref().is_some_and(|s| s.id == student_id) {
+            if let Some(hold) = self
+                .active_holds(student_id)?
+                .into_iter()
+                .find(|h| h.blocks_transcripts)
+            {
+                return Err(forbidden(format!(
+                    "A {} hold keeps your transcript back: {}",
+                    hold.kind, hold.reason
+                )));
+            }
+        }
+
+        let enrollments = self.db.enrollments_with_courses(vec![Filter::StudentCourses(
+            StudentCoursesFilter::StudentId(student_id),
+        )])?;
+
+        Ok(history::build(
+            student_id,
+            enrollments,
+            &self.get_terms()?,
+            &self.settings.standing,
+        ))
+    }
+
+    // Every standing decided for the student, oldest first; open to the same people as
+    // their history
+    pub fn standing_history(&self, student_id: i32) -> Result<Vec<StandingRecord>> {
+        self.check_student_access(student_id, "standing")?;
+
+        self.standing_records(student_id)
+    }
+
+    // Every hold placed on the student, newest first, released ones included
+    pub fn get_holds(&self, student_id: i32) -> Result<Vec<Hold>> {
+        self.check_student_access(student_id, "holds")?;
+
+        let mut holds = self.find_holds(vec![Filter::Holds(HoldsFilter::StudentId(student_id))])?;
+        holds.sort_by_key(|h| std::cmp::Reverse((h.placed_at, h.id)));
+
+        Ok(holds)
+    }
+
+    // Records who placed the hold and when, and tells the student
+    pub fn place_hold(&mut self, hold: Hold) -> Result<()> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| unauthenticated("Must be signed in."))?;
+        if session.role.to_lowercase() != "admin" {
+            return Err(forbidden("Only admins can place holds."));
+        }
+
+        let student = self
+            .db
+            .find(
+                Table::StudentAccount,
+                vec![Filter::StudentAccount(StudentAccountFilter::StudentId(hold.student_id))],
+                None,
+            )?;
+        if student.is_empty() {
+            return Err(not_found("Student not found."));
+        }
+
+        let mut errors = ValidationErrors::default();
+
+        if !HOLD_KINDS.contains(&hold.kind.as_str()) {
+            errors.add(
+                "kind",
+                "unknown",
+                &format!("Hold kind must be one of: {}.", HOLD_KINDS.join(", ")),
+            );
+        }
+
+        if hold.reason.trim().is_empty() {
+            errors.add("reason", "required", "Reason cannot be empty.");
+        }
+
+        if !hold.blocks_registration && !hold.blocks_transcripts {
+            errors.add(
+                "blocks",
+                "nothing_blocked",
+                "A hold must block registration, transcripts or both.",
+            );
+        }
+
+        errors.into_result()?;
+
+        let now = chrono::Utc::now().timestamp();
+        let hold = Hold {
+            id: 0,
+            placed_by: session.id,
+            placed_at: now,
+            released_by: 0,
+            released_at: 0,
+            ..hold
+        };
+        let notice = Notification {
+            id: 0,
+            user_id: hold.student_id,
+            subject: format!("A {} hold was placed on your account", hold.kind),
+            body: format!(
+                "{}\n\nUntil it is released you cannot {}.",
+                hold.reason,
+                blocked_by(&hold)
+            ),
+            created_at: now,
+        };
+        tracing::info!(student_id = hold.student_id, kind = %hold.kind, by = session.id, "hold placed");
+
+        self.db.transaction(|db| {
+            db.insert(vec![ReceiverType::Hold(hold), ReceiverType::Notification(notice)])
+        })
+    }
+
+    pub fn release_hold(&mut self, id: i32) -> Result<()> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| unauthenticated("Must be signed in."))?;
+        if session.role.to_lowercase() != "admin" {
+            return Err(forbidden("Only admins can release holds."));
+        }
+
+        let hold = self
+            .find_holds(vec![Filter::Holds(HoldsFilter::Id(id))])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| not_found("Hold not found."))?;
+        if hold.released_at != 0 {
+            return Err(conflict("This hold has already been released."));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let notice = Notification {
+            id: 0,
+            user_id: hold.student_id,
+            subject: format!("The {} hold on your account was released", hold.kind),
+            body: format!("You can {} again, unless another hold says otherwise.", blocked_by(&hold)),
+            created_at: now,
+        };
+        let hold = Hold {
+            released_by: session.id,
+            released_at: now,
+            ..hold
+        };
+        tracing::info!(hold_id = id, student_id = hold.student_id, by = session.id, "hold released");
+
+        self.db.transaction(|db| {
+            db.update(vec![ReceiverType::Hold(hold)])?;
+            db.insert(vec![ReceiverType::Notification(notice)])
+        })
+    }
+
+    // The signed in user's notifications, newest first
+    pub fn get_notifications(&self) -> Result<Vec<Notification>> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| unauthenticated("Must be signed in."))?;
+
+        let mut notifications: Vec<Notification> = self
+            .db
+            .find(
+                Table::Notifications,
+                vec![Filter::Notifications(NotificationsFilter::UserId(session.id))],
+                None,
+            )?
+            .into_iter()
+            .filter_map(|x| match x {
+                ReceiverType::Notification(n) => Some(n),
+                _ => None,
+            })
+            .collect();
+        notifications.sort_by_key(|n| std::cmp::Reverse((n.created_at, n.id)));
+
+        Ok(notifications)
+    }
+
+    pub fn get_student_standing(&self) -> Result<StudentAccount> {
+        if let Some(session) = &self.session {
+            match session.role.to_lowercase().as_str() {
+                "student" => {
+                    let findings = self.db.find(
+                        Table::StudentAccount,
+                        vec![Filter::StudentAccount(StudentAccountFilter::StudentId(
+                        session.id
+                        ))],
+                        None
+                    );
+
+                    findings?
                         .into_iter()
-                        .filter_map(|x| {
+                        .find_map(|x| {
                             if let ReceiverType::StudentAccount(student) = x {
                                 Some(student)
                             } else {
                                 None
                             }
                         })
-                        .collect::<Vec<_>>();
-
-                    Ok(student[0].to_owned())
+                        .ok_or_else(|| not_found("Student account not found."))
                 }
-                _ => Err(anyhow!("You are not a student.")),
+                _ => Err(forbidden("You are not a student.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -618,7 +1543,7 @@ impl ServerConnection {
                             let c = self.transmute_course_to_student_course(x.to_owned());
                             if c.student_id != session.id {
                                 Some(
-                                    anyhow!(
+                                    forbidden(
                                         "You do not have permission to register courses on someone else's behalf."
                                     )
                                 )
@@ -628,9 +1553,9 @@ impl ServerConnection {
                         })
                         .collect::<Vec<_>>();
 
-                    if errors.len() > 0 {
+                    if !errors.is_empty() {
                         return Err(
-                            anyhow!(
+                            forbidden(
                                 "You do not have permission to register courses on someone else's behalf. No action was taken."
                             )
                         );
@@ -649,10 +1574,10 @@ impl ServerConnection {
 
                     Ok(())
                 }
-                _ => Err(anyhow!("You do not have permission to drop courses.")),
+                _ => Err(forbidden("You do not have permission to drop courses.")),
             }
         } else {
-            Err(anyhow!("Must be signed in."))
+            Err(unauthenticated("Must be signed in."))
         }
     }
 
@@ -680,6 +1605,416 @@ impl ServerConnection {
         }
     }
 
+    pub fn get_terms(&self) -> Result<Vec<Term>> {
+        self.find_terms(vec![])
+    }
+
+    // The term enrollments made today are filed under, if one has been set up
+    pub fn current_term(&self) -> Result<Option<Term>> {
+        let today = chrono::Local::now().date_naive().to_string();
+        let terms = self.find_terms(vec![Filter::Terms(TermsFilter::Covering(today))])?;
+
+        Ok(terms.into_iter().next())
+    }
+
+    pub fn new_term(&mut self, term: Term) -> Result<()> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can set up terms."));
+        }
+
+        let mut errors = ValidationErrors::default();
+
+        if term.name.trim().is_empty() {
+            errors.add("name", "required", "Term name cannot be empty.");
+        }
+
+        let starts_on = chrono::NaiveDate::parse_from_str(&term.starts_on, "%Y-%m-%d");
+        let ends_on = chrono::NaiveDate::parse_from_str(&term.ends_on, "%Y-%m-%d");
+
+        if starts_on.is_err() {
+            errors.add("starts_on", "invalid_format", "Start date must be YYYY-MM-DD.");
+        }
+
+        if ends_on.is_err() {
+            errors.add("ends_on", "invalid_format", "End date must be YYYY-MM-DD.");
+        }
+
+        if let (Some(s), Some(e)) = (starts_on.ok(), ends_on.ok()) {
+            if e < s {
+                errors.add("ends_on", "before_start", "A term cannot end before it starts.");
+            }
+
+            let overlapping = self.get_terms()?.into_iter().find(|t| {
+                t.starts_on.as_str() <= term.ends_on.as_str()
+                    && t.ends_on.as_str() >= term.starts_on.as_str()
+            });
+
+            if let Some(t) = overlapping {
+                errors.add(
+                    "starts_on",
+                    "overlap",
+                    &format!("Overlaps with term {}.", t.name),
+                );
+            }
+        }
+
+        if !self
+            .find_terms(vec![Filter::Terms(TermsFilter::Name(term.name.clone()))])?
+            .is_empty()
+        {
+            errors.add("name", "taken", "A term with this name already exists.");
+        }
+
+        errors.into_result()?;
+
+        self.db.insert(vec![ReceiverType::Term(term)])
+    }
+
+    pub fn remove_term(&mut self, id: i32) -> Result<()> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can remove terms."));
+        }
+
+        let term = self
+            .find_terms(vec![Filter::Terms(TermsFilter::Id(id))])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| not_found("Term not found."))?;
+
+        self.db.delete(vec![ReceiverType::Term(term)])
+    }
+
+    // Decides the standing of every student enrolled in the term from its grades, records
+    // it and notifies the students it matters to. Closing a term again, say after a grade
+    // change, records only the standings that came out differently.
+    pub fn close_term(&mut self, id: i32) -> Result<TermClosing> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can close terms."));
+        }
+
+        let term = self
+            .find_terms(vec![Filter::Terms(TermsFilter::Id(id))])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| not_found("Term not found."))?;
+
+        let enrollments: Vec<StudentCourse> = self
+            .db
+            .find(
+                Table::StudentCourses,
+                vec![Filter::StudentCourses(StudentCoursesFilter::Semester(term.name.clone()))],
+                None,
+            )?
+            .into_iter()
+            .filter_map(|x| match x {
+                ReceiverType::StudentCourse(e) => Some(e),
+                _ => None,
+            })
+            .collect();
+
+        let ungraded = enrollments.iter().filter(|e| e.grade < 0.0).count();
+        if ungraded > 0 {
+            return Err(conflict(format!(
+                "{} enrollments in {} have no grade yet.",
+                ungraded, term.name
+            )));
+        }
+
+        let students: BTreeSet<i32> = enrollments.iter().map(|e| e.student_id).collect();
+        let terms = self.get_terms()?;
+        let rules = &self.settings.standing;
+        let now = chrono::Utc::now().timestamp();
+        let mut decisions = Vec::new();
+        let mut notifications = Vec::new();
+
+        for &student_id in &students {
+            let taken = self.db.enrollments_with_courses(vec![Filter::StudentCourses(
+                StudentCoursesFilter::StudentId(student_id),
+            )])?;
+            let history = history::build(student_id, taken, &terms, rules);
+            let Some(record) = history.terms.iter().find(|t| t.term == term.name) else {
+                continue;
+            };
+            let (Some(term_gpa), Some(cumulative_gpa)) = (record.totals.gpa, record.cumulative.gpa)
+            else {
+                continue;
+            };
+
+            // What the student was told last for this term, or else for the one before it
+            let previous = self
+                .standing_records(student_id)?
+                .into_iter()
+                .rev()
+                .find(|r| match terms.iter().find(|t| t.name == r.term) {
+                    Some(t) => t.starts_on <= term.starts_on,
+                    None => true,
+                });
+            if previous
+                .as_ref()
+                .is_some_and(|p| p.term == term.name && p.standing == record.standing.as_str())
+            {
+                continue;
+            }
+
+            let was = match &previous {
+                Some(p) => p.standing.parse()?,
+                None => Standing::Good,
+            };
+            let decided = StandingRecord {
+                id: 0,
+                student_id,
+                term: term.name.clone(),
+                standing: record.standing.as_str().to_string(),
+                term_gpa,
+                cumulative_gpa,
+                decided_at: now,
+            };
+
+            // Good standing is only news when it ends or comes back
+            let troubled = |s: Standing| matches!(s, Standing::Probation | Standing::Suspension);
+            if record.standing == Standing::DeansList
+                || (record.standing != was && (troubled(record.standing) || troubled(was)))
+            {
+                notifications.push(standing_notice(&decided, record.standing, rules, now));
+            }
+
+            decisions.push(decided);
+        }
+
+        let rows = decisions
+            .iter()
+            .cloned()
+            .map(ReceiverType::Standing)
+            .chain(notifications.into_iter().map(ReceiverType::Notification))
+            .collect();
+        self.db.transaction(|db| db.insert(rows))?;
+        tracing::info!(
+            term = %term.name,
+            students = students.len(),
+            recorded = decisions.len(),
+            "term closed"
+        );
+
+        Ok(TermClosing {
+            term: term.name,
+            students: students.len(),
+            decisions,
+        })
+    }
+
+    // Maintenance: the database's integrity check, `["ok"]` when healthy
+    pub fn check_database(&self) -> Result<Vec<String>> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can check the database."));
+        }
+
+        self.db.integrity_check()
+    }
+
+    pub fn vacuum_database(&mut self) -> Result<()> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can compact the database."));
+        }
+
+        self.db.vacuum()
+    }
+
+    // Snapshots the live database, then prunes what the retention policy no longer needs
+    pub fn take_snapshot(&mut self) -> Result<Snapshot> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can take snapshots."));
+        }
+        self.snapshots_supported()?;
+
+        let dir = self.settings.backup_dir();
+        let snapshot = backup::take(&self.settings.database.path, &dir)?;
+        tracing::info!(snapshot = %snapshot.name, "snapshot taken");
+
+        self.prune_snapshots()?;
+        Ok(snapshot)
+    }
+
+    pub fn list_snapshots(&self) -> Result<Vec<Snapshot>> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can list snapshots."));
+        }
+
+        backup::list(&self.settings.backup_dir())
+    }
+
+    pub fn verify_snapshot(&self, name: &str) -> Result<Verification> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can verify snapshots."));
+        }
+
+        backup::verify(&self.settings.backup_dir(), name)
+    }
+
+    // Returns the snapshot the current state was saved to before it was replaced
+    pub fn restore_snapshot(&mut self, name: &str) -> Result<Snapshot> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can restore snapshots."));
+        }
+        self.snapshots_supported()?;
+
+        let previous = backup::restore(&self.settings.database.path, &self.settings.backup_dir(), name)?;
+        tracing::warn!(snapshot = %name, previous = %previous.name, "database restored from snapshot");
+
+        Ok(previous)
+    }
+
+    pub fn prune_snapshots(&mut self) -> Result<Vec<Snapshot>> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can prune snapshots."));
+        }
+
+        let removed = backup::prune(&self.settings.backup_dir(), &self.settings.backup)?;
+        if !removed.is_empty() {
+            tracing::info!(removed = removed.len(), "snapshots pruned");
+        }
+
+        Ok(removed)
+    }
+
+    // Fills an empty database with generated departments, teachers, courses, terms,
+    // students and their grades, all in one transaction. Every account shares the plan's
+    // password, hashed once.
+    pub fn seed(&mut self, plan: &SeedPlan) -> Result<SeedReport> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can seed the database."));
+        }
+
+        plan.validate()?;
+
+        let mut errors = ValidationErrors::default();
+        self.settings
+            .registration
+            .check_password(&plan.password, &mut errors);
+        errors.into_result()?;
+
+        let people = [String::from("student"), String::from("teacher")]
+            .into_iter()
+            .map(|role| self.db.count("USERS", &[Filter::Users(UsersFilter::Role(role))]))
+            .sum::<Result<i64>>()?;
+        let rows = people
+            + self.db.count("DEPARTMENTS", &[])?
+            + self.db.count("COURSES", &[])?
+            + self.db.count("TERMS", &[])?;
+        if rows > 0 {
+            return Err(conflict(
+                "The database already has students, teachers, courses or terms; seed an empty one.",
+            ));
+        }
+
+        let domain = self
+            .settings
+            .registration
+            .email_domains
+            .first()
+            .cloned()
+            .unwrap_or_else(|| String::from("example.edu"));
+        let hash = password::hash(&plan.password, password::generate_salt(), &self.settings.auth.hashing);
+        let data = seed::generate(plan, &domain, &hash);
+
+        self.db.transaction(|db| write_seed(db, &data))?;
+
+        let report = SeedReport {
+            departments: data.departments.len(),
+            teachers: data.teachers.len(),
+            courses: data.courses.len(),
+            students: data.students.len(),
+            terms: data.terms.len(),
+            enrollments: data.students.iter().map(|s| s.enrollments.len()).sum(),
+        };
+        tracing::info!(seed = plan.seed, students = report.students, enrollments = report.enrollments, "database seeded");
+
+        Ok(report)
+    }
+
+    // Validates every row of a CSV file and, unless it's a dry run and only if every
+    // row passed, writes them all in a single transaction.
+    pub fn import_csv(
+        &mut self,
+        kind: ImportKind,
+        input: impl Read,
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can import data."));
+        }
+
+        let mut report = ImportReport::new(kind, dry_run);
+
+        let records = match kind {
+            ImportKind::Users => self.prepare_user_import(input, &mut report)?,
+            ImportKind::Courses => self.prepare_course_import(input, &mut report)?,
+            ImportKind::Enrollments => self.prepare_enrollment_import(input, &mut report)?,
+        };
+
+        if dry_run || !report.is_clean() {
+            return Ok(report);
+        }
+
+        let unverified: Vec<String> = records
+            .iter()
+            .filter_map(|r| match r {
+                ReceiverType::User(u) if !u.verified => Some(u.email.clone()),
+                _ => None,
+            })
+            .collect();
+
+        self.db.transaction(|db| db.insert(records))?;
+        report.committed = true;
+        tracing::info!(kind = ?kind, rows = report.total, "import committed");
+
+        // Mail problems shouldn't undo an import that already went through
+        for email in unverified {
+            if let Some(user) = self
+                .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(email))])?
+                .first()
+            {
+                if let Err(e) = self.send_verification(user) {
+                    tracing::warn!(email = %user.email, error = %e, "verification email not sent");
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    // Streams the matching rows into `out` one at a time; returns how many were written
+    pub fn export(&self, request: &ExportRequest, out: impl Write) -> Result<usize> {
+        if !self.is_admin() {
+            return Err(forbidden("Only admins can export data."));
+        }
+
+        let mut sink = export::sink_for(request.format, out);
+        let mut header_written = false;
+
+        let count = self.db.for_each_row(request.sql_table, &request.filters, |columns, values| {
+            let (columns, values) = export::without_excluded(columns, values, request.excluded);
+
+            if !header_written {
+                sink.header(&columns)?;
+                header_written = true;
+            }
+
+            sink.row(&values)
+        })?;
+
+        // An empty result still gets its header row
+        if !header_written {
+            let columns = self.db.columns(request.sql_table)?;
+            let (columns, _) =
+                export::without_excluded(&columns, vec![Value::Null; columns.len()], request.excluded);
+            sink.header(&columns)?;
+        }
+
+        sink.finish()?;
+        tracing::info!(table = %request.name, rows = count, "export finished");
+        Ok(count)
+    }
+
     pub fn generate_statistics(&self) -> Result<Statistics> {
         let registered_users = self.get_users()?.len() as i32;
         let suspended_users = self
@@ -697,9 +2032,12 @@ impl ServerConnection {
             .len() as i32
             - suspended_users;
         let graduated_students = self
-            .get_users_by_filters(vec![Filter::StudentAccount(StudentAccountFilter::CanGrad(
-                true,
-            ))])?
+            .db
+            .find(
+                Table::StudentAccount,
+                vec![Filter::StudentAccount(StudentAccountFilter::CanGrad(true))],
+                None,
+            )?
             .len() as i32;
         let courses = self.db.find(Table::Courses, vec![], None)?.len() as i32;
         let departments = self.db.find(Table::Departments, vec![], None)?.len() as i32;
@@ -718,12 +2056,449 @@ impl ServerConnection {
 
 // Private methods
 impl ServerConnection {
+    // Snapshots copy the SQLite file; a PostgreSQL database is backed up with pg_dump
+    fn snapshots_supported(&self) -> Result<()> {
+        if self.settings.database.url.is_some() {
+            return Err(bad_request(
+                "Snapshots are only available for SQLite databases. Use pg_dump for PostgreSQL.",
+            ));
+        }
+
+        if self.settings.database.in_memory() {
+            return Err(bad_request("In-memory databases have no snapshots."));
+        }
+
+        Ok(())
+    }
+
+    // Every password check goes through here so that it is throttled. Unknown emails and
+    // wrong passwords produce the same error, and take about as long.
+    fn check_password(&mut self, email: String, password: String) -> Result<User> {
+        // Stored the way registration normalised it
+        let account = email.trim().to_lowercase();
+        self.check_lockout(&account)?;
+
+        let binding =
+            self.get_users_by_filters(vec![Filter::Users(UsersFilter::Email(account.clone()))])?;
+
+        let verified = match binding.first() {
+            Some(u) => password::verify(&u.password, &password),
+            None => {
+                password::hash(&password, password::generate_salt(), &self.settings.auth.hashing);
+                false
+            }
+        };
+
+        if !verified {
+            self.record_failed_login(&account)?;
+            return Err(unauthenticated(INVALID_CREDENTIALS));
+        }
+
+        let user = binding.first().unwrap().to_owned();
+
+        // Only reveal this to someone who knows the password
+        if user.suspended {
+            return Err(forbidden("User is suspended."));
+        }
+
+        Ok(user)
+    }
+
+    fn login_attempt_keys(&self, account: &str) -> Vec<(&'static str, String)> {
+        let mut keys = vec![(LOCKOUT_ACCOUNT, account.to_owned())];
+
+        if let Some(ip) = &self.client_ip {
+            keys.push((LOCKOUT_IP, ip.to_owned()));
+        }
+
+        keys
+    }
+
+    fn find_login_attempt(&self, scope: &str, key: &str) -> Result<Option<LoginAttempt>> {
+        let findings = self.db.find(
+            Table::LoginAttempts,
+            vec![
+                Filter::LoginAttempts(LoginAttemptsFilter::Scope(scope.to_owned())),
+                Filter::LoginAttempts(LoginAttemptsFilter::Key(key.to_owned())),
+            ],
+            None,
+        )?;
+
+        match findings.into_iter().next() {
+            Some(ReceiverType::LoginAttempt(a)) => Ok(Some(a)),
+            _ => Ok(None),
+        }
+    }
+
+    fn check_lockout(&self, account: &str) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+
+        for (scope, key) in self.login_attempt_keys(account) {
+            if let Some(attempt) = self.find_login_attempt(scope, &key)? {
+                if attempt.locked_until > now {
+                    return Err(unauthenticated(format!(
+                        "Too many failed sign-in attempts. Try again in {} seconds.",
+                        attempt.locked_until - now
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Past the free attempts, every further failure doubles the lockout, up to the maximum
+    fn record_failed_login(&mut self, account: &str) -> Result<()> {
+        monitoring::login_failed();
+        tracing::warn!(%account, "failed sign-in");
+
+        let now = chrono::Utc::now().timestamp();
+        let lockout = self.settings.auth.lockout.clone();
+
+        for (scope, key) in self.login_attempt_keys(account) {
+            let free_attempts = match scope {
+                LOCKOUT_IP => lockout.ip_attempts,
+                _ => lockout.account_attempts,
+            };
+
+            let existing = self.find_login_attempt(scope, &key)?;
+            let mut attempt = existing.clone().unwrap_or(LoginAttempt {
+                id: 0,
+                scope: scope.to_owned(),
+                key,
+                failures: 0,
+                last_failure: 0,
+                locked_until: 0,
+            });
+
+            if now - attempt.last_failure > lockout.forget_after_seconds {
+                attempt.failures = 0;
+            }
+
+            attempt.failures += 1;
+            attempt.last_failure = now;
+
+            if attempt.failures >= free_attempts {
+                let exponent = (attempt.failures - free_attempts).min(20) as u32;
+                let duration = lockout
+                    .base_seconds
+                    .saturating_mul(1 << exponent)
+                    .min(lockout.max_seconds);
+                attempt.locked_until = now + duration;
+                tracing::warn!(scope, key = %attempt.key, seconds = duration, "sign-in locked");
+            }
+
+            match existing {
+                Some(_) => self.db.update(vec![ReceiverType::LoginAttempt(attempt)])?,
+                None => self.db.insert(vec![ReceiverType::LoginAttempt(attempt)])?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // A successful sign-in forgives the account, but not the address
+    fn clear_failed_logins(&mut self, account: &str) -> Result<()> {
+        if let Some(attempt) = self.find_login_attempt(LOCKOUT_ACCOUNT, account)? {
+            self.db.delete(vec![ReceiverType::LoginAttempt(attempt)])?;
+        }
+
+        Ok(())
+    }
+
+    fn find_two_factor(&self, user_id: i32) -> Result<Option<TwoFactor>> {
+        let findings = self.db.find(
+            Table::TwoFactor,
+            vec![Filter::TwoFactor(TwoFactorFilter::UserId(user_id))],
+            None,
+        )?;
+
+        match findings.into_iter().next() {
+            Some(ReceiverType::TwoFactor(t)) => Ok(Some(t)),
+            _ => Ok(None),
+        }
+    }
+
+    fn two_factor_required_for(&self, role: &str) -> Result<bool> {
+        Ok(self
+            .get_two_factor_policy()?
+            .contains(&role.to_lowercase()))
+    }
+
+    fn check_second_factor(&mut self, mut two_factor: TwoFactor, code: &str) -> Result<()> {
+        // A code is only accepted once, even within its 30 second window
+        if let Some(step) = totp::verify(&two_factor.secret, code, two_factor.last_step)? {
+            two_factor.last_step = step;
+            self.db.update(vec![ReceiverType::TwoFactor(two_factor)])?;
+            return Ok(());
+        }
+
+        let codes = self.db.find(
+            Table::RecoveryCodes,
+            vec![
+                Filter::RecoveryCodes(RecoveryCodesFilter::UserId(two_factor.user_id)),
+                Filter::RecoveryCodes(RecoveryCodesFilter::Used(false)),
+            ],
+            None,
+        )?;
+
+        for c in codes {
+            if let ReceiverType::RecoveryCode(mut recovery) = c {
+                if password::verify(&recovery.code_hash, code.trim()) {
+                    recovery.used = true;
+                    self.db.update(vec![ReceiverType::RecoveryCode(recovery)])?;
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(unauthenticated("Invalid two-factor authentication code."))
+    }
+
+    fn replace_recovery_codes(&mut self, user_id: i32) -> Result<Vec<String>> {
+        let old = self.db.find(
+            Table::RecoveryCodes,
+            vec![Filter::RecoveryCodes(RecoveryCodesFilter::UserId(user_id))],
+            None,
+        )?;
+        self.db.delete(old)?;
+
+        let codes = totp::generate_recovery_codes(10);
+        let hashed = codes
+            .iter()
+            .map(|c| {
+                ReceiverType::RecoveryCode(RecoveryCode {
+                    id: 0,
+                    user_id,
+                    code_hash: password::hash(c, password::generate_salt(), &self.settings.auth.hashing),
+                    used: false,
+                })
+            })
+            .collect();
+        self.db.insert(hashed)?;
+
+        Ok(codes)
+    }
+
+    fn revoke_sessions(&mut self, user_id: i32) -> Result<()> {
+        let sessions = self.db.find(
+            Table::Sessions,
+            vec![Filter::Sessions(SessionsFilter::UserId(user_id))],
+            None,
+        )?;
+
+        self.db.delete(sessions)
+    }
+
+    fn send_verification(&self, user: &User) -> Result<()> {
+        let expires_at = chrono::Utc::now().timestamp()
+            + self.settings.auth.verification_ttl_hours * 60 * 60;
+        let token = tokens::sign(
+            &self.settings.auth.token_secret,
+            tokens::VERIFY_EMAIL,
+            &format!("{}:{}", user.id, user.email),
+            expires_at,
+        );
+
+        let email = Email {
+            to: user.email.clone(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Hello {},\n\nPlease confirm your email address by opening the link below:\n\n{}/api/v1/verify?token={}\n\nThe link expires in {} hours.",
+                user.username,
+                self.settings.mail.public_url.trim_end_matches('/'),
+                token,
+                self.settings.auth.verification_ttl_hours
+            ),
+        };
+
+        mailer::from_settings(&self.settings)?.send(&email)
+    }
+
+    // The update has gone through either way; `/verify/resend` sends another link
+    fn confirm_new_email(&self, user: &User) {
+        if let Err(e) = self.send_verification(user) {
+            tracing::warn!(email = %user.email, error = %e, "verification email not sent");
+        }
+    }
+
+    // Admins, the student themselves and their advisor; `what` names the record in the refusal
+    fn check_student_access(&self, student_id: i32, what: &str) -> Result<()> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| unauthenticated("Must be signed in."))?;
+
+        let account = self
+            .db
+            .find(
+                Table::StudentAccount,
+                vec![Filter::StudentAccount(StudentAccountFilter::StudentId(student_id))],
+                None,
+            )?
+            .into_iter()
+            .find_map(|x| match x {
+                ReceiverType::StudentAccount(a) => Some(a),
+                _ => None,
+            });
+
+        let allowed = match session.role.to_lowercase().as_str() {
+            "admin" => true,
+            "teacher" => account.as_ref().is_some_and(|a| a.advisor_id == session.id),
+            _ => session.id == student_id,
+        };
+        if !allowed {
+            return Err(forbidden(format!(
+                "You can only see your own {} or your advisees'.",
+                what
+            )));
+        }
+        if account.is_none() {
+            return Err(not_found("Student not found."));
+        }
+
+        Ok(())
+    }
+
+    fn find_holds(&self, filters: Vec<Filter>) -> Result<Vec<Hold>> {
+        let findings = self.db.find(Table::Holds, filters, None)?;
+
+        Ok(findings
+            .into_iter()
+            .filter_map(|x| match x {
+                ReceiverType::Hold(h) => Some(h),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn active_holds(&self, student_id: i32) -> Result<Vec<Hold>> {
+        self.find_holds(vec![
+            Filter::Holds(HoldsFilter::StudentId(student_id)),
+            Filter::Holds(HoldsFilter::Active),
+        ])
+    }
+
+    // Oldest first
+    fn standing_records(&self, student_id: i32) -> Result<Vec<StandingRecord>> {
+        let mut records: Vec<StandingRecord> = self
+            .db
+            .find(
+                Table::StandingHistory,
+                vec![Filter::StandingHistory(StandingHistoryFilter::StudentId(student_id))],
+                None,
+            )?
+            .into_iter()
+            .filter_map(|x| match x {
+                ReceiverType::Standing(s) => Some(s),
+                _ => None,
+            })
+            .collect();
+
+        // In term order, so the last record is the latest term's latest decision. Terms that
+        // have since been removed can't be placed and go first.
+        let starts: HashMap<String, String> = self
+            .get_terms()?
+            .into_iter()
+            .map(|t| (t.name, t.starts_on))
+            .collect();
+        records.sort_by_key(|r| (starts.get(&r.term).cloned(), r.decided_at, r.id));
+
+        Ok(records)
+    }
+
+    // A suspended student sits out the term after the one that suspended them. Back from
+    // it, or on probation, they can only carry so many credits in a term.
+    fn check_standing(&self, student_id: i32, courses: &[Courses], semester: &str) -> Result<()> {
+        let Some(latest) = self.standing_records(student_id)?.pop() else {
+            return Ok(());
+        };
+
+        match latest.standing.parse()? {
+            Standing::Suspension if self.serving_suspension(&latest.term)? => Err(forbidden(
+                format!(
+                    "You were suspended after {} and cannot enroll until the following term is over.",
+                    latest.term
+                ),
+            )),
+            Standing::Probation | Standing::Suspension => {
+                let cap = self.settings.standing.probation_credit_cap;
+                let carried: i32 = self
+                    .db
+                    .enrollments_with_courses(vec![
+                        Filter::StudentCourses(StudentCoursesFilter::StudentId(student_id)),
+                        Filter::StudentCourses(StudentCoursesFilter::Semester(semester.to_string())),
+                    ])?
+                    .iter()
+                    .map(|e| e.course.cr_cost)
+                    .sum();
+                let adding: i32 = courses.iter().map(|c| c.cr_cost).sum();
+
+                if carried + adding > cap {
+                    return Err(conflict(format!(
+                        "Students on probation can take at most {} credits a term; you already have {} in {}.",
+                        cap, carried, semester
+                    )));
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Whether the term after `suspended_after` is still running or yet to come. A term that
+    // has since been removed can't be placed, so its suspension counts as served.
+    fn serving_suspension(&self, suspended_after: &str) -> Result<bool> {
+        let terms = self.get_terms()?;
+        let Some(suspended) = terms.iter().find(|t| t.name == suspended_after) else {
+            return Ok(false);
+        };
+
+        let today = chrono::Local::now().date_naive().to_string();
+        let following = terms
+            .iter()
+            .filter(|t| t.starts_on > suspended.ends_on)
+            .min_by(|a, b| a.starts_on.cmp(&b.starts_on));
+
+        Ok(match following {
+            Some(t) => t.ends_on >= today,
+            None => true,
+        })
+    }
+
+    fn find_terms(&self, filters: Vec<Filter>) -> Result<Vec<Term>> {
+        let findings = self.db.find(Table::Terms, filters, None)?;
+
+        let terms = findings
+            .into_iter()
+            .filter_map(|x| {
+                if let ReceiverType::Term(t) = x {
+                    Some(t)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(terms)
+    }
+
     fn transmute_course_to_student_course(&self, course: Courses) -> StudentCourse {
         StudentCourse {
             student_id: self.session.as_ref().unwrap().id,
             course_id: course.id,
             grade: -1.0,
-            semester: match chrono::Local::now().month() {
+            semester: self.current_semester(),
+        }
+    }
+
+    // What new enrollments are recorded under
+    fn current_semester(&self) -> String {
+        // Without any terms set up, fall back to the season
+        match self.current_term().ok().flatten() {
+            Some(term) => term.name,
+            _ => match chrono::Local::now().month() {
                 6..=12 => "Fall".to_string(),
                 _ => "Spring".to_string(),
             },
@@ -732,48 +2507,548 @@ impl ServerConnection {
 
     fn update_user_as_student(&mut self, mut user: User) -> Result<()> {
         let binding =
-            self.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(user.id.clone()))])?;
-        let u = binding.get(0).ok_or_else(|| anyhow!("User not found."))?;
+            self.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(user.id))])?;
+        let u = binding.first().ok_or_else(|| not_found("User not found."))?;
 
         // Check permissions
         if user.suspended != u.suspended {
-            return Err(anyhow!("Suspended cannot be changed."));
+            return Err(forbidden("Suspended cannot be changed."));
         }
 
         if user.verified != u.verified {
-            return Err(anyhow!("Verified cannot be changed."));
+            return Err(forbidden("Verified cannot be changed."));
         }
 
         if user.role != u.role {
-            return Err(anyhow!("Role cannot be changed."));
+            return Err(forbidden("Role cannot be changed."));
         }
         
+        self.check_changed_fields(&mut user, u)?;
+        let moved = user.email != u.email;
+
         if user.password.is_empty() || user.password.starts_with("$argon2id") {
             user.password = u.password.to_owned();
         } else {
             let salt = password::generate_salt();
-            user.password = password::hash(&user.password, salt);
+            user.password = password::hash(&user.password, salt, &self.settings.auth.hashing);
         }
 
-        self.db.update(vec![ReceiverType::User(user)])?;
+        self.db.update(vec![ReceiverType::User(user.clone())])?;
+        if moved {
+            self.confirm_new_email(&user);
+        }
 
         Ok(())
     }
 
     fn update_user_as_admin(&mut self, mut user: User) -> Result<()> {
         let binding =
-            self.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(user.id.clone()))])?;
-        let u = binding.get(0).ok_or_else(|| anyhow!("User not found."))?;
+            self.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(user.id))])?;
+        let u = binding.first().ok_or_else(|| not_found("User not found."))?;
+
+        self.check_changed_fields(&mut user, u)?;
+        let moved = user.email != u.email;
 
         if user.password.is_empty() || user.password.starts_with("$argon2id") {
             user.password = u.password.to_owned();
         } else {
             let salt = password::generate_salt();
-            user.password = password::hash(&user.password, salt);
+            user.password = password::hash(&user.password, salt, &self.settings.auth.hashing);
         }
 
-        self.db.update(vec![ReceiverType::User(user)])?;
+        // Role and suspension changes matter most when auditing an account
+        tracing::info!(
+            user_id = user.id,
+            role = %user.role,
+            suspended = user.suspended,
+            "user updated by an admin"
+        );
+        self.db.update(vec![ReceiverType::User(user.clone())])?;
+        if moved {
+            self.confirm_new_email(&user);
+        }
+
+        Ok(())
+    }
+
+    fn prepare_user_import(
+        &self,
+        input: impl Read,
+        report: &mut ImportReport,
+    ) -> Result<Vec<ReceiverType>> {
+        let mut records = Vec::new();
+        let mut seen = HashSet::new();
+
+        for (line, row) in import::read_rows::<UserRow>(input)? {
+            let row = match row {
+                Ok(r) => r,
+                Err(errors) => {
+                    report.record(line, errors);
+                    continue;
+                }
+            };
+
+            let mut errors = ValidationErrors::default();
+            let role = row
+                .role
+                .filter(|r| !r.is_empty())
+                .unwrap_or_else(|| String::from("student"));
+
+            if !["admin", "teacher", "student"].contains(&role.as_str()) {
+                errors.add("role", "invalid", "Role must be admin, teacher or student.");
+            }
+
+            // Accounts without a password must set one through a reset before signing in
+            let generated = row.password.is_empty();
+            let mut user = User {
+                id: 0,
+                username: row.username,
+                password: if generated {
+                    tokens::generate_opaque()
+                } else {
+                    row.password
+                },
+                email: row.email,
+                phone: row.phone,
+                verified: row.verified.unwrap_or(false),
+                suspended: false,
+                forcenewpw: generated,
+                role,
+            };
+
+            self.check_new_user(&mut user, &mut errors)?;
+
+            if !generated {
+                self.settings
+                    .registration
+                    .check_password(&user.password, &mut errors);
+            }
+
+            if !seen.insert(user.email.clone()) {
+                errors.add("email", "duplicate", "This email appears earlier in the file.");
+            }
+
+            if errors.is_empty() {
+                let salt = password::generate_salt();
+                user.password = password::hash(&user.password, salt, &self.settings.auth.hashing);
+                records.push(ReceiverType::User(user));
+            }
+
+            report.record(line, errors);
+        }
+
+        Ok(records)
+    }
+
+    fn prepare_course_import(
+        &self,
+        input: impl Read,
+        report: &mut ImportReport,
+    ) -> Result<Vec<ReceiverType>> {
+        let mut records = Vec::new();
+        let mut seen = HashSet::new();
+
+        for (line, row) in import::read_rows::<CourseRow>(input)? {
+            let row = match row {
+                Ok(r) => r,
+                Err(errors) => {
+                    report.record(line, errors);
+                    continue;
+                }
+            };
+
+            let mut errors = ValidationErrors::default();
+
+            if row.course.is_empty() {
+                errors.add("course", "required", "Course name cannot be empty.");
+            }
+
+            if row.course_nr.is_empty() {
+                errors.add("course_nr", "required", "Course number cannot be empty.");
+            } else if !seen.insert(row.course_nr.clone()) {
+                errors.add("course_nr", "duplicate", "This course number appears earlier in the file.");
+            } else if !self
+                .db
+                .find(
+                    Table::Courses,
+                    vec![Filter::Courses(CoursesFilter::CourseNr(row.course_nr.clone()))],
+                    None,
+                )?
+                .is_empty()
+            {
+                errors.add("course_nr", "taken", "A course with this number already exists.");
+            }
+
+            if row.cr_cost <= 0 {
+                errors.add("cr_cost", "invalid", "Credits must be a positive number.");
+            }
+
+            if row.timeslots.is_empty() {
+                errors.add("timeslots", "required", "Timeslots cannot be empty.");
+            }
+
+            if row.capacity < 0 {
+                errors.add("capacity", "invalid", "Capacity cannot be negative.");
+            }
+
+            let teacher = self
+                .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(
+                    row.teacher_email.to_lowercase(),
+                ))])?
+                .into_iter()
+                .next();
+
+            let teacher_id = match teacher {
+                Some(t) if t.role == "teacher" => t.id,
+                Some(_) => {
+                    errors.add("teacher_email", "not_a_teacher", "This user is not a teacher.");
+                    0
+                }
+                None => {
+                    errors.add("teacher_email", "not_found", "No user with this email.");
+                    0
+                }
+            };
+
+            if errors.is_empty() {
+                records.push(ReceiverType::Course(Courses {
+                    id: 0,
+                    teacher_id,
+                    course: row.course,
+                    course_nr: row.course_nr,
+                    description: row.description,
+                    cr_cost: row.cr_cost,
+                    timeslots: row.timeslots,
+                    capacity: row.capacity,
+                }));
+            }
+
+            report.record(line, errors);
+        }
+
+        Ok(records)
+    }
+
+    fn prepare_enrollment_import(
+        &self,
+        input: impl Read,
+        report: &mut ImportReport,
+    ) -> Result<Vec<ReceiverType>> {
+        let mut records = Vec::new();
+        let mut seen = HashSet::new();
+
+        let default_semester = match self.current_term()? {
+            Some(term) => term.name,
+            None => match chrono::Local::now().month() {
+                6..=12 => "Fall".to_string(),
+                _ => "Spring".to_string(),
+            },
+        };
+
+        for (line, row) in import::read_rows::<EnrollmentRow>(input)? {
+            let row = match row {
+                Ok(r) => r,
+                Err(errors) => {
+                    report.record(line, errors);
+                    continue;
+                }
+            };
+
+            let mut errors = ValidationErrors::default();
+
+            let student = self
+                .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(
+                    row.student_email.to_lowercase(),
+                ))])?
+                .into_iter()
+                .next();
+
+            let student_id = match student {
+                Some(s) if s.role == "student" => s.id,
+                Some(_) => {
+                    errors.add("student_email", "not_a_student", "This user is not a student.");
+                    0
+                }
+                None => {
+                    errors.add("student_email", "not_found", "No user with this email.");
+                    0
+                }
+            };
+
+            let courses = self.db.find(
+                Table::Courses,
+                vec![Filter::Courses(CoursesFilter::CourseNr(row.course_nr.clone()))],
+                None,
+            )?;
+
+            let course_id = match courses.as_slice() {
+                [ReceiverType::Course(c)] => c.id,
+                [] => {
+                    errors.add("course_nr", "not_found", "No course with this number.");
+                    0
+                }
+                _ => {
+                    errors.add("course_nr", "ambiguous", "Several courses share this number.");
+                    0
+                }
+            };
+
+            let grade = row.grade.unwrap_or(-1.0);
+            if grade != -1.0 && !(0.0..=4.0).contains(&grade) {
+                errors.add("grade", "out_of_range", "Grade must be between 0.0 and 4.0, or empty while in progress.");
+            }
+
+            let semester = row
+                .semester
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| default_semester.clone());
+
+            if errors.is_empty() {
+                let existing = self.db.find(
+                    Table::StudentCourses,
+                    vec![
+                        Filter::StudentCourses(StudentCoursesFilter::StudentId(student_id)),
+                        Filter::StudentCourses(StudentCoursesFilter::CourseId(course_id)),
+                        Filter::StudentCourses(StudentCoursesFilter::Semester(semester.clone())),
+                    ],
+                    None,
+                )?;
+
+                if !existing.is_empty() || !seen.insert((student_id, course_id, semester.clone())) {
+                    errors.add("course_nr", "duplicate", "The student is already enrolled in this course for the term.");
+                }
+            }
+
+            if errors.is_empty() {
+                records.push(ReceiverType::StudentCourse(StudentCourse {
+                    student_id,
+                    course_id,
+                    grade,
+                    semester,
+                }));
+            }
+
+            report.record(line, errors);
+        }
+
+        Ok(records)
+    }
+
+    // The registration rules apart from the password, shared by sign-up and bulk imports.
+    // Normalises the email and phone number in place.
+    fn check_new_user(&self, user: &mut User, errors: &mut ValidationErrors) -> Result<()> {
+        let policy = &self.settings.registration;
+
+        if user.username.is_empty() {
+            errors.add("username", "required", "Account name cannot be empty.");
+        }
+
+        user.email = policy.check_email(&user.email, errors);
+        user.phone = policy.check_phone(&user.phone, errors);
+
+        if !errors.errors.iter().any(|e| e.field == "email")
+            && !self
+                .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(user.email.clone()))])?
+                .is_empty()
+        {
+            errors.add("email", "taken", "A user with this email already exists.");
+        }
 
         Ok(())
     }
+
+    // Edits go through the same policy as registration, but only for the fields that changed
+    fn check_changed_fields(&self, user: &mut User, current: &User) -> Result<()> {
+        let policy = &self.settings.registration;
+        let mut errors = ValidationErrors::default();
+
+        if user.email != current.email {
+            user.email = policy.check_email(&user.email, &mut errors);
+        }
+
+        if user.email != current.email {
+            // Nobody has confirmed the new address yet
+            user.verified = false;
+
+            if !self
+                .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(user.email.clone()))])?
+                .is_empty()
+            {
+                errors.add("email", "taken", "A user with this email already exists.");
+            }
+        }
+
+        if user.phone != current.phone {
+            user.phone = policy.check_phone(&user.phone, &mut errors);
+        }
+
+        if !user.password.is_empty() && !user.password.starts_with("$argon2id") {
+            policy.check_password(&user.password, &mut errors);
+        }
+
+        errors.into_result()
+    }
+}
+
+// Writes generated rows in dependency order. Ids come from the database, so each kind of
+// row is read back and matched up before the rows that refer to it are written; the
+// tables start out empty, except for users, which are matched by email.
+fn write_seed(db: &mut DbDriver, data: &Dataset) -> Result<()> {
+    db.insert(data.departments.iter().cloned().map(ReceiverType::Department).collect())?;
+    db.insert(data.terms.iter().cloned().map(ReceiverType::Term).collect())?;
+    let departments: Vec<i32> = db
+        .find(Table::Departments, vec![], None)?
+        .into_iter()
+        .filter_map(|r| match r {
+            ReceiverType::Department(d) => Some(d.id),
+            _ => None,
+        })
+        .collect();
+
+    let people = data
+        .teachers
+        .iter()
+        .map(|t| &t.user)
+        .chain(data.students.iter().map(|s| &s.user));
+    db.insert(people.map(|u| ReceiverType::User(u.clone())).collect())?;
+
+    let users: HashMap<String, i32> = db
+        .find(Table::Users, vec![], None)?
+        .into_iter()
+        .filter_map(|r| match r {
+            ReceiverType::User(u) => Some((u.email, u.id)),
+            _ => None,
+        })
+        .collect();
+    let teachers: Vec<i32> = data.teachers.iter().map(|t| users[&t.user.email]).collect();
+    let departments_by_teacher: HashMap<i32, i32> = data
+        .teachers
+        .iter()
+        .zip(&teachers)
+        .map(|(t, id)| (*id, departments[t.department]))
+        .collect();
+
+    // The triggers gave every teacher and student an empty account
+    let teacher_accounts = db
+        .find(Table::TeacherAccount, vec![], None)?
+        .into_iter()
+        .filter_map(|r| match r {
+            ReceiverType::TeacherAccount(account) => Some(ReceiverType::TeacherAccount(TeacherAccount {
+                dept_id: departments_by_teacher[&account.teacher_id],
+                ..account
+            })),
+            _ => None,
+        })
+        .collect();
+    db.update(teacher_accounts)?;
+
+    let courses = data.courses.iter().map(|c| {
+        ReceiverType::Course(Courses {
+            teacher_id: teachers[c.teacher],
+            ..c.course.clone()
+        })
+    });
+    db.insert(courses.collect())?;
+    let courses: HashMap<String, i32> = db
+        .find(Table::Courses, vec![], None)?
+        .into_iter()
+        .filter_map(|r| match r {
+            ReceiverType::Course(c) => Some((c.course_nr, c.id)),
+            _ => None,
+        })
+        .collect();
+
+    let accounts: HashMap<i32, i32> = db
+        .find(Table::StudentAccount, vec![], None)?
+        .into_iter()
+        .filter_map(|r| match r {
+            ReceiverType::StudentAccount(a) => Some((a.student_id, a.id)),
+            _ => None,
+        })
+        .collect();
+
+    for student in &data.students {
+        let id = users[&student.user.email];
+
+        db.update(vec![ReceiverType::StudentAccount(StudentAccount {
+            id: accounts[&id],
+            student_id: id,
+            advisor_id: teachers[student.advisor],
+            ..student.account.clone()
+        })])?;
+
+        // The cgpa triggers take it from here
+        db.insert(
+            student
+                .enrollments
+                .iter()
+                .map(|e| {
+                    ReceiverType::StudentCourse(StudentCourse {
+                        student_id: id,
+                        course_id: courses[&data.courses[e.course].course.course_nr],
+                        grade: e.grade,
+                        semester: data.terms[e.term].name.clone(),
+                    })
+                })
+                .collect(),
+        )?;
+    }
+
+    Ok(())
+}
+
+// What a hold stops the student doing, to finish "you cannot ..."
+fn blocked_by(hold: &Hold) -> &'static str {
+    match (hold.blocks_registration, hold.blocks_transcripts) {
+        (true, true) => "register for courses or get your transcript",
+        (false, true) => "get your transcript",
+        _ => "register for courses",
+    }
+}
+
+// What a student is told about a standing decided at term close
+fn standing_notice(
+    decided: &StandingRecord,
+    standing: Standing,
+    rules: &StandingRules,
+    now: i64,
+) -> Notification {
+    let (subject, body) = match standing {
+        Standing::DeansList => (
+            format!("Dean's list for {}", decided.term),
+            format!(
+                "Congratulations! Your GPA of {:.2} for {} puts you on the dean's list.",
+                decided.term_gpa, decided.term
+            ),
+        ),
+        Standing::Probation => (
+            String::from("Academic probation"),
+            format!(
+                "Your cumulative GPA after {} is {:.2}, below the {:.2} needed for good standing. While on probation you can take at most {} credits a term.",
+                decided.term, decided.cumulative_gpa, rules.probation_below, rules.probation_credit_cap
+            ),
+        ),
+        Standing::Suspension => (
+            String::from("Academic suspension"),
+            format!(
+                "Your GPA for {} was {:.2} while on probation, and your cumulative GPA is {:.2}. You cannot enroll in the following term; after it you return on probation.",
+                decided.term, decided.term_gpa, decided.cumulative_gpa
+            ),
+        ),
+        Standing::Good | Standing::InProgress => (
+            String::from("Back in good standing"),
+            format!(
+                "With a cumulative GPA of {:.2} after {}, you are in good academic standing again.",
+                decided.cumulative_gpa, decided.term
+            ),
+        ),
+    };
+
+    Notification {
+        id: 0,
+        user_id: decided.student_id,
+        subject,
+        body,
+        created_at: now,
+    }
 }
@@ -1,18 +1,78 @@
 use anyhow::{Ok, Result};
+use rusqlite::types::ValueRef;
 use rusqlite::Connection;
+use serde_json::Value;
+use std::path::Path;
+
+use super::filter::Filter;
+use super::storage::{RowVisitor, Storage, RECOMPUTE_STANDING, SCHEMA_VERSION};
 
 pub struct DatabaseConnection {
     pub connection: Connection,
 }
 
 impl DatabaseConnection {
-    pub fn new() -> Result<Self> {
-        let connection = Connection::open("system.db")?;
+    pub fn new(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)?;
+
+        Ok(Self { connection })
+    }
+
+    // A private database that goes away with the connection
+    pub fn in_memory() -> Result<Self> {
+        let connection = Connection::open_in_memory()?;
 
         Ok(Self { connection })
     }
+}
+
+impl Storage for DatabaseConnection {
+    fn execute(&mut self, sql: &str) -> Result<()> {
+        self.connection.execute(sql, [])?;
+
+        Ok(())
+    }
+
+    fn query(&self, sql: &str, f: &mut RowVisitor) -> Result<usize> {
+        let mut stmt = self.connection.prepare(sql)?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let mut rows = stmt.query([])?;
+        let mut count = 0;
+
+        while let Some(row) = rows.next()? {
+            let values = (0..columns.len())
+                .map(|i| match row.get_ref(i).unwrap_or(ValueRef::Null) {
+                    ValueRef::Null => Value::Null,
+                    ValueRef::Integer(n) => Value::from(n),
+                    ValueRef::Real(r) => Value::from(r),
+                    ValueRef::Text(t) | ValueRef::Blob(t) => {
+                        Value::from(String::from_utf8_lossy(t).to_string())
+                    }
+                })
+                .collect();
+
+            f(&columns, values)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn columns(&self, table: &str) -> Result<Vec<String>> {
+        let stmt = self
+            .connection
+            .prepare(&format!(r#"SELECT * FROM "{}" LIMIT 0"#, table))?;
+
+        Ok(stmt.column_names().iter().map(|c| c.to_string()).collect())
+    }
+
+    fn create_tables(&mut self) -> Result<()> {
+        // Checked first, so opening an up-to-date database doesn't take the write lock or
+        // touch the schema other connections have loaded
+        if self.schema_version()? >= SCHEMA_VERSION {
+            return Ok(());
+        }
 
-    pub fn create_tables(&mut self) -> Result<&mut Self> {
         self.connection.execute_batch(
                 r#"
             BEGIN;
@@ -59,6 +119,7 @@ impl DatabaseConnection {
                 "description" TEXT,
                 "cr_cost" INTEGER NOT NULL,
                 "timeslots" TEXT NOT NULL,
+                "capacity" INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY ("teacher_id") REFERENCES "USERS"("id"),
                 PRIMARY KEY("id" AUTOINCREMENT)
             );
@@ -78,6 +139,107 @@ impl DatabaseConnection {
                 PRIMARY KEY("id" AUTOINCREMENT)
             );
 
+            CREATE TABLE IF NOT EXISTS "SESSIONS" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "user_id" INTEGER NOT NULL,
+                "token_hash" TEXT NOT NULL UNIQUE,
+                "created_at" INTEGER NOT NULL,
+                "expires_at" INTEGER NOT NULL,
+                FOREIGN KEY ("user_id") REFERENCES "USERS"("id"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "PASSWORD_RESETS" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "user_id" INTEGER NOT NULL,
+                "token_hash" TEXT NOT NULL UNIQUE,
+                "expires_at" INTEGER NOT NULL,
+                "used" BOOLEAN NOT NULL,
+                FOREIGN KEY ("user_id") REFERENCES "USERS"("id"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "TWO_FACTOR" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "user_id" INTEGER NOT NULL UNIQUE,
+                "secret" TEXT NOT NULL,
+                "enabled" BOOLEAN NOT NULL,
+                "last_step" INTEGER NOT NULL,
+                FOREIGN KEY ("user_id") REFERENCES "USERS"("id"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "RECOVERY_CODES" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "user_id" INTEGER NOT NULL,
+                "code_hash" TEXT NOT NULL,
+                "used" BOOLEAN NOT NULL,
+                FOREIGN KEY ("user_id") REFERENCES "USERS"("id"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "SYSTEM_SETTINGS" (
+                "key" TEXT NOT NULL UNIQUE,
+                "value" TEXT NOT NULL,
+                PRIMARY KEY("key")
+            );
+
+            CREATE TABLE IF NOT EXISTS "LOGIN_ATTEMPTS" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "scope" TEXT NOT NULL,
+                "key" TEXT NOT NULL,
+                "failures" INTEGER NOT NULL,
+                "last_failure" INTEGER NOT NULL,
+                "locked_until" INTEGER NOT NULL,
+                UNIQUE ("scope", "key"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "TERMS" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "name" TEXT NOT NULL UNIQUE,
+                "starts_on" TEXT NOT NULL,
+                "ends_on" TEXT NOT NULL,
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "STANDING_HISTORY" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "student_id" INTEGER NOT NULL,
+                "term" TEXT NOT NULL,
+                "standing" TEXT NOT NULL,
+                "term_gpa" REAL NOT NULL,
+                "cumulative_gpa" REAL NOT NULL,
+                "decided_at" INTEGER NOT NULL,
+                FOREIGN KEY ("student_id") REFERENCES "USERS"("id"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "NOTIFICATIONS" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "user_id" INTEGER NOT NULL,
+                "subject" TEXT NOT NULL,
+                "body" TEXT NOT NULL,
+                "created_at" INTEGER NOT NULL,
+                FOREIGN KEY ("user_id") REFERENCES "USERS"("id"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
+            CREATE TABLE IF NOT EXISTS "HOLDS" (
+                "id" INTEGER NOT NULL UNIQUE,
+                "student_id" INTEGER NOT NULL,
+                "kind" TEXT NOT NULL,
+                "reason" TEXT NOT NULL,
+                "placed_by" INTEGER NOT NULL,
+                "placed_at" INTEGER NOT NULL,
+                "blocks_registration" BOOLEAN NOT NULL,
+                "blocks_transcripts" BOOLEAN NOT NULL,
+                "released_by" INTEGER NOT NULL,
+                "released_at" INTEGER NOT NULL,
+                FOREIGN KEY ("student_id") REFERENCES "USERS"("id"),
+                PRIMARY KEY("id" AUTOINCREMENT)
+            );
+
             CREATE TRIGGER IF NOT EXISTS "manage_student_account_insert"
             AFTER INSERT ON "USERS"
             FOR EACH ROW
@@ -89,10 +251,15 @@ impl DatabaseConnection {
                 DELETE FROM TEACHER_ACCOUNT WHERE "teacher_id" = NEW."id";
             END;
 
+            -- These used to fire on every update and reset the account, so they are
+            -- recreated for databases that still have the old ones
+            DROP TRIGGER IF EXISTS "manage_student_account_update";
+            DROP TRIGGER IF EXISTS "manage_teacher_account_update";
+
             CREATE TRIGGER IF NOT EXISTS "manage_student_account_update"
             AFTER UPDATE ON "USERS"
             FOR EACH ROW
-            WHEN NEW."role" = 'student'
+            WHEN NEW."role" = 'student' AND OLD."role" IS NOT NEW."role"
             BEGIN
                 INSERT OR REPLACE INTO "STUDENT_ACCOUNT" ("student_id", "advisor_id", "discipline", 
                 "enrollment", "can_grad", "cgpa", "cur_credit", "cum_credit")
@@ -113,7 +280,7 @@ impl DatabaseConnection {
             CREATE TRIGGER IF NOT EXISTS "manage_teacher_account_update"
             AFTER UPDATE ON "USERS"
             FOR EACH ROW
-            WHEN NEW."role" = 'teacher'
+            WHEN NEW."role" = 'teacher' AND OLD."role" IS NOT NEW."role"
             BEGIN
                 INSERT OR REPLACE INTO "TEACHER_ACCOUNT" ("teacher_id", "dept_id")
                 VALUES (NEW."id", 0);
@@ -128,6 +295,35 @@ impl DatabaseConnection {
                 DELETE FROM TEACHER_ACCOUNT WHERE "teacher_id" = OLD."id";
             END;
 
+            -- This used to leave two-factor secrets and recovery codes behind, so it is
+            -- recreated for databases that still have the old one
+            DROP TRIGGER IF EXISTS "clear_credentials_on_delete";
+
+            CREATE TRIGGER IF NOT EXISTS "clear_credentials_on_delete"
+            AFTER DELETE ON "USERS"
+            FOR EACH ROW
+            BEGIN
+                DELETE FROM SESSIONS WHERE "user_id" = OLD."id";
+                DELETE FROM PASSWORD_RESETS WHERE "user_id" = OLD."id";
+                DELETE FROM TWO_FACTOR WHERE "user_id" = OLD."id";
+                DELETE FROM RECOVERY_CODES WHERE "user_id" = OLD."id";
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS "clear_standing_on_delete"
+            AFTER DELETE ON "USERS"
+            FOR EACH ROW
+            BEGIN
+                DELETE FROM STANDING_HISTORY WHERE "student_id" = OLD."id";
+                DELETE FROM NOTIFICATIONS WHERE "user_id" = OLD."id";
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS "clear_holds_on_delete"
+            AFTER DELETE ON "USERS"
+            FOR EACH ROW
+            BEGIN
+                DELETE FROM HOLDS WHERE "student_id" = OLD."id";
+            END;
+
             CREATE TRIGGER IF NOT EXISTS "handle_admin_role"
             AFTER INSERT ON USERS
             FOR EACH ROW
@@ -137,6 +333,34 @@ impl DatabaseConnection {
                 DELETE FROM TEACHER_ACCOUNT WHERE "teacher_id" = NEW."id";
             END;
 
+            CREATE INDEX IF NOT EXISTS "student_courses_by_student"
+            ON "STUDENT_COURSES" ("student_id");
+
+            -- Before version 3 asking for a course again took another seat; the first
+            -- enrollment is the one kept
+            DELETE FROM "STUDENT_COURSES" WHERE rowid NOT IN (
+                SELECT MIN(rowid) FROM "STUDENT_COURSES"
+                GROUP BY "student_id", "course_id", "semester"
+            );
+
+            CREATE UNIQUE INDEX IF NOT EXISTS "student_courses_once"
+            ON "STUDENT_COURSES" ("student_id", "course_id", "semester");
+
+            CREATE INDEX IF NOT EXISTS "standing_history_by_student"
+            ON "STANDING_HISTORY" ("student_id");
+
+            CREATE INDEX IF NOT EXISTS "notifications_by_user"
+            ON "NOTIFICATIONS" ("user_id");
+
+            CREATE INDEX IF NOT EXISTS "holds_by_student"
+            ON "HOLDS" ("student_id");
+
+            -- Before version 2 these updated the account whose own id matched the student's
+            -- and counted no credits towards graduation, so they are recreated on upgrade
+            DROP TRIGGER IF EXISTS "update_student_cgpa_insert";
+            DROP TRIGGER IF EXISTS "update_student_cgpa_update";
+            DROP TRIGGER IF EXISTS "update_student_cgpa_delete";
+
             CREATE TRIGGER IF NOT EXISTS "update_student_cgpa_insert"
             AFTER INSERT ON "STUDENT_COURSES"
             FOR EACH ROW
@@ -149,10 +373,15 @@ impl DatabaseConnection {
                     WHERE "STUDENT_COURSES"."student_id" = NEW."student_id"
                 ), 0.0),
                 "can_grad" = CASE
-                    WHEN COALESCE((SELECT SUM(CASE WHEN "grade" >= 0 THEN "cr_cost" ELSE 0 END) FROM "STUDENT_COURSES" WHERE "student_id" = NEW."student_id"), 0) >= 120 THEN 1
+                    WHEN COALESCE((
+                        SELECT SUM(CASE WHEN "grade" >= 0 THEN "cr_cost" ELSE 0 END)
+                        FROM "STUDENT_COURSES"
+                        JOIN "COURSES" ON "STUDENT_COURSES"."course_id" = "COURSES"."id"
+                        WHERE "STUDENT_COURSES"."student_id" = NEW."student_id"
+                    ), 0) >= 120 THEN 1
                     ELSE 0
                 END
-                WHERE "id" = NEW."student_id";
+                WHERE "student_id" = NEW."student_id";
             END;
 
             CREATE TRIGGER IF NOT EXISTS "update_student_cgpa_update"
@@ -167,10 +396,15 @@ impl DatabaseConnection {
                     WHERE "STUDENT_COURSES"."student_id" = NEW."student_id"
                 ), 0.0),
                 "can_grad" = CASE
-                    WHEN COALESCE((SELECT SUM(CASE WHEN "grade" >= 0 THEN "cr_cost" ELSE 0 END) FROM "STUDENT_COURSES" WHERE "student_id" = NEW."student_id"), 0) >= 120 THEN 1
+                    WHEN COALESCE((
+                        SELECT SUM(CASE WHEN "grade" >= 0 THEN "cr_cost" ELSE 0 END)
+                        FROM "STUDENT_COURSES"
+                        JOIN "COURSES" ON "STUDENT_COURSES"."course_id" = "COURSES"."id"
+                        WHERE "STUDENT_COURSES"."student_id" = NEW."student_id"
+                    ), 0) >= 120 THEN 1
                     ELSE 0
                 END
-                WHERE "id" = NEW."student_id";
+                WHERE "student_id" = NEW."student_id";
             END;
 
             CREATE TRIGGER IF NOT EXISTS "update_student_cgpa_delete"
@@ -181,20 +415,98 @@ impl DatabaseConnection {
                 SET "cgpa" = COALESCE((
                     SELECT SUM(CASE WHEN "grade" >= 0 THEN "grade" * "cr_cost" ELSE 0 END) / NULLIF(SUM(CASE WHEN "grade" >= 0 THEN "cr_cost" ELSE 0 END), 0)
                     FROM "STUDENT_COURSES"
-                    JOIN "COURSES" ON "STUDENT_COURSES"."course_id" = COURSES."id"
+                    JOIN "COURSES" ON "STUDENT_COURSES"."course_id" = "COURSES"."id"
                     WHERE "STUDENT_COURSES"."student_id" = OLD."student_id"
                 ), 0.0),
                 "can_grad" = CASE
-                    WHEN COALESCE((SELECT SUM(CASE WHEN "grade" >= 0 THEN "cr_cost" ELSE 0 END) FROM "STUDENT_COURSES" WHERE "student_id" = OLD."student_id"), 0) >= 120 THEN 1
+                    WHEN COALESCE((
+                        SELECT SUM(CASE WHEN "grade" >= 0 THEN "cr_cost" ELSE 0 END)
+                        FROM "STUDENT_COURSES"
+                        JOIN "COURSES" ON "STUDENT_COURSES"."course_id" = "COURSES"."id"
+                        WHERE "STUDENT_COURSES"."student_id" = OLD."student_id"
+                    ), 0) >= 120 THEN 1
                     ELSE 0
                 END
-                WHERE "id" = OLD."student_id";
+                WHERE "student_id" = OLD."student_id";
             END;
 
             COMMIT;
             "#,
             )?;
 
-        Ok(self)
+        // Version 3 added course capacities
+        if !self.columns("COURSES")?.iter().any(|c| c == "capacity") {
+            self.connection.execute_batch(
+                r#"ALTER TABLE "COURSES" ADD COLUMN "capacity" INTEGER NOT NULL DEFAULT 0"#,
+            )?;
+        }
+
+        // Recorded last, so an upgrade that fails part way runs again on the next open
+        self.connection.execute_batch(RECOMPUTE_STANDING)?;
+        self.connection
+            .pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
+        Ok(())
+    }
+
+    // Kept in `PRAGMA user_version`
+    fn schema_version(&self) -> Result<i32> {
+        let version = self
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        Ok(version)
+    }
+
+    fn begin(&mut self) -> Result<()> {
+        self.connection.execute_batch("BEGIN IMMEDIATE")?;
+
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.connection.execute_batch("COMMIT")?;
+
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<()> {
+        self.connection.execute_batch("ROLLBACK")?;
+
+        Ok(())
+    }
+
+    // `begin` has taken the write lock for the whole database already
+    fn lock(&mut self, _table: &str, _filters: &[Filter]) -> Result<()> {
+        Ok(())
+    }
+
+    // Fails on read-only files, full disks and databases another process keeps locked
+    fn check_writable(&mut self) -> Result<()> {
+        let connection = &self.connection;
+        connection.execute_batch("BEGIN IMMEDIATE")?;
+
+        let written = connection.execute(
+            r#"INSERT OR REPLACE INTO "SYSTEM_SETTINGS" ("key", "value") VALUES ('readiness_probe', '')"#,
+            [],
+        );
+
+        connection.execute_batch("ROLLBACK")?;
+        written?;
+
+        Ok(())
+    }
+
+    fn integrity_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.connection.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        Ok(rows.collect::<rusqlite::Result<Vec<String>>>()?)
+    }
+
+    fn vacuum(&mut self) -> Result<()> {
+        self.connection.execute_batch("VACUUM; ANALYZE;")?;
+
+        Ok(())
     }
 }
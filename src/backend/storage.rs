@@ -0,0 +1,446 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use super::db_driver::{Join, ReceiverType};
+use super::filter::*;
+use super::table_models::*;
+
+// Bumped whenever `create_tables` changes the schema, in every implementation
+pub const SCHEMA_VERSION: i32 = 5;
+
+// What the enrollment triggers keep up to date, worked out again for every student. Run
+// when upgrading from a schema whose triggers got it wrong.
+pub(super) const RECOMPUTE_STANDING: &str = r#"
+    UPDATE STUDENT_ACCOUNT
+    SET cgpa = COALESCE((
+        SELECT SUM(CASE WHEN grade >= 0 THEN grade * cr_cost ELSE 0 END)
+            / NULLIF(SUM(CASE WHEN grade >= 0 THEN cr_cost ELSE 0 END), 0)
+        FROM STUDENT_COURSES
+        JOIN COURSES ON STUDENT_COURSES.course_id = COURSES.id
+        WHERE STUDENT_COURSES.student_id = STUDENT_ACCOUNT.student_id
+    ), 0.0),
+    can_grad = COALESCE((
+        SELECT SUM(CASE WHEN grade >= 0 THEN cr_cost ELSE 0 END)
+        FROM STUDENT_COURSES
+        JOIN COURSES ON STUDENT_COURSES.course_id = COURSES.id
+        WHERE STUDENT_COURSES.student_id = STUDENT_ACCOUNT.student_id
+    ), 0) >= 120;
+"#;
+
+// Receives the column names and the values of one row
+pub type RowVisitor<'a> = dyn FnMut(&[String], Vec<Value>) -> Result<()> + 'a;
+
+// What `DbDriver` needs from a database. Implementations only run SQL and convert rows;
+// the statements come from `table_models` and `filter`, written in the subset of SQL
+// that SQLite and PostgreSQL both accept. `tests/storage.rs` holds every implementation
+// to the same behaviour.
+pub trait Storage: Send {
+    // Runs one statement that returns no rows
+    fn execute(&mut self, sql: &str) -> Result<()>;
+
+    // Hands each row to `f` as it is read. Integers, reals and text come back as JSON
+    // numbers and strings, booleans as 0 or 1. Returns the number of rows.
+    fn query(&self, sql: &str, f: &mut RowVisitor) -> Result<usize>;
+
+    // Column names of a table, in declaration order
+    fn columns(&self, table: &str) -> Result<Vec<String>>;
+
+    // Creates missing tables and triggers and records `SCHEMA_VERSION`
+    fn create_tables(&mut self) -> Result<()>;
+
+    fn schema_version(&self) -> Result<i32>;
+
+    // Takes the write lock up front, so a transaction doesn't fail halfway on a busy database
+    fn begin(&mut self) -> Result<()>;
+
+    fn commit(&mut self) -> Result<()>;
+
+    fn rollback(&mut self) -> Result<()>;
+
+    // Keeps other transactions from changing the matching rows of `table` (the SQL table
+    // name) until this one ends
+    fn lock(&mut self, table: &str, filters: &[Filter]) -> Result<()>;
+
+    // Writes a row and rolls it back
+    fn check_writable(&mut self) -> Result<()>;
+
+    // Problems found in the database files, or `["ok"]`
+    fn integrity_check(&self) -> Result<Vec<String>>;
+
+    // Reclaims free space and refreshes the planner's statistics
+    fn vacuum(&mut self) -> Result<()>;
+
+    fn find(
+        &self,
+        table: Table,
+        filters: Vec<Filter>,
+        join_mode: Option<Associativity>,
+    ) -> Result<Vec<ReceiverType>> {
+        assert!(
+            filters.iter().all(|f| f.to_string() == table.name()),
+            "Invalid filter for table."
+        );
+
+        let sql = select(table.name(), &filters, join_mode);
+        let mut found = Vec::new();
+
+        self.query(&sql, &mut |_, row| {
+            found.push(decode(&table, &row)?);
+            Ok(())
+        })?;
+
+        Ok(found)
+    }
+
+    fn insert(&mut self, data: Vec<ReceiverType>) -> Result<()> {
+        data.iter()
+            .try_for_each(|r| self.execute(&r.to_sql(Action::Insert)))
+    }
+
+    fn update(&mut self, data: Vec<ReceiverType>) -> Result<()> {
+        data.iter()
+            .try_for_each(|r| self.execute(&r.to_sql(Action::Update)))
+    }
+
+    fn delete(&mut self, data: Vec<ReceiverType>) -> Result<()> {
+        data.iter()
+            .try_for_each(|r| self.execute(&r.to_sql(Action::Delete)))
+    }
+
+    // Courses matching the filters, each with its teacher and their department
+    fn courses_with_teachers(&self, filters: Vec<Filter>) -> Result<Vec<CourseWithTeacher>> {
+        let tables = [Table::Courses, Table::Users, Table::TeacherAccount, Table::Departments];
+        let from = format!(
+            "{}{}{}",
+            Table::Courses.join(&Table::Users, Join::Inner),
+            Table::Users.join(&Table::TeacherAccount, Join::Inner),
+            Table::TeacherAccount.join(&Table::Departments, Join::Left),
+        );
+        let mut found = Vec::new();
+
+        self.query(&joined(&tables, &from, &filters), &mut |_, row| {
+            let mut row = JoinedRow::new(&row);
+            let (
+                Some(ReceiverType::Course(course)),
+                Some(ReceiverType::User(teacher)),
+                Some(ReceiverType::TeacherAccount(teacher_account)),
+                department,
+            ) = (
+                row.next(&tables[0])?,
+                row.next(&tables[1])?,
+                row.next(&tables[2])?,
+                row.next(&tables[3])?,
+            )
+            else {
+                unreachable!("decoded as the tables that were selected");
+            };
+
+            found.push(CourseWithTeacher {
+                course,
+                teacher,
+                teacher_account,
+                department: match department {
+                    Some(ReceiverType::Department(d)) => Some(d),
+                    _ => None,
+                },
+            });
+            Ok(())
+        })?;
+
+        Ok(found)
+    }
+
+    // Enrollments matching the filters, each with its course
+    fn enrollments_with_courses(&self, filters: Vec<Filter>) -> Result<Vec<EnrollmentWithCourse>> {
+        let tables = [Table::StudentCourses, Table::Courses];
+        let from = Table::StudentCourses.join(&Table::Courses, Join::Inner);
+        let mut found = Vec::new();
+
+        self.query(&joined(&tables, &from, &filters), &mut |_, row| {
+            let mut row = JoinedRow::new(&row);
+            let (Some(ReceiverType::StudentCourse(enrollment)), Some(ReceiverType::Course(course))) =
+                (row.next(&tables[0])?, row.next(&tables[1])?)
+            else {
+                unreachable!("decoded as the tables that were selected");
+            };
+
+            found.push(EnrollmentWithCourse { enrollment, course });
+            Ok(())
+        })?;
+
+        Ok(found)
+    }
+
+    // Enrollments matching the filters, each with its student
+    fn enrollments_with_students(&self, filters: Vec<Filter>) -> Result<Vec<EnrollmentWithStudent>> {
+        let tables = [Table::StudentCourses, Table::Users];
+        let from = Table::StudentCourses.join(&Table::Users, Join::Inner);
+        let mut found = Vec::new();
+
+        self.query(&joined(&tables, &from, &filters), &mut |_, row| {
+            let mut row = JoinedRow::new(&row);
+            let (Some(ReceiverType::StudentCourse(enrollment)), Some(ReceiverType::User(student))) =
+                (row.next(&tables[0])?, row.next(&tables[1])?)
+            else {
+                unreachable!("decoded as the tables that were selected");
+            };
+
+            found.push(EnrollmentWithStudent { enrollment, student });
+            Ok(())
+        })?;
+
+        Ok(found)
+    }
+
+    // Rows of `table` (the SQL table name) that match every filter
+    fn count(&self, table: &str, filters: &[Filter]) -> Result<i64> {
+        let sql = select(table, filters, None).replacen("SELECT *", "SELECT COUNT(*)", 1);
+        let mut count = 0;
+
+        self.query(&sql, &mut |_, row| {
+            count = integer(&row, 0)?;
+            Ok(())
+        })?;
+
+        Ok(count)
+    }
+}
+
+pub fn select(from: &str, filters: &[Filter], join_mode: Option<Associativity>) -> String {
+    if filters.is_empty() {
+        return format!("SELECT * FROM {}", from);
+    }
+
+    let conditions: Vec<String> = filters.iter().map(|f| f.to_sql()).collect();
+    let separator = join_mode.unwrap_or(Associativity::And).to_string();
+
+    format!("SELECT * FROM {} WHERE {}", from, conditions.join(&separator))
+}
+
+// Every column of `tables` aliased `<table>_<column>`, so none collide. `filters` select rows
+// of the first table, which `joins` follow.
+fn joined(tables: &[Table], joins: &str, filters: &[Filter]) -> String {
+    assert!(
+        filters.iter().all(|f| f.to_string() == tables[0].name()),
+        "Invalid filter for table."
+    );
+
+    let columns: Vec<String> = tables
+        .iter()
+        .flat_map(|table| {
+            table.columns().iter().map(move |column| {
+                format!("{}.{} AS {}_{}", table, column, table.name().to_lowercase(), column)
+            })
+        })
+        .collect();
+
+    // The filters' columns are unqualified, so they go on the first table alone
+    format!(
+        "SELECT {} FROM ({}) {}{}",
+        columns.join(", "),
+        select(tables[0].name(), filters, None),
+        tables[0],
+        joins
+    )
+}
+
+// Reads the tables of a joined row back, in the order they were selected
+struct JoinedRow<'a> {
+    row: &'a [Value],
+    at: usize,
+}
+
+impl<'a> JoinedRow<'a> {
+    fn new(row: &'a [Value]) -> Self {
+        Self { row, at: 0 }
+    }
+
+    // None when an outer join found no row, leaving every column null
+    fn next(&mut self, table: &Table) -> Result<Option<ReceiverType>> {
+        let end = self.at + table.columns().len();
+        let columns = self
+            .row
+            .get(self.at..end)
+            .ok_or_else(|| anyhow!("Expected {} more columns.", table.columns().len()))?;
+        self.at = end;
+
+        if columns.iter().all(Value::is_null) {
+            return Ok(None);
+        }
+
+        decode(table, columns).map(Some)
+    }
+}
+
+impl ReceiverType {
+    fn to_sql(&self, a: Action) -> String {
+        match self {
+            ReceiverType::User(u) => u.to_sql(a),
+            ReceiverType::StudentAccount(s) => s.to_sql(a),
+            ReceiverType::TeacherAccount(t) => t.to_sql(a),
+            ReceiverType::Course(c) => c.to_sql(a),
+            ReceiverType::StudentCourse(s) => s.to_sql(a),
+            ReceiverType::Department(d) => d.to_sql(a),
+            ReceiverType::Session(s) => s.to_sql(a),
+            ReceiverType::PasswordReset(p) => p.to_sql(a),
+            ReceiverType::TwoFactor(t) => t.to_sql(a),
+            ReceiverType::RecoveryCode(r) => r.to_sql(a),
+            ReceiverType::SystemSetting(s) => s.to_sql(a),
+            ReceiverType::LoginAttempt(l) => l.to_sql(a),
+            ReceiverType::Term(t) => t.to_sql(a),
+            ReceiverType::Standing(s) => s.to_sql(a),
+            ReceiverType::Notification(n) => n.to_sql(a),
+            ReceiverType::Hold(h) => h.to_sql(a),
+        }
+    }
+}
+
+// Builds the model for one row of `table`, with the columns in declaration order
+fn decode(table: &Table, row: &[Value]) -> Result<ReceiverType> {
+    let int = |i| integer(row, i).map(|n| n as i32);
+    let long = |i| integer(row, i);
+    let flag = |i| integer(row, i).map(|n| n != 0);
+    let text = |i| text(row, i);
+    let real = |i| {
+        row.get(i)
+            .and_then(Value::as_f64)
+            .map(|r| r as f32)
+            .ok_or_else(|| anyhow!("Column {} is not a number.", i))
+    };
+
+    Ok(match table {
+        Table::Users => ReceiverType::User(User {
+            id: int(0)?,
+            username: text(1)?,
+            password: text(2)?,
+            email: text(3)?,
+            phone: text(4)?,
+            verified: flag(5)?,
+            suspended: flag(6)?,
+            forcenewpw: flag(7)?,
+            role: text(8)?,
+        }),
+        Table::StudentAccount => ReceiverType::StudentAccount(StudentAccount {
+            id: int(0)?,
+            student_id: int(1)?,
+            advisor_id: int(2)?,
+            discipline: text(3)?,
+            enrollment: text(4)?,
+            cgpa: real(5)?,
+            can_grad: flag(6)?,
+            cur_credit: int(7)?,
+            cum_credit: int(8)?,
+        }),
+        Table::TeacherAccount => ReceiverType::TeacherAccount(TeacherAccount {
+            id: int(0)?,
+            teacher_id: int(1)?,
+            dept_id: int(2)?,
+        }),
+        Table::Courses => ReceiverType::Course(Courses {
+            id: int(0)?,
+            teacher_id: int(1)?,
+            course: text(2)?,
+            course_nr: text(3)?,
+            description: text(4)?,
+            cr_cost: int(5)?,
+            timeslots: text(6)?,
+            capacity: int(7)?,
+        }),
+        Table::StudentCourses => ReceiverType::StudentCourse(StudentCourse {
+            student_id: int(0)?,
+            course_id: int(1)?,
+            grade: real(2)?,
+            semester: text(3)?,
+        }),
+        Table::Departments => ReceiverType::Department(Departments {
+            id: int(0)?,
+            name: text(1)?,
+        }),
+        Table::Sessions => ReceiverType::Session(Session {
+            id: int(0)?,
+            user_id: int(1)?,
+            token_hash: text(2)?,
+            created_at: long(3)?,
+            expires_at: long(4)?,
+        }),
+        Table::PasswordResets => ReceiverType::PasswordReset(PasswordReset {
+            id: int(0)?,
+            user_id: int(1)?,
+            token_hash: text(2)?,
+            expires_at: long(3)?,
+            used: flag(4)?,
+        }),
+        Table::TwoFactor => ReceiverType::TwoFactor(TwoFactor {
+            id: int(0)?,
+            user_id: int(1)?,
+            secret: text(2)?,
+            enabled: flag(3)?,
+            last_step: long(4)?,
+        }),
+        Table::RecoveryCodes => ReceiverType::RecoveryCode(RecoveryCode {
+            id: int(0)?,
+            user_id: int(1)?,
+            code_hash: text(2)?,
+            used: flag(3)?,
+        }),
+        Table::SystemSettings => ReceiverType::SystemSetting(SystemSetting {
+            key: text(0)?,
+            value: text(1)?,
+        }),
+        Table::LoginAttempts => ReceiverType::LoginAttempt(LoginAttempt {
+            id: int(0)?,
+            scope: text(1)?,
+            key: text(2)?,
+            failures: int(3)?,
+            last_failure: long(4)?,
+            locked_until: long(5)?,
+        }),
+        Table::Terms => ReceiverType::Term(Term {
+            id: int(0)?,
+            name: text(1)?,
+            starts_on: text(2)?,
+            ends_on: text(3)?,
+        }),
+        Table::StandingHistory => ReceiverType::Standing(StandingRecord {
+            id: int(0)?,
+            student_id: int(1)?,
+            term: text(2)?,
+            standing: text(3)?,
+            term_gpa: real(4)?,
+            cumulative_gpa: real(5)?,
+            decided_at: long(6)?,
+        }),
+        Table::Notifications => ReceiverType::Notification(Notification {
+            id: int(0)?,
+            user_id: int(1)?,
+            subject: text(2)?,
+            body: text(3)?,
+            created_at: long(4)?,
+        }),
+        Table::Holds => ReceiverType::Hold(Hold {
+            id: int(0)?,
+            student_id: int(1)?,
+            kind: text(2)?,
+            reason: text(3)?,
+            placed_by: int(4)?,
+            placed_at: long(5)?,
+            blocks_registration: flag(6)?,
+            blocks_transcripts: flag(7)?,
+            released_by: int(8)?,
+            released_at: long(9)?,
+        }),
+    })
+}
+
+fn integer(row: &[Value], i: usize) -> Result<i64> {
+    row.get(i)
+        .and_then(Value::as_i64)
+        .ok_or_else(|| anyhow!("Column {} is not an integer.", i))
+}
+
+fn text(row: &[Value], i: usize) -> Result<String> {
+    match row.get(i) {
+        Some(Value::String(s)) => Ok(s.to_owned()),
+        _ => Err(anyhow!("Column {} is not text.", i)),
+    }
+}
@@ -14,25 +14,118 @@ pub enum Table {
     TeacherAccount,
     Courses,
     StudentCourses,
-    Departments
+    Departments,
+    Sessions,
+    PasswordResets,
+    TwoFactor,
+    RecoveryCodes,
+    SystemSettings,
+    LoginAttempts,
+    Terms,
+    StandingHistory,
+    Notifications,
+    Holds
 }
 
 impl Display for Table {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Table::Users => write!(f, r#""USERS""#),
-            Table::StudentAccount => write!(f, r#""STUDENT_ACCOUNTS""#),
-            Table::TeacherAccount => write!(f, r#""TEACHER_ACCOUNTS""#),
-            Table::Courses => write!(f, r#""COURSES""#),
-            Table::StudentCourses => write!(f, r#""STUDENT_COURSES""#),
-            Table::Departments => write!(f, r#""DEPARTMENTS""#)
-        }
+        write!(f, "{}", self.name())
     }
 }
 
 impl Table {
+    // Unquoted, so PostgreSQL folds them to the lowercase names it created
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Users => "USERS",
+            Table::StudentAccount => "STUDENT_ACCOUNT",
+            Table::TeacherAccount => "TEACHER_ACCOUNT",
+            Table::Courses => "COURSES",
+            Table::StudentCourses => "STUDENT_COURSES",
+            Table::Departments => "DEPARTMENTS",
+            Table::Sessions => "SESSIONS",
+            Table::PasswordResets => "PASSWORD_RESETS",
+            Table::TwoFactor => "TWO_FACTOR",
+            Table::RecoveryCodes => "RECOVERY_CODES",
+            Table::SystemSettings => "SYSTEM_SETTINGS",
+            Table::LoginAttempts => "LOGIN_ATTEMPTS",
+            Table::Terms => "TERMS",
+            Table::StandingHistory => "STANDING_HISTORY",
+            Table::Notifications => "NOTIFICATIONS",
+            Table::Holds => "HOLDS",
+        }
+    }
+
+    // Every column, in declaration order
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Table::Users => &[
+                "id", "username", "password", "email", "phone", "verified", "suspended",
+                "forcenewpw", "role",
+            ],
+            Table::StudentAccount => &[
+                "id", "student_id", "advisor_id", "discipline", "enrollment", "cgpa", "can_grad",
+                "cur_credit", "cum_credit",
+            ],
+            Table::TeacherAccount => &["id", "teacher_id", "dept_id"],
+            Table::Courses => &[
+                "id", "teacher_id", "course", "course_nr", "description", "cr_cost", "timeslots",
+                "capacity",
+            ],
+            Table::StudentCourses => &["student_id", "course_id", "grade", "semester"],
+            Table::Departments => &["id", "name"],
+            Table::Sessions => &["id", "user_id", "token_hash", "created_at", "expires_at"],
+            Table::PasswordResets => &["id", "user_id", "token_hash", "expires_at", "used"],
+            Table::TwoFactor => &["id", "user_id", "secret", "enabled", "last_step"],
+            Table::RecoveryCodes => &["id", "user_id", "code_hash", "used"],
+            Table::SystemSettings => &["key", "value"],
+            Table::LoginAttempts => &[
+                "id", "scope", "key", "failures", "last_failure", "locked_until",
+            ],
+            Table::Terms => &["id", "name", "starts_on", "ends_on"],
+            Table::StandingHistory => &[
+                "id", "student_id", "term", "standing", "term_gpa", "cumulative_gpa", "decided_at",
+            ],
+            Table::Notifications => &["id", "user_id", "subject", "body", "created_at"],
+            Table::Holds => &[
+                "id", "student_id", "kind", "reason", "placed_by", "placed_at",
+                "blocks_registration", "blocks_transcripts", "released_by", "released_at",
+            ],
+        }
+    }
+
+    // ` INNER JOIN USERS ON COURSES.teacher_id = USERS.id`, to follow a FROM that already
+    // has this table. Tables that don't refer to each other are paired row by row.
     pub fn join(&self, other: &Table, join_as: Join) -> String {
-        format!("{} {} {}", self, join_as, other)
+        let on = match (self.reference_to(other), other.reference_to(self)) {
+            (Some(column), _) => format!("{}.{} = {}.id", self, column, other),
+            (None, Some(column)) => format!("{}.{} = {}.id", other, column, self),
+            (None, None) => String::from("1 = 1"),
+        };
+
+        format!("{}{} ON {}", join_as, other, on)
+    }
+
+    // The column of this table that holds an id of `other`
+    fn reference_to(&self, other: &Table) -> Option<&'static str> {
+        match (self, other) {
+            (Table::StudentAccount, Table::Users) => Some("student_id"),
+            (Table::TeacherAccount, Table::Users) => Some("teacher_id"),
+            (Table::TeacherAccount, Table::Departments) => Some("dept_id"),
+            (Table::Courses, Table::Users) => Some("teacher_id"),
+            (Table::StudentCourses, Table::Users) => Some("student_id"),
+            (Table::StudentCourses, Table::Courses) => Some("course_id"),
+            (Table::StandingHistory | Table::Holds, Table::Users) => Some("student_id"),
+            (
+                Table::Sessions
+                | Table::PasswordResets
+                | Table::TwoFactor
+                | Table::RecoveryCodes
+                | Table::Notifications,
+                Table::Users,
+            ) => Some("user_id"),
+            _ => None,
+        }
     }
 }
 
@@ -40,7 +133,12 @@ pub trait ToSQL {
     fn to_sql(&self, a: Action) -> String;
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Doubles single quotes so free text can sit inside a SQL string literal
+pub fn escape(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct User {
     pub id: i32,
     pub username: String,
@@ -59,18 +157,18 @@ impl ToSQL for User {
             Action::Insert => format!(
                 "INSERT INTO USERS (username, password, email, phone, 
                     verified, suspended, forcenewpw, role) VALUES ('{}', '{}', '{}', '{}', {}, {}, {}, '{}')",
-                self.username, self.password, self.email, self.phone, 
-                    self.verified, self.suspended, self.forcenewpw, self.role
+                escape(&self.username), escape(&self.password), escape(&self.email), escape(&self.phone),
+                    self.verified, self.suspended, self.forcenewpw, escape(&self.role)
             ),
 
             Action::Update => {
-                if self.password == "" {
+                if self.password.is_empty() {
                     return format!(
                         "UPDATE USERS SET username = '{}', email = '{}', phone = '{}', 
                             verified = {}, suspended = {}, forcenewpw = {}, role = '{}' 
                             WHERE id = {}",
-                        self.username, self.email, self.phone,
-                        self.verified, self.suspended, self.forcenewpw, self.role, self.id
+                        escape(&self.username), escape(&self.email), escape(&self.phone),
+                        self.verified, self.suspended, self.forcenewpw, escape(&self.role), self.id
                     )
                 }
 
@@ -78,8 +176,8 @@ impl ToSQL for User {
                     "UPDATE USERS SET username = '{}', password = '{}', email = '{}', phone = '{}', 
                         verified = {}, suspended = {}, forcenewpw = {}, role = '{}' 
                         WHERE id = {}",
-                    self.username, self.password, self.email, self.phone, 
-                        self.verified, self.suspended, self.forcenewpw, self.role, self.id
+                    escape(&self.username), escape(&self.password), escape(&self.email), escape(&self.phone),
+                        self.verified, self.suspended, self.forcenewpw, escape(&self.role), self.id
                 )
             },
 
@@ -109,14 +207,14 @@ impl ToSQL for StudentAccount {
             Action::Insert => format!(
                 "INSERT INTO STUDENT_ACCOUNT (student_id, advisor_id, discipline, enrollment, cgpa, can_grad, cur_credit, cum_credit) 
                 VALUES ({}, {}, '{}', '{}', {}, {}, {}, {})",
-                self.student_id, self.advisor_id, self.discipline, self.enrollment, self.cgpa, self.can_grad, self.cur_credit, self.cum_credit
+                self.student_id, self.advisor_id, escape(&self.discipline), escape(&self.enrollment), self.cgpa, self.can_grad, self.cur_credit, self.cum_credit
             ),
 
             Action::Update => format!(
                 "UPDATE STUDENT_ACCOUNT SET student_id = {}, advisor_id = {}, discipline = '{}', 
                 enrollment = '{}', cgpa = {}, can_grad = {}, cur_credit = {}, cum_credit = {} 
                 WHERE id = {}",
-                self.student_id, self.advisor_id, self.discipline, self.enrollment, self.cgpa, 
+                self.student_id, self.advisor_id, escape(&self.discipline), escape(&self.enrollment), self.cgpa,
                 self.can_grad, self.cur_credit, self.cum_credit, self.id
             ),
 
@@ -138,17 +236,17 @@ impl ToSQL for TeacherAccount {
     fn to_sql(&self, a: Action) -> String {
         match a {
             Action::Insert => format!(
-                r#"INSERT INTO "TEACHER_ACCOUNT" ("teacher_id", "dept_id") VALUES ({}, {})"#,
+                r#"INSERT INTO TEACHER_ACCOUNT ("teacher_id", "dept_id") VALUES ({}, {})"#,
                 self.teacher_id, self.dept_id
             ),
 
             Action::Update => format!(
-                r#"UPDATE "TEACHER_ACCOUNT" SET "teacher_id" = {}, "dept_id" = {} WHERE "id" = {}"#,
+                r#"UPDATE TEACHER_ACCOUNT SET "teacher_id" = {}, "dept_id" = {} WHERE "id" = {}"#,
                 self.teacher_id, self.dept_id, self.id
             ),
 
             Action::Delete => format!(
-                r#"DELETE FROM "TEACHER_ACCOUNT" WHERE "id" = {}"#, self.id
+                r#"DELETE FROM TEACHER_ACCOUNT WHERE "id" = {}"#, self.id
             )
         }
     }
@@ -163,21 +261,24 @@ pub struct Courses {
     pub description: String,
     pub cr_cost: i32,
     pub timeslots: String,
+    // Seats per term; 0 for no limit
+    #[serde(default)]
+    pub capacity: i32,
 }
 
 impl ToSQL for Courses {
     fn to_sql(&self, a: Action) -> String {
         match a {
             Action::Insert => format!(
-                r#"INSERT INTO "COURSES" ("teacher_id", "course", "course_nr", "description", "cr_cost", "timeslots") 
-                VALUES ({}, '{}', '{}', '{}', {}, '{}')"#,
-                self.teacher_id, self.course, self.course_nr, self.description, self.cr_cost, self.timeslots
+                r#"INSERT INTO COURSES ("teacher_id", "course", "course_nr", "description", "cr_cost", "timeslots", "capacity") 
+                VALUES ({}, '{}', '{}', '{}', {}, '{}', {})"#,
+                self.teacher_id, escape(&self.course), escape(&self.course_nr), escape(&self.description), self.cr_cost, escape(&self.timeslots), self.capacity
             ),
 
             Action::Update => format!(
-                r#"UPDATE COURSES SET teacher_id = {}, course = '{}', course_nr = '{}', description = '{}', cr_cost = {}, timeslots = '{}' WHERE id = {}"#,
-                self.teacher_id, self.course, self.course_nr, 
-                self.description, self.cr_cost, self.timeslots, self.id
+                r#"UPDATE COURSES SET teacher_id = {}, course = '{}', course_nr = '{}', description = '{}', cr_cost = {}, timeslots = '{}', capacity = {} WHERE id = {}"#,
+                self.teacher_id, escape(&self.course), escape(&self.course_nr),
+                escape(&self.description), self.cr_cost, escape(&self.timeslots), self.capacity, self.id
             ),
             
             Action::Delete => format!(
@@ -201,17 +302,19 @@ impl ToSQL for StudentCourse {
             Action::Insert => format!(
                 "INSERT INTO student_courses (student_id, course_id, grade, semester) 
                 VALUES ({}, {}, {}, '{}')",
-                self.student_id, self.course_id, self.grade, self.semester
+                self.student_id, self.course_id, self.grade, escape(&self.semester)
             ),
 
+            // A repeated course is a new row each term, so the term is part of the key
             Action::Update => format!(
-                "UPDATE student_courses SET student_id = {}, course_id = {}, grade = {}, semester = '{}' 
-                WHERE student_id = {} AND course_id = {}",
-                self.student_id, self.course_id, self.grade, self.semester, self.student_id, self.course_id
+                "UPDATE student_courses SET grade = {}
+                WHERE student_id = {} AND course_id = {} AND semester = '{}'",
+                self.grade, self.student_id, self.course_id, escape(&self.semester)
             ),
 
             Action::Delete => format!(
-                "DELETE FROM student_courses WHERE student_id = {} AND course_id = {}", self.student_id, self.course_id
+                "DELETE FROM student_courses WHERE student_id = {} AND course_id = {} AND semester = '{}'",
+                self.student_id, self.course_id, escape(&self.semester)
             )
         }
     }
@@ -228,12 +331,12 @@ impl ToSQL for Departments {
         match a {
             Action::Insert => format!(
                 "INSERT INTO departments (name) VALUES ('{}')",
-                self.name
+                escape(&self.name)
             ),
 
             Action::Update => format!(
                 "UPDATE departments SET name = '{}' WHERE id = {}",
-                self.name, self.id
+                escape(&self.name), self.id
             ),
 
             Action::Delete => format!(
@@ -242,3 +345,325 @@ impl ToSQL for Departments {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+impl ToSQL for Session {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO sessions (user_id, token_hash, created_at, expires_at) VALUES ({}, '{}', {}, {})",
+                self.user_id, self.token_hash, self.created_at, self.expires_at
+            ),
+
+            Action::Update => format!(
+                "UPDATE sessions SET user_id = {}, token_hash = '{}', created_at = {}, expires_at = {} WHERE id = {}",
+                self.user_id, self.token_hash, self.created_at, self.expires_at, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM sessions WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordReset {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: i64,
+    pub used: bool,
+}
+
+impl ToSQL for PasswordReset {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO password_resets (user_id, token_hash, expires_at, used) VALUES ({}, '{}', {}, {})",
+                self.user_id, self.token_hash, self.expires_at, self.used
+            ),
+
+            Action::Update => format!(
+                "UPDATE password_resets SET user_id = {}, token_hash = '{}', expires_at = {}, used = {} WHERE id = {}",
+                self.user_id, self.token_hash, self.expires_at, self.used, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM password_resets WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactor {
+    pub id: i32,
+    pub user_id: i32,
+    pub secret: String,
+    pub enabled: bool,
+    pub last_step: i64,
+}
+
+impl ToSQL for TwoFactor {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO two_factor (user_id, secret, enabled, last_step) VALUES ({}, '{}', {}, {})",
+                self.user_id, escape(&self.secret), self.enabled, self.last_step
+            ),
+
+            Action::Update => format!(
+                "UPDATE two_factor SET user_id = {}, secret = '{}', enabled = {}, last_step = {} WHERE id = {}",
+                self.user_id, escape(&self.secret), self.enabled, self.last_step, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM two_factor WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryCode {
+    pub id: i32,
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used: bool,
+}
+
+impl ToSQL for RecoveryCode {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO recovery_codes (user_id, code_hash, used) VALUES ({}, '{}', {})",
+                self.user_id, escape(&self.code_hash), self.used
+            ),
+
+            Action::Update => format!(
+                "UPDATE recovery_codes SET user_id = {}, code_hash = '{}', used = {} WHERE id = {}",
+                self.user_id, escape(&self.code_hash), self.used, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM recovery_codes WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemSetting {
+    pub key: String,
+    pub value: String,
+}
+
+impl ToSQL for SystemSetting {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO system_settings (key, value) VALUES ('{}', '{}') ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                escape(&self.key), escape(&self.value)
+            ),
+
+            Action::Update => format!(
+                "UPDATE system_settings SET value = '{}' WHERE key = '{}'",
+                escape(&self.value), escape(&self.key)
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM system_settings WHERE key = '{}'", escape(&self.key)
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginAttempt {
+    pub id: i32,
+    pub scope: String,
+    pub key: String,
+    pub failures: i32,
+    pub last_failure: i64,
+    pub locked_until: i64,
+}
+
+impl ToSQL for LoginAttempt {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO login_attempts (scope, key, failures, last_failure, locked_until) VALUES ('{}', '{}', {}, {}, {})",
+                escape(&self.scope), escape(&self.key), self.failures, self.last_failure, self.locked_until
+            ),
+
+            Action::Update => format!(
+                "UPDATE login_attempts SET scope = '{}', key = '{}', failures = {}, last_failure = {}, locked_until = {} WHERE id = {}",
+                escape(&self.scope), escape(&self.key), self.failures, self.last_failure, self.locked_until, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM login_attempts WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Term {
+    pub id: i32,
+    pub name: String,
+    pub starts_on: String,
+    pub ends_on: String,
+}
+
+impl ToSQL for Term {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO terms (name, starts_on, ends_on) VALUES ('{}', '{}', '{}')",
+                escape(&self.name), escape(&self.starts_on), escape(&self.ends_on)
+            ),
+
+            Action::Update => format!(
+                "UPDATE terms SET name = '{}', starts_on = '{}', ends_on = '{}' WHERE id = {}",
+                escape(&self.name), escape(&self.starts_on), escape(&self.ends_on), self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM terms WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+// A student's standing as decided when a term was closed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StandingRecord {
+    pub id: i32,
+    pub student_id: i32,
+    pub term: String,
+    pub standing: String,
+    pub term_gpa: f32,
+    pub cumulative_gpa: f32,
+    pub decided_at: i64,
+}
+
+impl ToSQL for StandingRecord {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO standing_history (student_id, term, standing, term_gpa, cumulative_gpa, decided_at) VALUES ({}, '{}', '{}', {}, {}, {})",
+                self.student_id, escape(&self.term), escape(&self.standing), self.term_gpa, self.cumulative_gpa, self.decided_at
+            ),
+
+            Action::Update => format!(
+                "UPDATE standing_history SET student_id = {}, term = '{}', standing = '{}', term_gpa = {}, cumulative_gpa = {}, decided_at = {} WHERE id = {}",
+                self.student_id, escape(&self.term), escape(&self.standing), self.term_gpa, self.cumulative_gpa, self.decided_at, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM standing_history WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub subject: String,
+    pub body: String,
+    pub created_at: i64,
+}
+
+impl ToSQL for Notification {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO notifications (user_id, subject, body, created_at) VALUES ({}, '{}', '{}', {})",
+                self.user_id, escape(&self.subject), escape(&self.body), self.created_at
+            ),
+
+            Action::Update => format!(
+                "UPDATE notifications SET user_id = {}, subject = '{}', body = '{}', created_at = {} WHERE id = {}",
+                self.user_id, escape(&self.subject), escape(&self.body), self.created_at, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM notifications WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+// Keeps a student from registering, from getting a transcript, or both, until released
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hold {
+    pub id: i32,
+    pub student_id: i32,
+    // One of `HOLD_KINDS` in server_connection_impl, e.g. `fees`
+    pub kind: String,
+    pub reason: String,
+    pub placed_by: i32,
+    pub placed_at: i64,
+    pub blocks_registration: bool,
+    pub blocks_transcripts: bool,
+    // Both 0 while the hold is in force
+    pub released_by: i32,
+    pub released_at: i64,
+}
+
+impl ToSQL for Hold {
+    fn to_sql(&self, a: Action) -> String {
+        match a {
+            Action::Insert => format!(
+                "INSERT INTO holds (student_id, kind, reason, placed_by, placed_at, blocks_registration, blocks_transcripts, released_by, released_at) VALUES ({}, '{}', '{}', {}, {}, {}, {}, {}, {})",
+                self.student_id, escape(&self.kind), escape(&self.reason), self.placed_by, self.placed_at,
+                self.blocks_registration, self.blocks_transcripts, self.released_by, self.released_at
+            ),
+
+            Action::Update => format!(
+                "UPDATE holds SET student_id = {}, kind = '{}', reason = '{}', placed_by = {}, placed_at = {}, blocks_registration = {}, blocks_transcripts = {}, released_by = {}, released_at = {} WHERE id = {}",
+                self.student_id, escape(&self.kind), escape(&self.reason), self.placed_by, self.placed_at,
+                self.blocks_registration, self.blocks_transcripts, self.released_by, self.released_at, self.id
+            ),
+
+            Action::Delete => format!(
+                "DELETE FROM holds WHERE id = {}", self.id
+            )
+        }
+    }
+}
+
+// Rows of several tables read together by one query; see `Storage::courses_with_teachers`
+
+#[derive(Debug, Clone)]
+pub struct CourseWithTeacher {
+    pub course: Courses,
+    pub teacher: User,
+    pub teacher_account: TeacherAccount,
+    // None while the teacher is in no department
+    pub department: Option<Departments>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnrollmentWithCourse {
+    pub enrollment: StudentCourse,
+    pub course: Courses,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnrollmentWithStudent {
+    pub enrollment: StudentCourse,
+    pub student: User,
+}
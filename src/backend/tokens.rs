@@ -0,0 +1,74 @@
+use anyhow::{Ok, Result};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use super::error::bad_request;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const VERIFY_EMAIL: &str = "verify-email";
+
+// Tokens look like `base64(purpose|expires_at|subject).base64(hmac)`, so they can be
+// checked without any server-side state.
+pub fn sign(secret: &[u8], purpose: &str, subject: &str, expires_at: i64) -> String {
+    let payload = format!("{}|{}|{}", purpose, expires_at, subject);
+    let signature = mac(secret, payload.as_bytes()).finalize().into_bytes();
+
+    format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(payload),
+        URL_SAFE_NO_PAD.encode(signature)
+    )
+}
+
+// Returns the subject of a valid, unexpired token issued for `purpose`
+pub fn verify(secret: &[u8], purpose: &str, token: &str) -> Result<String> {
+    let invalid = || bad_request("Invalid or expired token.");
+
+    let (payload, signature) = token.split_once('.').ok_or_else(invalid)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+
+    mac(secret, &payload)
+        .verify_slice(&signature)
+        .map_err(|_| invalid())?;
+
+    let payload = String::from_utf8(payload).map_err(|_| invalid())?;
+    let mut parts = payload.splitn(3, '|');
+
+    let (token_purpose, expires_at, subject) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(p), Some(e), Some(s)) => (p, e, s),
+        _ => return Err(invalid()),
+    };
+
+    let expires_at = expires_at.parse::<i64>().map_err(|_| invalid())?;
+
+    if token_purpose != purpose || expires_at < chrono::Utc::now().timestamp() {
+        return Err(invalid());
+    }
+
+    Ok(subject.to_owned())
+}
+
+// Random bearer tokens (sessions, password resets). Only their digest is ever stored.
+pub fn generate_opaque() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn digest(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn mac(secret: &[u8], payload: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac
+}
@@ -0,0 +1,107 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+// Every version the server mounts. A breaking change goes into a new scope next to the
+// old one (`/api/v2`) rather than into `rest_api::routes`.
+pub const V1: &str = "/api/v1";
+pub const VERSIONS: &[&str] = &[V1];
+
+// When the unversioned routes were deprecated: 2026-10-18 00:00:00 UTC, the release that
+// added `/api/v1`. RFC 9745 wants it as a structured date, `@` and Unix seconds.
+pub const LEGACY_DEPRECATION: &str = "@1792281600";
+
+// `/api/v1/courses` -> `/courses`, for code that only cares about the route itself
+pub fn unversioned(path: &str) -> &str {
+    for version in VERSIONS {
+        if let Some(rest) = path.strip_prefix(version) {
+            if rest.is_empty() {
+                return "/";
+            }
+            if rest.starts_with('/') {
+                return rest;
+            }
+        }
+    }
+
+    path
+}
+
+// Marks responses from the unversioned routes as deprecated (RFC 9745 / RFC 8594) and
+// points clients at the same route under `/api/v1`.
+pub struct Deprecated {
+    sunset: Rc<str>,
+}
+
+impl Deprecated {
+    // `sunset` is an HTTP date, e.g. `Thu, 01 Jul 2027 00:00:00 GMT`
+    pub fn new(sunset: &str) -> Self {
+        Self {
+            sunset: Rc::from(sunset),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Deprecated
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = DeprecatedMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeprecatedMiddleware {
+            service: Rc::new(service),
+            sunset: self.sunset.clone(),
+        }))
+    }
+}
+
+pub struct DeprecatedMiddleware<S> {
+    service: Rc<S>,
+    sunset: Rc<str>,
+}
+
+impl<S, B> Service<ServiceRequest> for DeprecatedMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let sunset = self.sunset.clone();
+        let successor = format!("<{}{}>; rel=\"successor-version\"", V1, req.path());
+
+        Box::pin(async move {
+            let mut res = service.call(req).await?;
+            let headers = res.headers_mut();
+
+            headers.insert(
+                HeaderName::from_static("deprecation"),
+                HeaderValue::from_static(LEGACY_DEPRECATION),
+            );
+            if let Ok(sunset) = HeaderValue::from_str(&sunset) {
+                headers.insert(HeaderName::from_static("sunset"), sunset);
+            }
+            if let Ok(link) = HeaderValue::from_str(&successor) {
+                headers.insert(HeaderName::from_static("link"), link);
+            }
+
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,106 @@
+use serde_derive::Serialize;
+
+use super::table_models::{Courses, Departments, StandingRecord, TeacherAccount, User};
+
+// What responses may show of an account. `User` itself isn't `Serialize`, so the
+// password hash can only reach a client by being copied into one of these on purpose.
+
+// Another person's account; see `UserResponse::for_viewer` for when the email is shown
+#[derive(Debug, Clone, Serialize)]
+pub struct UserView {
+    pub id: i32,
+    pub username: String,
+    pub role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+// Everything but the password, for admins and for the account's owner
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminUserView {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub phone: String,
+    pub verified: bool,
+    pub suspended: bool,
+    pub forcenewpw: bool,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum UserResponse {
+    Full(AdminUserView),
+    Public(UserView),
+}
+
+impl From<&User> for AdminUserView {
+    fn from(u: &User) -> Self {
+        Self {
+            id: u.id,
+            username: u.username.clone(),
+            email: u.email.clone(),
+            phone: u.phone.clone(),
+            verified: u.verified,
+            suspended: u.suspended,
+            forcenewpw: u.forcenewpw,
+            role: u.role.clone(),
+        }
+    }
+}
+
+impl UserResponse {
+    // Admins and the owner see the whole account. Otherwise staff emails are visible to
+    // everyone signed in, students' only to staff; names are always visible.
+    pub fn for_viewer(viewer: Option<&User>, user: &User) -> Self {
+        let is_staff = |u: &User| matches!(u.role.to_lowercase().as_str(), "admin" | "teacher");
+
+        match viewer {
+            Some(v) if v.role.to_lowercase() == "admin" || v.id == user.id => {
+                UserResponse::Full(AdminUserView::from(user))
+            }
+            _ => UserResponse::Public(UserView {
+                id: user.id,
+                username: user.username.clone(),
+                role: user.role.clone(),
+                email: viewer
+                    .filter(|v| is_staff(v) || is_staff(user))
+                    .map(|_| user.email.clone()),
+            }),
+        }
+    }
+}
+
+// A course with who teaches it and how full it is this term
+#[derive(Debug, Clone, Serialize)]
+pub struct CourseDetail {
+    pub course: Courses,
+    // The instructor
+    pub user: UserResponse,
+    pub teacher_account: TeacherAccount,
+    pub department: Option<Departments>,
+    pub semester: String,
+    pub enrolled: usize,
+    // None when the course has no limit
+    pub remaining_seats: Option<i32>,
+    // Only for the instructor and admins
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roster: Option<Vec<RosterEntry>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RosterEntry {
+    pub student: UserResponse,
+    pub grade: f32,
+}
+
+// What closing a term decided
+#[derive(Debug, Clone, Serialize)]
+pub struct TermClosing {
+    pub term: String,
+    // Students with enrollments in the term
+    pub students: usize,
+    // Standings recorded this time; closing a term again leaves out those unchanged
+    pub decisions: Vec<StandingRecord>,
+}
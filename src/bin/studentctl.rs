@@ -0,0 +1,711 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+
+use student_sys::backend::error::redacted;
+use student_sys::backend::export::{ExportFormat, ExportRequest};
+use student_sys::backend::filter::{Filter, UsersFilter};
+use student_sys::backend::import::ImportKind;
+use student_sys::backend::policy::ValidationErrors;
+use student_sys::backend::seed::SeedPlan;
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::Settings;
+use student_sys::backend::table_models::{Courses, Departments, Term, User};
+use student_sys::backend::views::AdminUserView;
+
+// Administration tool that works on the database directly, with the same rules as the server.
+// Reads the same config file and STUDENT_SYS_* variables as the server.
+#[derive(Parser)]
+#[command(name = "studentctl", version, about = "Administer the student management system")]
+struct Cli {
+    /// Print machine-readable JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create, suspend and change the role of users
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Manage departments
+    #[command(subcommand)]
+    Departments(DepartmentsCommand),
+    /// Manage courses
+    #[command(subcommand)]
+    Courses(CoursesCommand),
+    /// Set up academic terms
+    #[command(subcommand)]
+    Terms(TermsCommand),
+    /// Import users, courses or enrollments from CSV; all rows or none
+    Import {
+        /// users, courses or enrollments
+        kind: ImportKind,
+        file: PathBuf,
+        /// Validate and report without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export a table as CSV, JSON Lines or XLSX; sensitive columns are left out
+    Export {
+        /// users, students, teachers, courses, enrollments, departments or terms
+        table: String,
+        /// csv, jsonl or xlsx
+        #[arg(long, short, default_value = "csv")]
+        format: ExportFormat,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Only rows where COLUMN equals VALUE; may be repeated
+        #[arg(long, value_name = "COLUMN=VALUE")]
+        filter: Vec<String>,
+    },
+    /// Show system statistics
+    Stats,
+    /// Fill an empty database with generated data for demos and load tests
+    Seed(SeedArgs),
+    /// Database maintenance
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// Take, verify and restore snapshots of the database; safe while the server runs
+    #[command(subcommand)]
+    Backup(BackupCommand),
+}
+
+#[derive(Subcommand)]
+enum UsersCommand {
+    /// List users, optionally only those with a role
+    List {
+        #[arg(long)]
+        role: Option<String>,
+    },
+    /// Create a user; the registration policy applies
+    Create(CreateUser),
+    /// Suspend a user
+    Suspend { id: i32 },
+    /// Lift a suspension
+    Unsuspend { id: i32 },
+    /// Change a user's role (admin, teacher or student)
+    SetRole { id: i32, role: String },
+    /// Delete a user
+    Delete { id: i32 },
+}
+
+#[derive(Args)]
+struct CreateUser {
+    #[arg(long)]
+    username: String,
+    #[arg(long)]
+    email: String,
+    #[arg(long)]
+    password: String,
+    #[arg(long, default_value = "")]
+    phone: String,
+    #[arg(long, default_value = "student")]
+    role: String,
+    /// Require the user to verify their email before signing in
+    #[arg(long)]
+    unverified: bool,
+}
+
+#[derive(Subcommand)]
+enum DepartmentsCommand {
+    List,
+    Create { name: String },
+    Delete { id: i32 },
+}
+
+#[derive(Subcommand)]
+enum CoursesCommand {
+    List,
+    Create(CreateCourse),
+    Delete { id: i32 },
+}
+
+#[derive(Args)]
+struct CreateCourse {
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    number: String,
+    #[arg(long)]
+    teacher_id: i32,
+    #[arg(long)]
+    credits: i32,
+    #[arg(long)]
+    timeslots: String,
+    #[arg(long, default_value = "")]
+    description: String,
+    /// Seats per term, 0 for no limit
+    #[arg(long, default_value_t = 0)]
+    capacity: i32,
+}
+
+#[derive(Subcommand)]
+enum TermsCommand {
+    List,
+    /// Show the term running today
+    Current,
+    /// Add a term; dates are YYYY-MM-DD
+    Create {
+        name: String,
+        #[arg(long)]
+        starts: String,
+        #[arg(long)]
+        ends: String,
+    },
+    Delete { id: i32 },
+    /// Decide every enrolled student's standing from the term's grades
+    Close { id: i32 },
+}
+
+// Unset options take the defaults of `SeedPlan`
+#[derive(Args)]
+struct SeedArgs {
+    /// The same seed and sizes always generate the same data [default: 1]
+    #[arg(long)]
+    seed: Option<u64>,
+    /// [default: 8]
+    #[arg(long)]
+    departments: Option<usize>,
+    /// [default: 40]
+    #[arg(long)]
+    teachers: Option<usize>,
+    /// [default: 160]
+    #[arg(long)]
+    courses: Option<usize>,
+    /// [default: 1000]
+    #[arg(long)]
+    students: Option<usize>,
+    /// Terms of enrollments; the last one is in progress [default: 4]
+    #[arg(long)]
+    terms: Option<usize>,
+    /// [default: 4]
+    #[arg(long)]
+    courses_per_term: Option<usize>,
+    /// Year of the first fall term [default: 2024]
+    #[arg(long)]
+    first_year: Option<i32>,
+    /// Password of every generated account [default: Seed-Passw0rd!]
+    #[arg(long)]
+    password: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Create any missing tables and triggers
+    Init,
+    /// Run the database's integrity check
+    Check,
+    /// Compact the database file and refresh query statistics
+    Vacuum,
+}
+
+#[derive(Subcommand)]
+enum BackupCommand {
+    /// Snapshot the database, then prune by the retention policy
+    Create,
+    /// List snapshots, newest first
+    List,
+    /// Run the integrity check on a snapshot and compare its schema version
+    Verify { name: String },
+    /// Replace the database with a snapshot; the current state is snapshotted first
+    Restore { name: String },
+    /// Delete the snapshots the retention policy no longer keeps
+    Prune,
+}
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    fn table<T: serde::Serialize>(&self, items: &[T], headers: &[&str], row: impl Fn(&T) -> Vec<String>) {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(items).unwrap_or_default());
+            return;
+        }
+
+        let rows: Vec<Vec<String>> = items.iter().map(row).collect();
+        let widths: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                rows.iter()
+                    .map(|r| r[i].chars().count())
+                    .chain([h.len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let line = |cells: Vec<String>| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(c, w)| format!("{:<width$}", c, width = w))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_owned()
+        };
+
+        println!("{}", line(headers.iter().map(|h| h.to_uppercase()).collect()));
+        for r in rows {
+            println!("{}", line(r));
+        }
+    }
+
+    fn value<T: serde::Serialize>(&self, value: &T, human: impl FnOnce(&T)) {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+        } else {
+            human(value);
+        }
+    }
+
+    fn done(&self, message: &str) {
+        if self.json {
+            println!("{}", json!({"message": message}));
+        } else {
+            println!("{}", message);
+        }
+    }
+
+    fn error(&self, e: &anyhow::Error) {
+        let fields = e.downcast_ref::<ValidationErrors>().map(|v| &v.errors);
+
+        if self.json {
+            eprintln!("{}", json!({"error": redacted(e), "fields": fields}));
+            return;
+        }
+
+        match fields {
+            Some(fields) => {
+                eprintln!("error: validation failed");
+                for f in fields {
+                    eprintln!("  {}: {}", f.field, f.message);
+                }
+            }
+            None => eprintln!("error: {}", redacted(e)),
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let out = Output { json: cli.json };
+
+    let result = Settings::load()
+        .and_then(|settings| run(cli.command, ServerConnection::system(Arc::new(settings)), &out));
+
+    if let Err(e) = result {
+        out.error(&e);
+        exit(1);
+    }
+}
+
+fn run(command: Command, mut conn: ServerConnection, out: &Output) -> Result<()> {
+    match command {
+        Command::Users(c) => users(c, &mut conn, out),
+        Command::Departments(c) => departments(c, &mut conn, out),
+        Command::Courses(c) => courses(c, &mut conn, out),
+        Command::Terms(c) => terms(c, &mut conn, out),
+        Command::Import {
+            kind,
+            file,
+            dry_run,
+        } => import(kind, file, dry_run, &mut conn, out),
+        Command::Export {
+            table,
+            format,
+            output,
+            filter,
+        } => export(&table, format, output, filter, &conn),
+        Command::Stats => {
+            let stats = conn.generate_statistics()?;
+            out.value(&stats, |s| {
+                println!("Registered users:   {}", s.registered_users);
+                println!("Suspended users:    {}", s.suspended_users);
+                println!("Faculty members:    {}", s.faculty_members);
+                println!("Active students:    {}", s.active_students);
+                println!("Graduated students: {}", s.graduated_students);
+                println!("Courses:            {}", s.courses);
+                println!("Departments:        {}", s.departments);
+            });
+            Ok(())
+        }
+        Command::Seed(args) => {
+            let defaults = SeedPlan::default();
+            let plan = SeedPlan {
+                seed: args.seed.unwrap_or(defaults.seed),
+                departments: args.departments.unwrap_or(defaults.departments),
+                teachers: args.teachers.unwrap_or(defaults.teachers),
+                courses: args.courses.unwrap_or(defaults.courses),
+                students: args.students.unwrap_or(defaults.students),
+                terms: args.terms.unwrap_or(defaults.terms),
+                courses_per_term: args.courses_per_term.unwrap_or(defaults.courses_per_term),
+                first_year: args.first_year.unwrap_or(defaults.first_year),
+                password: args.password.unwrap_or(defaults.password),
+            };
+
+            let report = conn.seed(&plan)?;
+            out.value(&report, |r| {
+                println!("Departments:  {}", r.departments);
+                println!("Teachers:     {}", r.teachers);
+                println!("Courses:      {}", r.courses);
+                println!("Terms:        {}", r.terms);
+                println!("Students:     {}", r.students);
+                println!("Enrollments:  {}", r.enrollments);
+            });
+            Ok(())
+        }
+        Command::Db(c) => db(c, &mut conn, out),
+        Command::Backup(c) => backup(c, &mut conn, out),
+    }
+}
+
+fn users(command: UsersCommand, conn: &mut ServerConnection, out: &Output) -> Result<()> {
+    match command {
+        UsersCommand::List { role } => {
+            let users = match role {
+                Some(r) => conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Role(r))])?,
+                None => conn.get_users()?,
+            };
+            let users: Vec<AdminUserView> = users.iter().map(AdminUserView::from).collect();
+
+            out.table(
+                &users,
+                &["id", "username", "email", "role", "verified", "suspended"],
+                |u| {
+                    vec![
+                        u.id.to_string(),
+                        u.username.to_owned(),
+                        u.email.to_owned(),
+                        u.role.to_owned(),
+                        u.verified.to_string(),
+                        u.suspended.to_string(),
+                    ]
+                },
+            );
+        }
+        UsersCommand::Create(u) => {
+            check_role(&u.role)?;
+
+            conn.register_user(User {
+                id: 0,
+                username: u.username,
+                password: u.password,
+                email: u.email,
+                phone: u.phone,
+                verified: !u.unverified,
+                suspended: false,
+                forcenewpw: false,
+                role: u.role,
+            })?;
+            out.done("User created.");
+        }
+        UsersCommand::Suspend { id } => {
+            let mut user = find_user(conn, id)?;
+            user.suspended = true;
+            conn.update_user(user)?;
+            out.done("User suspended.");
+        }
+        UsersCommand::Unsuspend { id } => {
+            let mut user = find_user(conn, id)?;
+            user.suspended = false;
+            conn.update_user(user)?;
+            out.done("User unsuspended.");
+        }
+        UsersCommand::SetRole { id, role } => {
+            check_role(&role)?;
+
+            let mut user = find_user(conn, id)?;
+            user.role = role;
+            conn.update_user(user)?;
+            out.done("Role changed.");
+        }
+        UsersCommand::Delete { id } => {
+            let user = find_user(conn, id)?;
+            conn.delete_user(user)?;
+            out.done("User deleted.");
+        }
+    }
+
+    Ok(())
+}
+
+fn departments(command: DepartmentsCommand, conn: &mut ServerConnection, out: &Output) -> Result<()> {
+    match command {
+        DepartmentsCommand::List => {
+            let departments = conn.get_departments()?;
+            out.table(&departments, &["id", "name"], |d| {
+                vec![d.id.to_string(), d.name.to_owned()]
+            });
+        }
+        DepartmentsCommand::Create { name } => {
+            conn.new_department(&name)?;
+            out.done("Department created.");
+        }
+        DepartmentsCommand::Delete { id } => {
+            let department: Departments = conn.get_department(id)?;
+            conn.remove_department(department)?;
+            out.done("Department deleted.");
+        }
+    }
+
+    Ok(())
+}
+
+fn courses(command: CoursesCommand, conn: &mut ServerConnection, out: &Output) -> Result<()> {
+    match command {
+        CoursesCommand::List => {
+            let courses = conn.search_courses(String::new())?;
+            out.table(
+                &courses,
+                &["id", "course_nr", "course", "teacher_id", "cr_cost", "timeslots", "capacity"],
+                |c| {
+                    vec![
+                        c.id.to_string(),
+                        c.course_nr.to_owned(),
+                        c.course.to_owned(),
+                        c.teacher_id.to_string(),
+                        c.cr_cost.to_string(),
+                        c.timeslots.to_owned(),
+                        c.capacity.to_string(),
+                    ]
+                },
+            );
+        }
+        CoursesCommand::Create(c) => {
+            conn.register_courses(vec![Courses {
+                id: 0,
+                teacher_id: c.teacher_id,
+                course: c.name,
+                course_nr: c.number,
+                description: c.description,
+                cr_cost: c.credits,
+                timeslots: c.timeslots,
+                capacity: c.capacity,
+            }])?;
+            out.done("Course created.");
+        }
+        CoursesCommand::Delete { id } => {
+            let course = conn
+                .search_courses(id.to_string())?
+                .into_iter()
+                .find(|c| c.id == id)
+                .ok_or_else(|| anyhow!("Course not found."))?;
+            conn.remove_courses(vec![course])?;
+            out.done("Course deleted.");
+        }
+    }
+
+    Ok(())
+}
+
+fn terms(command: TermsCommand, conn: &mut ServerConnection, out: &Output) -> Result<()> {
+    match command {
+        TermsCommand::List => {
+            let terms = conn.get_terms()?;
+            out.table(&terms, &["id", "name", "starts_on", "ends_on"], |t| {
+                vec![
+                    t.id.to_string(),
+                    t.name.to_owned(),
+                    t.starts_on.to_owned(),
+                    t.ends_on.to_owned(),
+                ]
+            });
+        }
+        TermsCommand::Current => {
+            let term = conn.current_term()?;
+            out.value(&term, |t| match t {
+                Some(t) => println!("{} ({} to {})", t.name, t.starts_on, t.ends_on),
+                None => println!("No term is running today."),
+            });
+        }
+        TermsCommand::Create { name, starts, ends } => {
+            conn.new_term(Term {
+                id: 0,
+                name,
+                starts_on: starts,
+                ends_on: ends,
+            })?;
+            out.done("Term created.");
+        }
+        TermsCommand::Delete { id } => {
+            conn.remove_term(id)?;
+            out.done("Term deleted.");
+        }
+        TermsCommand::Close { id } => {
+            let closing = conn.close_term(id)?;
+            out.value(&closing, |c| {
+                println!("Closed {}: {} students.", c.term, c.students);
+                for d in &c.decisions {
+                    println!(
+                        "  student {}: {} (term GPA {:.2}, cumulative {:.2})",
+                        d.student_id, d.standing, d.term_gpa, d.cumulative_gpa
+                    );
+                }
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn import(
+    kind: ImportKind,
+    file: PathBuf,
+    dry_run: bool,
+    conn: &mut ServerConnection,
+    out: &Output,
+) -> Result<()> {
+    let report = conn.import_csv(kind, File::open(&file)?, dry_run)?;
+
+    out.value(&report, |report| {
+        for row in report.rows.iter().filter(|r| !r.ok) {
+            for e in &row.errors {
+                println!("line {}: {}: {}", row.line, e.field, e.message);
+            }
+        }
+
+        match (report.committed, report.dry_run) {
+            (true, _) => println!("Imported {} rows.", report.total),
+            (false, true) if report.is_clean() => {
+                println!("All {} rows are valid. Nothing was written (dry run).", report.total)
+            }
+            _ => println!(
+                "{} of {} rows failed. Nothing was written.",
+                report.failed, report.total
+            ),
+        }
+    });
+
+    if !report.is_clean() {
+        return Err(anyhow!("Import failed validation."));
+    }
+
+    Ok(())
+}
+
+fn export(
+    table: &str,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+    filter: Vec<String>,
+    conn: &ServerConnection,
+) -> Result<()> {
+    let filters = filter
+        .iter()
+        .map(|f| {
+            f.split_once('=')
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .ok_or_else(|| anyhow!("Filters are written as COLUMN=VALUE, got '{}'.", f))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let request = ExportRequest::parse(table, format, &filters)?;
+
+    let count = match output {
+        Some(path) => conn.export(&request, BufWriter::new(File::create(path)?))?,
+        None => conn.export(&request, io::stdout().lock())?,
+    };
+
+    eprintln!("Exported {} rows.", count);
+    Ok(())
+}
+
+fn db(command: DbCommand, conn: &mut ServerConnection, out: &Output) -> Result<()> {
+    match command {
+        // Opening the connection already created the schema
+        DbCommand::Init => out.done("Database is up to date."),
+        DbCommand::Check => {
+            let problems = conn.check_database()?;
+            let healthy = problems == ["ok"];
+
+            out.value(&json!({"ok": healthy, "problems": problems}), |_| {
+                if healthy {
+                    println!("Database is healthy.");
+                } else {
+                    problems.iter().for_each(|p| println!("{}", p));
+                }
+            });
+
+            if !healthy {
+                return Err(anyhow!("Integrity check failed."));
+            }
+        }
+        DbCommand::Vacuum => {
+            conn.vacuum_database()?;
+            out.done("Database compacted.");
+        }
+    }
+
+    Ok(())
+}
+
+fn backup(command: BackupCommand, conn: &mut ServerConnection, out: &Output) -> Result<()> {
+    match command {
+        BackupCommand::Create => {
+            let snapshot = conn.take_snapshot()?;
+            out.value(&snapshot, |s| println!("Saved {} ({} bytes).", s.name, s.size_bytes));
+        }
+        BackupCommand::List => {
+            let snapshots = conn.list_snapshots()?;
+            out.table(&snapshots, &["name", "taken at", "bytes"], |s| {
+                vec![s.name.clone(), s.taken_at.clone(), s.size_bytes.to_string()]
+            });
+        }
+        BackupCommand::Verify { name } => {
+            let verification = conn.verify_snapshot(&name)?;
+            out.value(&verification, |v| {
+                if v.ok {
+                    println!("{} is intact (schema version {}).", v.name, v.schema_version);
+                } else {
+                    v.problems.iter().for_each(|p| println!("{}", p));
+                }
+            });
+
+            if !verification.ok {
+                return Err(anyhow!("Snapshot {} failed verification.", name));
+            }
+        }
+        BackupCommand::Restore { name } => {
+            let previous = conn.restore_snapshot(&name)?;
+            out.done(&format!(
+                "Restored {}. The previous state was saved as {}.",
+                name, previous.name
+            ));
+        }
+        BackupCommand::Prune => {
+            let removed = conn.prune_snapshots()?;
+            out.value(&removed, |r| println!("Removed {} snapshots.", r.len()));
+        }
+    }
+
+    Ok(())
+}
+
+fn find_user(conn: &ServerConnection, id: i32) -> Result<User> {
+    conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Id(id))])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("User not found."))
+}
+
+fn check_role(role: &str) -> Result<()> {
+    match role {
+        "admin" | "teacher" | "student" => Ok(()),
+        _ => Err(anyhow!("Unknown role '{}' (expected admin, teacher or student).", role)),
+    }
+}
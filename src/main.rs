@@ -1,43 +1,67 @@
 use actix_cors::Cors;
-use actix_web::{App, HttpServer};
-use backend::rest_api::*;
-
-mod backend;
+use actix_web::{web, App, HttpServer};
+use student_sys::backend::auth::RequireSession;
+use student_sys::backend::backup;
+use student_sys::backend::logging::{self, RequestTracing};
+use student_sys::backend::monitoring::{self, RequestMetrics};
+use student_sys::backend::rest_api::*;
+use student_sys::backend::settings::Settings;
+use student_sys::backend::versioning::{Deprecated, V1};
 
 extern crate actix_web;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let http_server = HttpServer::new(|| {
+    let settings = match Settings::load() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    logging::init(&settings.log);
+
+    let server = settings.server.clone();
+    let (bind, port, workers) = (server.bind.clone(), server.port, server.workers);
+    let settings = web::Data::new(settings);
+    backup::schedule(settings.clone().into_inner());
+
+    let mut http_server = HttpServer::new(move || {
         App::new()
-            .wrap(Cors::permissive())
-            .service(index)
-            .service(get_users)
-            .service(get_students)
-            .service(get_teachers)
-            .service(get_departments)
-            .service(get_department)
-            .service(new_department)
-            .service(invite_to_department)
-            .service(kick_from_department)
-            .service(get_courses)
-            .service(get_course)
-            .service(new_course)
-            .service(update_course)
-            .service(remove_course)
-            .service(update_user)
-            .service(delete_user)
-            .service(get_self)
-            .service(update_self)
-            .service(admin)
-            .service(enroll)
-            .service(unenroll)
-            .service(login)
-            .service(logout)
-            .service(register)
-            .service(register_admin)
+            .wrap(RequireSession)
+            .wrap(cors(&server.cors_origins))
+            .wrap(RequestMetrics)
+            .wrap(RequestTracing)
+            .app_data(settings.clone())
+            .configure(monitoring::routes)
+            .service(web::scope(V1).configure(routes))
+            // The unversioned routes from before /api/v1, kept working until the sunset date
+            .service(
+                web::scope("")
+                    .wrap(Deprecated::new(&server.legacy_sunset))
+                    .configure(routes),
+            )
     })
-    .bind(("127.0.0.1", 8080))?;
+    .bind((bind.as_str(), port))?;
+
+    if let Some(workers) = workers {
+        http_server = http_server.workers(workers);
+    }
+
+    tracing::info!(%bind, port, "listening");
 
     http_server.run().await
 }
+
+fn cors(origins: &[String]) -> Cors {
+    if origins.iter().any(|o| o == "*") {
+        return Cors::permissive();
+    }
+
+    origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allow_any_method()
+        .allow_any_header()
+        .expose_any_header()
+}
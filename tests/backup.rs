@@ -0,0 +1,152 @@
+// Snapshots: which ones retention keeps, and which ones may be restored.
+
+#[macro_use]
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::test::call_service;
+
+use common::{reply, request, Fixture, PASSWORD};
+use student_sys::backend::backup;
+use student_sys::backend::filter::{Filter, UsersFilter};
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::BackupSettings;
+use student_sys::backend::storage::SCHEMA_VERSION;
+use student_sys::backend::table_models::User;
+
+fn names(dir: &Path) -> Vec<String> {
+    backup::list(dir).unwrap().into_iter().map(|s| s.name).collect()
+}
+
+fn users_named(fixture: &Fixture, email: &str) -> usize {
+    ServerConnection::system(Arc::new(fixture.settings.clone()))
+        .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(email.to_owned()))])
+        .unwrap()
+        .len()
+}
+
+#[test]
+fn pruning_keeps_the_newest_snapshot_of_each_period() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // Newest first. 2026-10-18 is a Sunday, so the 16th to the 18th share a week.
+    let taken = [
+        "20261018T123000000Z", // newest, and newest of its hour, day and week
+        "20261018T121000000Z",
+        "20261018T115000000Z", // newest of the second hour
+        "20261018T100000000Z",
+        "20261017T230000000Z", // newest of the second day
+        "20261017T080000000Z",
+        "20261016T090000000Z",
+        "20261005T090000000Z", // newest of the second week
+        "20260901T090000000Z",
+    ];
+    for stamp in taken {
+        std::fs::write(dir.path().join(format!("snapshot-{}.db", stamp)), b"").unwrap();
+    }
+    std::fs::write(dir.path().join("notes.txt"), b"not a snapshot").unwrap();
+
+    let policy = BackupSettings {
+        keep_hourly: 2,
+        keep_daily: 2,
+        keep_weekly: 2,
+        ..BackupSettings::default()
+    };
+    let removed = backup::prune(dir.path(), &policy).unwrap();
+
+    let kept: Vec<String> = [taken[0], taken[2], taken[4], taken[7]]
+        .iter()
+        .map(|stamp| format!("snapshot-{}.db", stamp))
+        .collect();
+    assert_eq!(names(dir.path()), kept);
+    assert_eq!(removed.len(), 5);
+    assert!(dir.path().join("notes.txt").exists());
+}
+
+#[actix_web::test]
+async fn a_snapshot_from_another_schema_version_is_not_restored() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let database = &fixture.settings.database.path;
+    let dir = fixture.settings.backup_dir();
+
+    let old = backup::take(database, &dir).unwrap();
+    rusqlite::Connection::open(dir.join(&old.name))
+        .unwrap()
+        .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION - 1))
+        .unwrap();
+
+    let verification = backup::verify(&dir, &old.name).unwrap();
+    assert!(!verification.ok);
+    assert_eq!(verification.schema_version, SCHEMA_VERSION - 1);
+    assert!(verification.problems[0].contains("schema is at version"));
+
+    let (status, body) = reply(
+        call_service(
+            &app,
+            request("POST", &format!("/admin/backups/{}/restore", old.name), &admin).to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("cannot be restored"));
+
+    // Refused before anything was touched, including the safety snapshot
+    assert_eq!(names(&dir), [old.name]);
+}
+
+#[actix_web::test]
+async fn restoring_keeps_the_state_it_replaces() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let database = &fixture.settings.database.path;
+    let dir = fixture.settings.backup_dir();
+
+    let before = backup::take(database, &dir).unwrap();
+    assert!(backup::verify(&dir, &before.name).unwrap().ok);
+
+    ServerConnection::system(Arc::new(fixture.settings.clone()))
+        .register_user(User {
+            id: 0,
+            username: String::from("Late"),
+            password: String::from(PASSWORD),
+            email: String::from("late@aubg.edu"),
+            phone: String::new(),
+            verified: true,
+            suspended: false,
+            forcenewpw: false,
+            role: String::from("student"),
+        })
+        .unwrap();
+    assert_eq!(users_named(&fixture, "late@aubg.edu"), 1);
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("POST", &format!("/admin/backups/{}/restore", before.name), &admin).to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(users_named(&fixture, "late@aubg.edu"), 0);
+
+    // The replaced state was saved first and holds the account
+    let saved = backup::list(&dir).unwrap().remove(0);
+    assert_ne!(saved.name, before.name);
+    let count: i64 = rusqlite::Connection::open(dir.join(&saved.name))
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM \"USERS\" WHERE \"email\" = 'late@aubg.edu'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+}
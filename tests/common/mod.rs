@@ -0,0 +1,187 @@
+// Shared by the end-to-end tests: settings for a throwaway database seeded with an admin,
+// two teachers, a student, a department and a course, and the app built as in main.rs.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::StatusCode;
+use actix_web::test::{read_body, TestRequest};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use student_sys::backend::filter::{Filter, UsersFilter};
+use student_sys::backend::mailer::{Email, SqliteOutbox};
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::Settings;
+use student_sys::backend::table_models::{Courses, Departments, TeacherAccount, User};
+
+pub const PASSWORD: &str = "Passw0rd!";
+pub const SUNSET: &str = "Thu, 01 Jul 2027 00:00:00 GMT";
+
+pub struct Fixture {
+    pub settings: Settings,
+    pub admin: User,
+    pub teacher: User,
+    pub other_teacher: User,
+    pub student: User,
+    pub department: Departments,
+    // Taught by `teacher`
+    pub course: Courses,
+    _dir: TempDir,
+}
+
+impl Fixture {
+    pub fn seed() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut settings = Settings::default();
+        settings.database.path = dir.path().join("test.db");
+        settings.auth.token_secret = b"test secret".to_vec();
+        settings.server.legacy_sunset = String::from(SUNSET);
+        // The cheapest hashes argon2 accepts; these passwords guard nothing
+        settings.auth.hashing.memory_kib = 8;
+        settings.auth.hashing.iterations = 1;
+        settings.auth.hashing.parallelism = 1;
+
+        let mut conn = ServerConnection::system(Arc::new(settings.clone()));
+
+        let admin = register(&mut conn, "Admin", "admin@aubg.edu", "admin");
+        let teacher = register(&mut conn, "Tess", "tess@aubg.edu", "teacher");
+        let other_teacher = register(&mut conn, "Otto", "otto@aubg.edu", "teacher");
+        let student = register(&mut conn, "Stu", "stu@aubg.edu", "student");
+
+        conn.new_department("Computer Science").unwrap();
+        let department = conn.get_departments().unwrap().pop().unwrap();
+
+        for account in conn.get_teacher_accounts().unwrap() {
+            conn.update_teacher_account(TeacherAccount {
+                dept_id: department.id,
+                ..account
+            })
+            .unwrap();
+        }
+
+        conn.register_courses(vec![Courses {
+            id: 0,
+            teacher_id: teacher.id,
+            course: String::from("Intro to Programming"),
+            course_nr: String::from("COS 101"),
+            description: String::from("Variables, loops and functions."),
+            cr_cost: 3,
+            timeslots: String::from("MW 10:00"),
+            capacity: 0,
+        }])
+        .unwrap();
+        let course = conn.search_courses(String::new()).unwrap().pop().unwrap();
+
+        Self {
+            settings,
+            admin,
+            teacher,
+            other_teacher,
+            student,
+            department,
+            course,
+            _dir: dir,
+        }
+    }
+
+    // A session token for `email`, signed in without going through HTTP
+    pub fn session(&self, email: &str) -> String {
+        let mut conn = ServerConnection::new(Arc::new(self.settings.clone()));
+        conn.login(email.to_owned(), PASSWORD.to_owned(), None)
+            .unwrap();
+
+        conn.create_session().unwrap()
+    }
+
+    // Everything mailed to `email` so far, oldest first
+    pub fn outbox(&self, email: &str) -> Vec<Email> {
+        SqliteOutbox::open(&self.settings.outbox_path())
+            .unwrap()
+            .messages_for(email)
+            .unwrap()
+    }
+}
+
+// The token or link on a line of its own in a mailed message
+pub fn token_in(email: &Email) -> String {
+    let line = email
+        .body
+        .lines()
+        .find(|l| !l.is_empty() && !l.contains(' '))
+        .unwrap();
+
+    line.rsplit("token=").next().unwrap().to_owned()
+}
+
+// Every service and middleware from main.rs except CORS, on the fixture's database
+macro_rules! app {
+    ($fixture:expr) => {
+        actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(student_sys::backend::auth::RequireSession)
+                .wrap(student_sys::backend::monitoring::RequestMetrics)
+                .wrap(student_sys::backend::logging::RequestTracing)
+                .app_data(actix_web::web::Data::new($fixture.settings.clone()))
+                .configure(student_sys::backend::monitoring::routes)
+                .service(
+                    actix_web::web::scope(student_sys::backend::versioning::V1)
+                        .configure(student_sys::backend::rest_api::routes),
+                )
+                .service(
+                    actix_web::web::scope("")
+                        .wrap(student_sys::backend::versioning::Deprecated::new(
+                            &$fixture.settings.server.legacy_sunset,
+                        ))
+                        .configure(student_sys::backend::rest_api::routes),
+                ),
+        )
+        .await
+    };
+}
+
+// A request to `/api/v1{path}` carrying `token` as its session
+pub fn request(method: &str, path: &str, token: &str) -> TestRequest {
+    let req = match method {
+        "GET" => TestRequest::get(),
+        "POST" => TestRequest::post(),
+        "PATCH" => TestRequest::patch(),
+        "DELETE" => TestRequest::delete(),
+        other => panic!("unsupported method {}", other),
+    };
+
+    req.uri(&format!("/api/v1{}", path))
+        .insert_header(("session_token", token))
+}
+
+// The status and the JSON body; `null` when the body is empty or not JSON
+pub async fn reply<B: MessageBody>(res: ServiceResponse<B>) -> (StatusCode, Value) {
+    let status = res.status();
+    let body = read_body(res).await;
+
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn register(conn: &mut ServerConnection, username: &str, email: &str, role: &str) -> User {
+    conn.register_user(User {
+        id: 0,
+        username: String::from(username),
+        password: String::from(PASSWORD),
+        email: String::from(email),
+        phone: String::new(),
+        verified: true,
+        suspended: false,
+        forcenewpw: false,
+        role: String::from(role),
+    })
+    .unwrap();
+
+    conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Email(String::from(email)))])
+        .unwrap()
+        .pop()
+        .unwrap()
+}
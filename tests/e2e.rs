@@ -0,0 +1,427 @@
+// The main flows through the whole app, on a fresh seeded database per test.
+
+#[macro_use]
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+
+use common::{reply, request, Fixture, PASSWORD};
+use student_sys::backend::db_driver::{DbDriver, ReceiverType};
+use student_sys::backend::error::ApiError;
+use student_sys::backend::filter::{Filter, StudentAccountFilter};
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::Settings;
+use student_sys::backend::table_models::{StudentAccount, StudentCourse, Table, User};
+
+#[actix_web::test]
+async fn registered_users_can_sign_in() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let register = || {
+        TestRequest::post()
+            .uri("/api/v1/register")
+            .insert_header(("username", "Nia"))
+            .insert_header(("password", PASSWORD))
+            .insert_header(("email", "nia@aubg.edu"))
+            .to_request()
+    };
+
+    let (status, _) = reply(call_service(&app, register()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The address is taken now
+    let (status, body) = reply(call_service(&app, register()).await).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["details"][0]["field"], "email");
+
+    let res = call_service(
+        &app,
+        TestRequest::post()
+            .uri("/api/v1/login")
+            .insert_header(("login_email", "nia@aubg.edu"))
+            .insert_header(("login_password", PASSWORD))
+            .to_request(),
+    )
+    .await;
+    let token = res.headers().get("session_token").unwrap().to_str().unwrap().to_owned();
+    let (status, body) = reply(res).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["role"], "student");
+
+    let (status, body) = reply(call_service(&app, request("GET", "/account", &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["user"]["email"], "nia@aubg.edu");
+    assert_eq!(body["enrollments"], serde_json::json!([]));
+}
+
+#[actix_web::test]
+async fn sessions_need_the_right_password_and_end_on_logout() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let res = call_service(
+        &app,
+        TestRequest::post()
+            .uri("/api/v1/login")
+            .insert_header(("login_email", fixture.student.email.as_str()))
+            .insert_header(("login_password", "wrong password"))
+            .to_request(),
+    )
+    .await;
+    assert!(res.headers().get("session_token").is_none());
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let (status, _) = reply(call_service(&app, TestRequest::get().uri("/api/v1/account").to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let token = fixture.session(&fixture.student.email);
+    let (status, _) = reply(call_service(&app, request("GET", "/account", &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = reply(call_service(&app, request("GET", "/logout", &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = reply(call_service(&app, request("GET", "/account", &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn students_enroll_and_drop() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let token = fixture.session(&fixture.student.email);
+    let enroll = format!("/enroll/{}", fixture.course.id);
+    let unenroll = format!("/unenroll/{}", fixture.course.id);
+
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Asking again doesn't take a second seat
+    let (status, body) = reply(call_service(&app, request("POST", &enroll, &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"], "You are already enrolled in COS 101.");
+
+    let (_, account) = reply(call_service(&app, request("GET", "/account", &token).to_request()).await).await;
+    assert_eq!(account["enrollments"].as_array().unwrap().len(), 1);
+    assert_eq!(account["enrollments"][0]["course_id"], fixture.course.id);
+    assert_eq!(account["enrollments"][0]["grade"], -1.0);
+    assert_eq!(account["courses"][0]["course_nr"], "COS 101");
+
+    let (status, _) = reply(call_service(&app, request("POST", &unenroll, &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, account) = reply(call_service(&app, request("GET", "/account", &token).to_request()).await).await;
+    assert_eq!(account["enrollments"], serde_json::json!([]));
+    assert_eq!(account["courses"], serde_json::json!([]));
+
+    // Teachers don't take courses
+    let teacher = fixture.session(&fixture.teacher.email);
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &teacher).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn records_keep_their_students_teachers_and_courses() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let student = fixture.session(&fixture.student.email);
+    let admin = fixture.session(&fixture.admin.email);
+    let enroll = format!("/enroll/{}", fixture.course.id);
+    let unenroll = format!("/unenroll/{}", fixture.course.id);
+    let remove_student = format!("/admin/users/{}", fixture.student.id);
+    let remove_teacher = format!("/admin/users/{}", fixture.teacher.id);
+    let remove_course = format!("/courses/{}", fixture.course.id);
+
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for path in [&remove_student, &remove_teacher, &remove_course] {
+        let (status, body) = reply(call_service(&app, request("DELETE", path, &admin).to_request()).await).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}", path);
+        assert_eq!(body["code"], "conflict");
+    }
+
+    let (status, _) = reply(call_service(&app, request("POST", &unenroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for path in [&remove_student, &remove_course, &remove_teacher] {
+        let (status, _) = reply(call_service(&app, request("DELETE", path, &admin).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK, "{}", path);
+    }
+}
+
+#[actix_web::test]
+async fn admins_create_update_and_remove_courses() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let token = fixture.session(&fixture.admin.email);
+
+    let course = |method, path: &str, name| {
+        request(method, path, &token)
+            .insert_header(("name", name))
+            .insert_header(("description", "Graphs and trees."))
+            .insert_header(("course_nr", "COS 220"))
+            .insert_header(("id", fixture.teacher.id.to_string()))
+            .insert_header(("cr_cost", "4"))
+            .insert_header(("timeslots", "TR 13:00"))
+            .to_request()
+    };
+
+    let (status, _) = reply(call_service(&app, course("POST", "/courses", "Data Structures")).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, listing) = reply(call_service(&app, request("GET", "/courses", &token).to_request()).await).await;
+    let created = listing["courses"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["course_nr"] == "COS 220")
+        .unwrap();
+    let path = format!("/courses/{}", created["id"]);
+
+    let (status, body) = reply(call_service(&app, request("GET", &path, &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["course"]["course"], "Data Structures");
+    assert_eq!(body["course"]["cr_cost"], 4);
+    assert_eq!(body["user"]["email"], fixture.teacher.email);
+    assert_eq!(body["department"]["name"], fixture.department.name);
+
+    let (status, _) = reply(call_service(&app, course("PATCH", &path, "Algorithms")).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = reply(call_service(&app, request("GET", &path, &token).to_request()).await).await;
+    assert_eq!(body["course"]["course"], "Algorithms");
+
+    let (status, _) = reply(call_service(&app, request("DELETE", &path, &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = reply(call_service(&app, request("GET", &path, &token).to_request()).await).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn teachers_only_manage_their_own_courses() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let own = fixture.session(&fixture.teacher.email);
+    let other = fixture.session(&fixture.other_teacher.email);
+    let path = format!("/courses/{}", fixture.course.id);
+
+    let (status, _) = reply(call_service(&app, request("DELETE", &path, &other).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let new_course = |token: &str| {
+        request("POST", "/courses", token)
+            .insert_header(("name", "Compilers"))
+            .insert_header(("description", "Parsing and code generation."))
+            .insert_header(("course_nr", "COS 440"))
+            .insert_header(("id", fixture.teacher.id.to_string()))
+            .insert_header(("cr_cost", "3"))
+            .insert_header(("timeslots", "MW 14:00"))
+            .to_request()
+    };
+
+    let (status, _) = reply(call_service(&app, new_course(&other)).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = reply(call_service(&app, new_course(&own)).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = reply(call_service(&app, request("DELETE", &path, &own).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Students can't touch courses at all
+    let student = fixture.session(&fixture.student.email);
+    let (status, _) = reply(call_service(&app, request("DELETE", "/courses/2", &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn course_details_count_seats_and_show_the_roster_to_staff() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let student = fixture.session(&fixture.student.email);
+    let path = format!("/courses/{}", fixture.course.id);
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("PATCH", &path, &admin)
+                .insert_header(("name", fixture.course.course.as_str()))
+                .insert_header(("description", fixture.course.description.as_str()))
+                .insert_header(("course_nr", fixture.course.course_nr.as_str()))
+                .insert_header(("id", fixture.teacher.id.to_string()))
+                .insert_header(("cr_cost", fixture.course.cr_cost.to_string()))
+                .insert_header(("timeslots", fixture.course.timeslots.as_str()))
+                .insert_header(("capacity", "1"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let enroll = format!("/enroll/{}", fixture.course.id);
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Students see how full it is, not who is in it
+    let (status, body) = reply(call_service(&app, request("GET", &path, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["user"]["email"], fixture.teacher.email);
+    assert_eq!(body["department"]["name"], fixture.department.name);
+    assert_eq!(body["enrolled"], 1);
+    assert_eq!(body["remaining_seats"], 0);
+    assert!(body.get("roster").is_none());
+
+    let teacher = fixture.session(&fixture.teacher.email);
+    let (_, body) = reply(call_service(&app, request("GET", &path, &teacher).to_request()).await).await;
+    assert_eq!(body["roster"][0]["student"]["email"], fixture.student.email);
+    assert_eq!(body["roster"][0]["grade"], -1.0);
+
+    let other = fixture.session(&fixture.other_teacher.email);
+    let (_, body) = reply(call_service(&app, request("GET", &path, &other).to_request()).await).await;
+    assert!(body.get("roster").is_none());
+
+    // The last seat is gone
+    let (status, _) = reply(
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri("/api/v1/register")
+                .insert_header(("username", "Nia"))
+                .insert_header(("password", PASSWORD))
+                .insert_header(("email", "nia@aubg.edu"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let nia = fixture.session("nia@aubg.edu");
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &nia).to_request()).await).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Ids match exactly
+    let missing = format!("/courses/{}1", fixture.course.id);
+    let (status, _) = reply(call_service(&app, request("GET", &missing, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = reply(call_service(&app, request("GET", "/courses/first", &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn histories_are_for_the_student_their_advisor_and_admins() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let student = fixture.session(&fixture.student.email);
+    let enroll = format!("/enroll/{}", fixture.course.id);
+
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = reply(call_service(&app, request("GET", "/account/history", &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["student_id"], fixture.student.id);
+    assert_eq!(body["terms"][0]["courses"][0]["course_nr"], "COS 101");
+    assert_eq!(body["terms"][0]["courses"][0]["grade"], serde_json::Value::Null);
+    assert_eq!(body["terms"][0]["standing"], "in_progress");
+    assert_eq!(body["cumulative"]["in_progress"], fixture.course.cr_cost);
+
+    let path = format!("/students/{}/history", fixture.student.id);
+    let admin = fixture.session(&fixture.admin.email);
+    let (status, _) = reply(call_service(&app, request("GET", &path, &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Teaching the student's course doesn't make you their advisor
+    let teacher = fixture.session(&fixture.teacher.email);
+    let (status, _) = reply(call_service(&app, request("GET", &path, &teacher).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Nothing assigns advisors yet but the seeder, so straight to the database
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+    let accounts = db
+        .find(
+            Table::StudentAccount,
+            vec![Filter::StudentAccount(StudentAccountFilter::StudentId(fixture.student.id))],
+            None,
+        )
+        .unwrap();
+    let Some(ReceiverType::StudentAccount(account)) = accounts.into_iter().next() else {
+        panic!("the student has no account");
+    };
+    db.update(vec![ReceiverType::StudentAccount(StudentAccount {
+        advisor_id: fixture.teacher.id,
+        ..account
+    })])
+    .unwrap();
+
+    let (status, _) = reply(call_service(&app, request("GET", &path, &teacher).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let admins = format!("/students/{}/history", fixture.admin.id);
+    let (status, _) = reply(call_service(&app, request("GET", &admins, &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn dropping_a_repeated_course_keeps_the_earlier_attempt() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let student = fixture.session(&fixture.student.email);
+
+    // Graded a year ago, before terms were set up
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+    db.insert(vec![ReceiverType::StudentCourse(StudentCourse {
+        student_id: fixture.student.id,
+        course_id: fixture.course.id,
+        grade: 1.0,
+        semester: String::from("Fall 2025"),
+    })])
+    .unwrap();
+
+    let enroll = format!("/enroll/{}", fixture.course.id);
+    let unenroll = format!("/unenroll/{}", fixture.course.id);
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = reply(call_service(&app, request("POST", &unenroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = reply(call_service(&app, request("GET", "/account/history", &student).to_request()).await).await;
+    let terms = body["terms"].as_array().unwrap();
+    assert_eq!(terms.len(), 1);
+    assert_eq!(terms[0]["term"], "Fall 2025");
+    assert_eq!(terms[0]["courses"][0]["grade"], 1.0);
+}
+
+#[test]
+fn in_memory_connections_keep_to_themselves() {
+    let settings = Settings::default();
+
+    let mut first = ServerConnection::in_memory(&settings).unwrap().into_system();
+    first
+        .register_user(User {
+            id: 0,
+            username: String::from("Ada"),
+            password: String::from(PASSWORD),
+            email: String::from("ada@aubg.edu"),
+            phone: String::new(),
+            verified: true,
+            suspended: false,
+            forcenewpw: false,
+            role: String::from("student"),
+        })
+        .unwrap();
+    assert_eq!(first.get_users().unwrap().len(), 1);
+
+    let second = ServerConnection::in_memory(&settings).unwrap();
+    assert!(second.get_users().unwrap().is_empty());
+
+    // There is no file to copy
+    let snapshot = first.take_snapshot().unwrap_err();
+    assert!(matches!(ApiError::from(snapshot), ApiError::BadRequest(_)));
+}
@@ -0,0 +1,101 @@
+// Malformed requests get a coded error body rather than a dropped connection.
+
+#[macro_use]
+mod common;
+
+use actix_web::http::header::HeaderValue;
+use actix_web::http::StatusCode;
+use actix_web::test::call_service;
+
+use common::{reply, request, Fixture};
+use student_sys::backend::db_driver::DbDriver;
+use student_sys::backend::filter::{Filter, StudentAccountFilter};
+use student_sys::backend::table_models::Table;
+
+#[actix_web::test]
+async fn header_values_that_are_not_ascii_are_refused_not_fatal() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let umlaut = HeaderValue::from_bytes("Jürgen".as_bytes()).unwrap();
+    let course = format!("/courses/{}", fixture.course.id);
+    let department = format!("/admin/department/{}", fixture.department.id);
+    let user = format!("/admin/users/{}", fixture.student.id);
+    let course_headers = [
+        ("name", "Algorithms"),
+        ("course_nr", "COS 220"),
+        ("id", "0"),
+        ("cr_cost", "4"),
+        ("timeslots", "TR 13:00"),
+    ];
+
+    let mut requests = vec![
+        ("GET", String::from("/departments/1"), "id"),
+        ("POST", String::from("/departments"), "name"),
+        ("POST", department.clone(), "teacher_id"),
+        ("DELETE", department, "teacher_id"),
+        ("PATCH", user.clone(), "username"),
+        ("PATCH", user.clone(), "verified"),
+        ("PATCH", String::from("/account"), "email"),
+    ];
+    for (header, _) in course_headers {
+        requests.push(("POST", String::from("/courses"), header));
+        requests.push(("PATCH", course.clone(), header));
+    }
+
+    for (method, path, header) in requests {
+        let mut req = request(method, &path, &admin);
+        if path.starts_with("/courses") {
+            for (name, value) in course_headers.into_iter().filter(|(name, _)| *name != header) {
+                req = req.insert_header((name, value));
+            }
+        }
+        let req = req.insert_header((header, umlaut.clone())).to_request();
+
+        let (status, body) = reply(call_service(&app, req).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{} {} {}", method, path, header);
+        assert_eq!(body["code"], "bad_request");
+    }
+
+    // Numbers that don't parse are refused the same way
+    let req = course_headers
+        .iter()
+        .fold(request("PATCH", &course, &admin), |req, (name, value)| {
+            req.insert_header((*name, if *name == "cr_cost" { "four" } else { *value }))
+        })
+        .to_request();
+    let (status, body) = reply(call_service(&app, req).await).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "Invalid course cost.");
+
+    // The description is optional
+    let req = course_headers
+        .iter()
+        .fold(request("POST", "/courses", &admin), |req, (name, value)| {
+            req.insert_header((*name, if *name == "id" { fixture.teacher.id.to_string() } else { value.to_string() }))
+        })
+        .to_request();
+    let (status, _) = reply(call_service(&app, req).await).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn a_student_without_an_account_is_not_found() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let student = fixture.session(&fixture.student.email);
+
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+    let accounts = db
+        .find(
+            Table::StudentAccount,
+            vec![Filter::StudentAccount(StudentAccountFilter::StudentId(fixture.student.id))],
+            None,
+        )
+        .unwrap();
+    db.delete(accounts).unwrap();
+
+    let (status, body) = reply(call_service(&app, request("GET", "/account", &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+}
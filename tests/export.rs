@@ -0,0 +1,84 @@
+// Exports: what each format carries, what never leaves the server, and how filters narrow it.
+
+#[macro_use]
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body};
+use serde_json::Value;
+
+use common::{reply, request, Fixture};
+
+#[actix_web::test]
+async fn exports_leave_the_password_column_out() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+
+    let res = call_service(&app, request("GET", "/admin/export/users?format=csv", &admin).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("content-type").unwrap(), "text/csv; charset=utf-8");
+    let csv = String::from_utf8(read_body(res).await.to_vec()).unwrap();
+    let header: Vec<&str> = csv.lines().next().unwrap().split(',').collect();
+    assert!(header.contains(&"email"), "{:?}", header);
+    assert!(!header.contains(&"password"), "{:?}", header);
+    assert_eq!(csv.lines().count(), 5);
+
+    let res = call_service(&app, request("GET", "/admin/export/users?format=jsonl", &admin).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = read_body(res).await;
+    let rows: Vec<Value> = String::from_utf8_lossy(&body)
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 4);
+    for row in &rows {
+        assert!(row.get("email").is_some());
+        assert!(row.get("password").is_none(), "{}", row);
+    }
+
+    let res = call_service(&app, request("GET", "/admin/export/users?format=xlsx", &admin).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    // A zip archive, which every .xlsx is
+    assert!(read_body(res).await.starts_with(b"PK"));
+
+    // Nor can it be probed through a filter
+    let (status, body) = reply(
+        call_service(&app, request("GET", "/admin/export/users?password=x", &admin).to_request()).await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["details"][0]["code"], "not_filterable");
+}
+
+#[actix_web::test]
+async fn filters_narrow_the_export() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+
+    let res = call_service(
+        &app,
+        request("GET", "/admin/export/users?format=jsonl&role=teacher", &admin).to_request(),
+    )
+    .await;
+    let body = read_body(res).await;
+    let emails: Vec<String> = String::from_utf8_lossy(&body)
+        .lines()
+        .map(|l| serde_json::from_str::<Value>(l).unwrap()["email"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(emails, [fixture.teacher.email.clone(), fixture.other_teacher.email.clone()]);
+
+    let (status, _) = reply(
+        call_service(&app, request("GET", "/admin/export/users?shoe_size=42", &admin).to_request()).await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = reply(call_service(&app, request("GET", "/admin/export/sessions", &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let student = fixture.session(&fixture.student.email);
+    let (status, _) = reply(call_service(&app, request("GET", "/admin/export/users", &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,170 @@
+// How enrollments add up term by term: GPAs, credits, repeats and standing.
+
+use student_sys::backend::history::{self, Standing, StandingRules};
+use student_sys::backend::table_models::*;
+
+fn term(name: &str, starts_on: &str) -> Term {
+    Term {
+        id: 0,
+        name: String::from(name),
+        starts_on: String::from(starts_on),
+        ends_on: String::from(starts_on),
+    }
+}
+
+fn taken(course_id: i32, credits: i32, semester: &str, grade: f32) -> EnrollmentWithCourse {
+    EnrollmentWithCourse {
+        enrollment: StudentCourse {
+            student_id: 1,
+            course_id,
+            grade,
+            semester: String::from(semester),
+        },
+        course: Courses {
+            id: course_id,
+            teacher_id: 2,
+            course: format!("Course {}", course_id),
+            course_nr: format!("COS {}", 100 + course_id),
+            description: String::new(),
+            cr_cost: credits,
+            timeslots: String::new(),
+            capacity: 0,
+        },
+    }
+}
+
+#[test]
+fn terms_come_in_calendar_order() {
+    let terms = [term("Spring 2026", "2026-01-15"), term("Fall 2025", "2025-09-01")];
+    let history = history::build(
+        1,
+        vec![
+            taken(1, 3, "Spring 2026", 3.0),
+            taken(2, 3, "Summer", 4.0),
+            taken(3, 3, "Fall 2025", 2.0),
+        ],
+        &terms,
+        &StandingRules::default(),
+    );
+
+    let names: Vec<&str> = history.terms.iter().map(|t| t.term.as_str()).collect();
+    assert_eq!(names, ["Fall 2025", "Spring 2026", "Summer"]);
+    assert_eq!(history.terms[0].starts_on.as_deref(), Some("2025-09-01"));
+    assert_eq!(history.terms[2].starts_on, None);
+}
+
+#[test]
+fn a_repeat_replaces_the_earlier_grade_in_the_cumulative_totals() {
+    let terms = [term("Fall 2025", "2025-09-01"), term("Spring 2026", "2026-01-15")];
+    let history = history::build(
+        1,
+        vec![
+            taken(1, 3, "Fall 2025", 0.0),
+            taken(2, 4, "Fall 2025", 3.0),
+            taken(1, 3, "Spring 2026", 4.0),
+        ],
+        &terms,
+        &StandingRules::default(),
+    );
+
+    let fall = &history.terms[0];
+    assert_eq!(fall.totals.attempted, 7);
+    assert_eq!(fall.totals.earned, 4);
+    assert!((fall.totals.gpa.unwrap() - 12.0 / 7.0).abs() < 1e-5);
+    assert_eq!(fall.standing, Standing::Probation);
+    assert!(fall.courses[0].repeated && fall.courses[0].replaced);
+    assert!(!fall.courses[1].repeated);
+
+    let spring = &history.terms[1];
+    assert_eq!(spring.totals.gpa, Some(4.0));
+    assert!(spring.courses[0].repeated && !spring.courses[0].replaced);
+    assert_eq!(spring.cumulative.attempted, 7);
+    assert_eq!(spring.cumulative.earned, 7);
+    assert!((spring.cumulative.gpa.unwrap() - 24.0 / 7.0).abs() < 1e-5);
+    assert_eq!(spring.standing, Standing::Good);
+
+    assert_eq!(history.cumulative.earned, 7);
+}
+
+#[test]
+fn courses_in_progress_count_apart() {
+    let terms = [term("Fall 2025", "2025-09-01"), term("Spring 2026", "2026-01-15")];
+    let history = history::build(
+        1,
+        vec![
+            taken(1, 3, "Fall 2025", 2.0),
+            // Retaken now; the old grade stands until the new one is in
+            taken(1, 3, "Spring 2026", -1.0),
+            taken(2, 4, "Spring 2026", -1.0),
+        ],
+        &terms,
+        &StandingRules::default(),
+    );
+
+    assert!(!history.terms[0].courses[0].replaced);
+
+    let spring = &history.terms[1];
+    assert_eq!(spring.courses[0].grade, None);
+    assert_eq!(spring.totals.in_progress, 7);
+    assert_eq!(spring.totals.gpa, None);
+    assert_eq!(spring.standing, Standing::InProgress);
+    assert_eq!(spring.cumulative.attempted, 3);
+    assert_eq!(spring.cumulative.in_progress, 7);
+    assert_eq!(spring.cumulative.gpa, Some(2.0));
+
+    let empty = history::build(1, vec![], &terms, &StandingRules::default());
+    assert!(empty.terms.is_empty());
+    assert_eq!(empty.cumulative.gpa, None);
+}
+
+#[test]
+fn honours_need_a_full_load_and_probation_can_turn_into_suspension() {
+    let terms = [
+        term("Fall 2025", "2025-09-01"),
+        term("Spring 2026", "2026-01-15"),
+        term("Fall 2026", "2026-09-01"),
+    ];
+    let rules = StandingRules::default();
+    let history = history::build(
+        1,
+        vec![
+            taken(1, 12, "Fall 2025", 3.5),
+            taken(2, 3, "Spring 2026", 4.0),
+            taken(3, 4, "Spring 2026", 3.0),
+        ],
+        &terms,
+        &rules,
+    );
+    assert_eq!(history.terms[0].standing, Standing::DeansList);
+    // 3.43 for the term, but over only 7 credits
+    assert_eq!(history.terms[1].standing, Standing::Good);
+
+    let history = history::build(
+        1,
+        vec![
+            taken(1, 3, "Fall 2025", 1.0),
+            taken(2, 3, "Spring 2026", 1.5),
+            taken(3, 3, "Fall 2026", 4.0),
+        ],
+        &terms,
+        &rules,
+    );
+    let standings: Vec<Standing> = history.terms.iter().map(|t| t.standing).collect();
+    assert_eq!(
+        standings,
+        [Standing::Probation, Standing::Suspension, Standing::Good]
+    );
+
+    // With a lower bar for suspension the same grades keep the student on probation
+    let lenient = StandingRules {
+        suspension_below: 1.0,
+        ..StandingRules::default()
+    };
+    let history = history::build(
+        1,
+        vec![taken(1, 3, "Fall 2025", 1.0), taken(2, 3, "Spring 2026", 1.5)],
+        &terms,
+        &lenient,
+    );
+    assert_eq!(history.terms[1].standing, Standing::Probation);
+}
@@ -0,0 +1,161 @@
+// Holds: placed and released by admins, seen by the student, and what they block while in force.
+
+#[macro_use]
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::call_service;
+
+use common::{reply, request, Fixture};
+
+#[actix_web::test]
+async fn holds_block_registration_and_transcripts_until_released() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let student = fixture.session(&fixture.student.email);
+    let place = format!("/admin/students/{}/holds", fixture.student.id);
+    let holds = format!("/students/{}/holds", fixture.student.id);
+    let enroll = format!("/enroll/{}", fixture.course.id);
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("POST", &place, &admin)
+                .insert_header(("kind", "fees"))
+                .insert_header(("reason", "Spring tuition is unpaid."))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"].as_str().unwrap().contains("Spring tuition is unpaid."));
+
+    // A registration hold leaves the transcript alone
+    let (status, _) = reply(call_service(&app, request("GET", "/account/history", &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("POST", &place, &admin)
+                .insert_header(("kind", "documents"))
+                .insert_header(("reason", "Send us your diploma."))
+                .insert_header(("blocks", "transcripts"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = reply(call_service(&app, request("GET", "/account/history", &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let history = format!("/students/{}/history", fixture.student.id);
+    let (status, _) = reply(call_service(&app, request("GET", &history, &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = reply(call_service(&app, request("GET", &holds, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[1]["kind"], "fees");
+    assert_eq!(body[1]["placed_by"], fixture.admin.id);
+    assert_eq!(body[1]["blocks_registration"], true);
+    assert_eq!(body[1]["blocks_transcripts"], false);
+
+    let teacher = fixture.session(&fixture.teacher.email);
+    let (status, _) = reply(call_service(&app, request("GET", &holds, &teacher).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let release = format!("/admin/holds/{}/release", body[1]["id"]);
+    let (status, _) = reply(call_service(&app, request("POST", &release, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = reply(call_service(&app, request("POST", &release, &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = reply(call_service(&app, request("POST", &release, &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = reply(call_service(&app, request("GET", &holds, &student).to_request()).await).await;
+    assert_eq!(body[1]["released_by"], fixture.admin.id);
+    assert_eq!(body[0]["released_at"], 0);
+
+    let (_, body) = reply(call_service(&app, request("GET", "/account/notifications", &student).to_request()).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 3);
+    assert_eq!(body[0]["subject"], "The fees hold on your account was released");
+}
+
+#[actix_web::test]
+async fn holds_need_a_known_kind_a_reason_and_a_student() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let place = format!("/admin/students/{}/holds", fixture.student.id);
+
+    let (status, body) = reply(
+        call_service(
+            &app,
+            request("POST", &place, &admin)
+                .insert_header(("kind", "parking"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<&str> = body["details"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["kind", "reason"]);
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("POST", &place, &admin)
+                .insert_header(("kind", "fees"))
+                .insert_header(("reason", "Unpaid."))
+                .insert_header(("blocks", "everything"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let teacher = format!("/admin/students/{}/holds", fixture.teacher.id);
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("POST", &teacher, &admin)
+                .insert_header(("kind", "fees"))
+                .insert_header(("reason", "Unpaid."))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let student = fixture.session(&fixture.student.email);
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("POST", &place, &student)
+                .insert_header(("kind", "fees"))
+                .insert_header(("reason", "Unpaid."))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
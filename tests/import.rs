@@ -0,0 +1,157 @@
+// CSV imports: what lands in the database is exactly what the file says, all of it or none.
+
+#[macro_use]
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::test::call_service;
+
+use common::{reply, request, Fixture};
+use student_sys::backend::filter::{Filter, UsersFilter};
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::table_models::User;
+
+const USERS: &str = "username,email,password\n\
+                     Ada,ada@aubg.edu,Passw0rd!\n\
+                     Ben,ben@aubg.edu,Passw0rd!\n\
+                     Cy,cy@aubg.edu,Passw0rd!\n";
+
+fn find(fixture: &Fixture, email: &str) -> Vec<User> {
+    let conn = ServerConnection::system(Arc::new(fixture.settings.clone()));
+
+    conn.get_users_by_filters(vec![Filter::Users(UsersFilter::Email(String::from(email)))])
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn quotes_in_imported_fields_are_stored_as_text() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+
+    let csv = "username,email,password\n\
+               Conan O'Brien,conan@aubg.edu,Passw0rd!\n\
+               \"x', 'p', 'evil@aubg.edu', '', 1, 0, 0, 'admin') --\",mallory@aubg.edu,Passw0rd!\n";
+    let (status, body) = reply(
+        call_service(
+            &app,
+            request("POST", "/admin/import/users", &admin)
+                .set_payload(csv)
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["committed"], true);
+
+    assert_eq!(find(&fixture, "conan@aubg.edu")[0].username, "Conan O'Brien");
+
+    let mallory = find(&fixture, "mallory@aubg.edu");
+    assert_eq!(mallory[0].username, "x', 'p', 'evil@aubg.edu', '', 1, 0, 0, 'admin') --");
+    assert_eq!(mallory[0].role, "student");
+    assert!(!mallory[0].verified);
+    assert!(find(&fixture, "evil@aubg.edu").is_empty());
+}
+
+#[actix_web::test]
+async fn a_dry_run_reports_without_writing() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+
+    let (status, body) = reply(
+        call_service(
+            &app,
+            request("POST", "/admin/import/users?dry_run=true", &admin)
+                .set_payload(USERS)
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["dry_run"], true);
+    assert_eq!(body["committed"], false);
+    assert_eq!(body["total"], 3);
+    assert_eq!(body["failed"], 0);
+    assert!(find(&fixture, "ada@aubg.edu").is_empty());
+}
+
+#[actix_web::test]
+async fn every_bad_row_is_reported_and_none_are_written() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+
+    let csv = "username,email,password,role\n\
+               Ada,ada@aubg.edu,Passw0rd!,student\n\
+               Mallory,mallory@example.com,abc,student\n\
+               Ada Again,ada@aubg.edu,Passw0rd!,janitor\n";
+    let (status, body) = reply(
+        call_service(
+            &app,
+            request("POST", "/admin/import/users", &admin)
+                .set_payload(csv)
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["committed"], false);
+    assert_eq!(body["total"], 3);
+    assert_eq!(body["failed"], 2);
+
+    // Lines count the header as line 1
+    let rows = body["rows"].as_array().unwrap();
+    let fields = |line: usize| -> Vec<(String, String)> {
+        let row = rows.iter().find(|r| r["line"] == line).unwrap();
+        row["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["field"].as_str().unwrap().to_owned(), e["code"].as_str().unwrap().to_owned()))
+            .collect()
+    };
+    assert!(fields(2).is_empty());
+    assert!(fields(3).contains(&("email".into(), "domain_not_allowed".into())));
+    assert!(fields(3).contains(&("password".into(), "too_short".into())));
+    assert!(fields(4).contains(&("email".into(), "duplicate".into())));
+    assert!(fields(4).contains(&("role".into(), "invalid".into())));
+
+    assert!(find(&fixture, "ada@aubg.edu").is_empty());
+}
+
+#[actix_web::test]
+async fn a_row_the_database_refuses_undoes_the_rest() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+
+    // Valid as far as the import can tell, refused once it is written
+    rusqlite::Connection::open(&fixture.settings.database.path)
+        .unwrap()
+        .execute_batch(
+            "CREATE TRIGGER refuse_cy BEFORE INSERT ON \"USERS\" WHEN NEW.\"email\" = 'cy@aubg.edu'
+             BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+        )
+        .unwrap();
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("POST", "/admin/import/users", &admin)
+                .set_payload(USERS)
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert!(!status.is_success());
+
+    assert!(find(&fixture, "ada@aubg.edu").is_empty());
+    assert!(find(&fixture, "ben@aubg.edu").is_empty());
+}
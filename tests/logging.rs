@@ -0,0 +1,142 @@
+// What ends up in the logs: request ids, headers, and errors without secrets in them.
+
+#[macro_use]
+mod common;
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::test::{call_service, TestRequest};
+
+use common::{Fixture, PASSWORD};
+use student_sys::backend::error::{redacted, ApiError};
+use student_sys::backend::logging::{self, REQUEST_ID_HEADER};
+
+// Log output kept in memory, for the subscriber of one test
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[test]
+fn database_errors_leave_the_statement_out() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    conn.execute_batch("CREATE TABLE users (name TEXT, password TEXT)").unwrap();
+
+    let failed = conn
+        .execute(
+            "INSERT INTO users VALUES ('O'Brien', '$argon2id$v=19$m=8,t=1,p=1$c2FsdA$aGFzaA')",
+            [],
+        )
+        .unwrap_err();
+    let failed = anyhow::Error::from(failed);
+    assert!(failed.to_string().contains("argon2"));
+
+    assert!(!redacted(&failed).contains("argon2"));
+    assert!(redacted(&failed).starts_with("database error"));
+    assert!(!ApiError::from(failed).to_string().contains("argon2"));
+}
+
+#[test]
+fn secret_headers_are_redacted() {
+    let mut headers = HeaderMap::new();
+    for (name, value) in [
+        ("session_token", "s3ss10n"),
+        ("login_password", PASSWORD),
+        ("totp_code", "123456"),
+        ("authorization", "Bearer abc"),
+        ("id", "4"),
+    ] {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from_static(value));
+    }
+
+    let logged = logging::redacted(&headers);
+    for secret in ["s3ss10n", PASSWORD, "123456", "Bearer"] {
+        assert!(!logged.contains(secret), "{}", logged);
+    }
+    assert!(logged.contains("login_password: [redacted]"));
+    assert!(logged.contains("id: 4"));
+}
+
+#[actix_web::test]
+async fn request_ids_are_kept_or_made_up() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let id = |res: &actix_web::dev::ServiceResponse<_>| {
+        res.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap().to_owned()
+    };
+
+    let res = call_service(
+        &app,
+        TestRequest::get().uri("/healthz").insert_header((REQUEST_ID_HEADER, "edge-7f3a:1")).to_request(),
+    )
+    .await;
+    assert_eq!(id(&res), "edge-7f3a:1");
+
+    // Anything that could forge a log line is replaced
+    let res = call_service(
+        &app,
+        TestRequest::get().uri("/healthz").insert_header((REQUEST_ID_HEADER, "x\" injected=1")).to_request(),
+    )
+    .await;
+    let made_up = id(&res);
+    assert!(uuid::Uuid::parse_str(&made_up).is_ok(), "{}", made_up);
+
+    let res = call_service(&app, TestRequest::get().uri("/healthz").to_request()).await;
+    assert_ne!(id(&res), made_up);
+}
+
+#[actix_web::test]
+async fn log_lines_carry_the_request_id_and_no_secrets() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    call_service(
+        &app,
+        TestRequest::post()
+            .uri("/api/v1/login")
+            .insert_header((REQUEST_ID_HEADER, "trace-me"))
+            .insert_header(("login_email", fixture.student.email.as_str()))
+            .insert_header(("login_password", "Wr0ng-but-secret!"))
+            .to_request(),
+    )
+    .await;
+
+    let log = captured.text();
+    let lines: Vec<serde_json::Value> = log.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert!(lines.iter().any(|l| l["message"] == "request received"), "{}", log);
+    assert!(lines.iter().any(|l| l["message"] == "failed sign-in"), "{}", log);
+    for line in &lines {
+        assert_eq!(line["span"]["request_id"], "trace-me", "{}", line);
+    }
+    assert!(!log.contains("Wr0ng-but-secret!"), "{}", log);
+}
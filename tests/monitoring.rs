@@ -0,0 +1,148 @@
+// Probes and metrics, as a load balancer or Prometheus would see them.
+
+#[macro_use]
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body, TestRequest};
+use serde_json::json;
+
+use common::{reply, request, Fixture, PASSWORD};
+
+// The value of the sample named exactly `series`, labels included
+fn sample(scrape: &str, series: &str) -> Option<f64> {
+    scrape
+        .lines()
+        .find_map(|l| l.strip_prefix(series)?.strip_prefix(' '))
+        .map(|v| v.parse().unwrap())
+}
+
+#[actix_web::test]
+async fn readiness_reports_on_the_schema_without_upgrading_it() {
+    let mut fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let (status, body) = reply(call_service(&app, TestRequest::get().uri("/readyz").to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"status": "ready"}));
+
+    let dir = tempfile::tempdir().unwrap();
+    let empty = dir.path().join("empty.db");
+    fixture.settings.database.path = empty.clone();
+    let app = app!(fixture);
+
+    let (status, body) = reply(call_service(&app, TestRequest::get().uri("/readyz").to_request()).await).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body, json!({"status": "unavailable"}));
+
+    let version: i32 = rusqlite::Connection::open(&empty)
+        .unwrap()
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(version, 0);
+}
+
+#[actix_web::test]
+async fn metrics_count_requests_by_route_pattern() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let token = fixture.session(&fixture.student.email);
+    let admin = fixture.session(&fixture.admin.email);
+
+    macro_rules! scrape {
+        () => {{
+            let res = call_service(
+                &app,
+                TestRequest::get()
+                    .uri("/metrics")
+                    .insert_header(("session_token", admin.as_str()))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                res.headers().get("content-type").unwrap(),
+                "text/plain; version=0.0.4; charset=utf-8"
+            );
+            String::from_utf8(read_body(res).await.to_vec()).unwrap()
+        }};
+    }
+
+    let before = scrape!();
+    let failures = "student_sys_login_failures_total";
+
+    for _ in 0..2 {
+        let path = format!("/courses/{}", fixture.course.id);
+        let res = call_service(&app, request("GET", &path, &token).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    call_service(&app, TestRequest::get().uri("/no/such/page/42").to_request()).await;
+    call_service(
+        &app,
+        TestRequest::post()
+            .uri("/api/v1/login")
+            .insert_header(("login_email", fixture.student.email.as_str()))
+            .insert_header(("login_password", format!("{}x", PASSWORD)))
+            .to_request(),
+    )
+    .await;
+
+    let after = scrape!();
+    let route = r#"method="GET",route="/api/v1/courses/{id}""#;
+
+    assert_eq!(
+        sample(&after, &format!("student_sys_http_requests_total{{{},status=\"200\"}}", route)),
+        Some(2.0)
+    );
+    assert_eq!(
+        sample(&after, &format!("student_sys_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}}", route)),
+        Some(2.0)
+    );
+    assert_eq!(
+        sample(&after, &format!("student_sys_http_request_duration_seconds_count{{{}}}", route)),
+        Some(2.0)
+    );
+    assert!(after.contains(r#"student_sys_http_requests_total{method="GET",route="unmatched","#));
+    assert!(!after.contains("/no/such/page"));
+    assert!(after.contains(r#"student_sys_db_query_duration_seconds_count{operation="#));
+
+    assert_eq!(sample(&after, failures).unwrap() - sample(&before, failures).unwrap(), 1.0);
+    assert!(sample(&after, "student_sys_active_sessions").unwrap() >= 1.0);
+    assert_eq!(sample(&after, "student_sys_enrollments"), Some(0.0));
+    assert!(after.contains("# TYPE student_sys_http_request_duration_seconds histogram"));
+}
+
+#[actix_web::test]
+async fn metrics_are_for_admins_and_the_scraper() {
+    let mut fixture = Fixture::seed();
+    fixture.settings.server.metrics_token = Some(String::from("scraper token 0123456789"));
+    let app = app!(fixture);
+    let student = fixture.session(&fixture.student.email);
+    let admin = fixture.session(&fixture.admin.email);
+
+    let scrape = |header: Option<(&str, &str)>| {
+        let req = TestRequest::get().uri("/metrics");
+        match header {
+            Some(h) => req.insert_header(h),
+            None => req,
+        }
+        .to_request()
+    };
+
+    for (header, expected) in [
+        (None, StatusCode::UNAUTHORIZED),
+        (Some(("authorization", "Bearer not the token")), StatusCode::UNAUTHORIZED),
+        (Some(("session_token", student.as_str())), StatusCode::FORBIDDEN),
+        (Some(("session_token", admin.as_str())), StatusCode::OK),
+        (Some(("authorization", "Bearer scraper token 0123456789")), StatusCode::OK),
+    ] {
+        let res = call_service(&app, scrape(header)).await;
+        assert_eq!(res.status(), expected, "{:?}", header);
+    }
+
+    // Probes still answer anyone
+    for probe in ["/healthz", "/readyz"] {
+        let res = call_service(&app, TestRequest::get().uri(probe).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK, "{}", probe);
+    }
+}
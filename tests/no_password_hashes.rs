@@ -0,0 +1,209 @@
+// Every response body, for every kind of caller, must be free of Argon2 hashes.
+
+use actix_web::http::Method;
+use actix_web::{test, web, App};
+use std::sync::Arc;
+
+use student_sys::backend::auth::RequireSession;
+use student_sys::backend::rest_api::routes;
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::Settings;
+use student_sys::backend::table_models::{Courses, TeacherAccount, User};
+
+const PASSWORD: &str = "Passw0rd!";
+
+fn user(username: &str, email: &str, role: &str) -> User {
+    User {
+        id: 0,
+        username: username.to_owned(),
+        password: PASSWORD.to_owned(),
+        email: email.to_owned(),
+        phone: String::new(),
+        verified: true,
+        suspended: false,
+        forcenewpw: false,
+        role: role.to_owned(),
+    }
+}
+
+fn seed(settings: Arc<Settings>) {
+    let mut conn = ServerConnection::system(settings);
+
+    conn.register_user(user("Admin", "admin@aubg.edu", "admin")).unwrap();
+    conn.register_user(user("Teacher", "teacher@aubg.edu", "teacher")).unwrap();
+    conn.register_user(user("Student", "student@aubg.edu", "student")).unwrap();
+    conn.register_user(user("Classmate", "classmate@aubg.edu", "student")).unwrap();
+    conn.new_department("Computer Science").unwrap();
+
+    let teacher = conn.search_users("teacher@aubg.edu".to_owned()).unwrap()[0].clone();
+    let account = conn
+        .get_teacher_accounts()
+        .unwrap()
+        .into_iter()
+        .find(|a| a.teacher_id == teacher.id)
+        .unwrap();
+    let department = conn.get_departments().unwrap()[0].clone();
+
+    conn.update_teacher_account(TeacherAccount {
+        dept_id: department.id,
+        ..account
+    })
+    .unwrap();
+
+    conn.register_courses(vec![Courses {
+        id: 0,
+        teacher_id: teacher.id,
+        course: String::from("Algorithms"),
+        course_nr: String::from("COS 3100"),
+        description: String::new(),
+        cr_cost: 3,
+        timeslots: String::from("MW 10:00"),
+        capacity: 0,
+    }])
+    .unwrap();
+}
+
+#[actix_web::test]
+async fn responses_never_contain_password_hashes() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut settings = Settings::default();
+    settings.database.path = dir.path().join("test.db");
+    settings.auth.token_secret = b"test secret".to_vec();
+
+    seed(Arc::new(settings.clone()));
+
+    let app = test::init_service(
+        App::new()
+            .wrap(RequireSession)
+            .app_data(web::Data::new(settings))
+            .configure(routes),
+    )
+    .await;
+
+    let mut sessions = vec![None];
+    let mut checked = 0;
+
+    for email in ["admin@aubg.edu", "teacher@aubg.edu", "student@aubg.edu"] {
+        let req = test::TestRequest::post()
+            .uri("/login")
+            .insert_header(("login_email", email))
+            .insert_header(("login_password", PASSWORD))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(res.status().is_success(), "{} could not sign in", email);
+
+        let token = res.headers().get("session_token").unwrap().to_str().unwrap().to_owned();
+        let body = test::read_body(res).await;
+        assert!(!String::from_utf8_lossy(&body).contains("$argon2"), "POST /login leaked a hash");
+        checked += 1;
+
+        sessions.push(Some(token));
+    }
+
+    let endpoints = [
+        (Method::GET, "/users"),
+        (Method::GET, "/students"),
+        (Method::GET, "/teachers"),
+        (Method::GET, "/courses"),
+        (Method::GET, "/courses/1"),
+        (Method::GET, "/departments"),
+        (Method::GET, "/account"),
+        (Method::PATCH, "/admin/users/3"),
+        (Method::POST, "/enroll/1"),
+        (Method::POST, "/unenroll/1"),
+        (Method::GET, "/admin/stats"),
+        (Method::GET, "/admin/export/users?format=jsonl"),
+        (Method::GET, "/admin/export/users?format=csv"),
+    ];
+
+    for session in &sessions {
+        for (method, uri) in &endpoints {
+            let mut req = test::TestRequest::default().method(method.clone()).uri(uri);
+            if let Some(token) = session {
+                req = req.insert_header(("session_token", token.as_str()));
+            }
+
+            let res = test::call_service(&app, req.to_request()).await;
+            let status = res.status();
+            let body = test::read_body(res).await;
+
+            assert!(
+                !String::from_utf8_lossy(&body).contains("$argon2"),
+                "{} {} ({}) leaked a hash: {}",
+                method,
+                uri,
+                status,
+                String::from_utf8_lossy(&body)
+            );
+            checked += 1;
+        }
+    }
+
+    assert_eq!(checked, 3 + sessions.len() * endpoints.len());
+}
+
+#[actix_web::test]
+async fn admins_and_owners_see_more_than_other_users() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut settings = Settings::default();
+    settings.database.path = dir.path().join("test.db");
+    settings.auth.token_secret = b"test secret".to_vec();
+
+    seed(Arc::new(settings.clone()));
+
+    let app = test::init_service(
+        App::new()
+            .wrap(RequireSession)
+            .app_data(web::Data::new(settings))
+            .configure(routes),
+    )
+    .await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/users").to_request()).await;
+    assert_eq!(res.status(), 401);
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert!(res.status().is_success());
+
+    let list_users = |email: &'static str| {
+        let app = &app;
+        async move {
+            let req = test::TestRequest::get()
+                .uri("/users")
+                .insert_header(("login_email", email))
+                .insert_header(("login_password", PASSWORD))
+                .to_request();
+            let users: serde_json::Value = test::call_and_read_body_json(app, req).await;
+            users.as_array().unwrap().clone()
+        }
+    };
+
+    let admin = list_users("admin@aubg.edu").await;
+    assert_eq!(admin.len(), 4);
+    for user in &admin {
+        assert!(user.get("email").is_some());
+        assert!(user.get("suspended").is_some());
+        assert!(user.get("password").is_none());
+    }
+
+    let student = list_users("student@aubg.edu").await;
+    assert_eq!(student.len(), 4);
+    for user in &student {
+        let name = user["username"].as_str().unwrap();
+        match name {
+            "Student" => assert!(user.get("phone").is_some()),
+            "Classmate" => {
+                assert!(user.get("email").is_none());
+                assert!(user.get("phone").is_none());
+            }
+            _ => {
+                assert!(user.get("email").is_some());
+                assert!(user.get("phone").is_none());
+            }
+        }
+        assert!(user.get("password").is_none());
+    }
+
+    let teacher = list_users("teacher@aubg.edu").await;
+    assert!(teacher.iter().all(|u| u.get("email").is_some()));
+}
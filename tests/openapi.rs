@@ -0,0 +1,496 @@
+// Keeps the OpenAPI document honest: every mounted handler is documented, the router serves
+// each documented route, handlers read the headers and query fields documented for them, and
+// every schema matches what its DTO serialises to.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use actix_web::test::{
+    call_and_read_body_json, call_service, init_service, read_body, TestRequest,
+};
+use actix_web::http::Method;
+use actix_web::{web, App, HttpResponse};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+use student_sys::backend::auth::RequireSession;
+use student_sys::backend::backup;
+use student_sys::backend::history::{self, StandingRules};
+use student_sys::backend::import::{ImportKind, ImportReport};
+use student_sys::backend::openapi::{self, In, Route};
+use student_sys::backend::policy::ValidationErrors;
+use student_sys::backend::rest_api::{routes, HANDLERS};
+use student_sys::backend::server_connection_impl::{ServerConnection, Statistics};
+use student_sys::backend::settings::Settings;
+use student_sys::backend::table_models::*;
+use student_sys::backend::views::{
+    AdminUserView, CourseDetail, RosterEntry, TermClosing, UserResponse,
+};
+
+fn source() -> String {
+    std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/backend/rest_api.rs"
+    ))
+    .unwrap()
+}
+
+// From the `{` that opens an item to the `}` that closes it, whatever the formatting
+fn block(source: &str, from: usize) -> &str {
+    let open = from + source[from..].find('{').unwrap();
+    let mut depth = 0;
+    for (i, c) in source[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => continue,
+        }
+        if depth == 0 {
+            return &source[open..=open + i];
+        }
+    }
+    panic!("unbalanced braces after {}", from);
+}
+
+// The parameter list and body of `fn name`
+fn handler<'a>(source: &'a str, name: &str) -> (&'a str, &'a str) {
+    let at = Regex::new(&format!(r"\bfn\s+{}\s*\(", name))
+        .unwrap()
+        .find(source)
+        .unwrap_or_else(|| panic!("no handler {}", name))
+        .end();
+    let body = block(source, at);
+    let signature = &source[at..at + source[at..].find(body).unwrap()];
+    (signature, body)
+}
+
+fn struct_fields(source: &str, name: &str) -> BTreeSet<String> {
+    let at = Regex::new(&format!(r"\bstruct\s+{}\b", name))
+        .unwrap()
+        .find(source)
+        .unwrap()
+        .end();
+
+    Regex::new(r"(?m)^\s*(?:pub\s+)?(\w+)\s*:")
+        .unwrap()
+        .captures_iter(block(source, at))
+        .map(|c| c[1].to_owned())
+        .collect()
+}
+
+// Marks requests the routing table had no handler for
+async fn unrouted() -> HttpResponse {
+    HttpResponse::NotFound().insert_header(("x-unrouted", "1")).finish()
+}
+
+// A request for the route with its path parameters filled in
+fn probe(route: &Route) -> TestRequest {
+    let path = Regex::new(r"\{\w+\}").unwrap().replace_all(route.path, "1");
+    TestRequest::default()
+        .method(Method::from_bytes(route.method.to_uppercase().as_bytes()).unwrap())
+        .uri(&path)
+}
+
+#[test]
+fn every_mounted_route_is_documented() {
+    let mounted: BTreeSet<&str> = HANDLERS.iter().map(|(name, _)| *name).collect();
+    let documented: BTreeSet<&str> = openapi::ROUTES.iter().map(|r| r.handler).collect();
+
+    let undocumented: Vec<_> = mounted.difference(&documented).collect();
+    let stale: Vec<_> = documented.difference(&mounted).collect();
+
+    assert!(
+        undocumented.is_empty(),
+        "Handlers missing from openapi::ROUTES: {:?}",
+        undocumented
+    );
+    assert!(
+        stale.is_empty(),
+        "Documented handlers that aren't mounted: {:?}",
+        stale
+    );
+}
+
+// Each documented method and path is asked of the router, once with only the documented
+// handler mounted and once with all of them, so neither a wrong path nor a route shadowed
+// by another goes unnoticed
+#[actix_web::test]
+async fn the_router_serves_every_documented_route() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut settings = Settings::default();
+    settings.database.path = dir.path().join("test.db");
+    settings.auth.token_secret = b"test secret".to_vec();
+    let settings = web::Data::new(settings);
+
+    let all = init_service(
+        App::new()
+            .app_data(settings.clone())
+            .configure(routes)
+            .default_service(web::to(unrouted)),
+    )
+    .await;
+
+    for route in openapi::ROUTES {
+        let (_, mount) = HANDLERS
+            .iter()
+            .find(|(name, _)| *name == route.handler)
+            .unwrap_or_else(|| panic!("{} isn't mounted", route.handler));
+        let alone = init_service(
+            App::new()
+                .app_data(settings.clone())
+                .configure(*mount)
+                .default_service(web::to(unrouted)),
+        )
+        .await;
+
+        let res = call_service(&alone, probe(route).to_request()).await;
+        assert!(
+            !res.headers().contains_key("x-unrouted"),
+            "{} doesn't serve {} {}",
+            route.handler,
+            route.method,
+            route.path
+        );
+        assert_eq!(
+            res.request().match_pattern().as_deref(),
+            Some(route.path),
+            "path of {}",
+            route.handler
+        );
+
+        let res = call_service(&all, probe(route).to_request()).await;
+        assert!(
+            !res.headers().contains_key("x-unrouted"),
+            "{} {} isn't routed",
+            route.method,
+            route.path
+        );
+    }
+}
+
+#[test]
+fn documented_parameters_match_what_handlers_read() {
+    let source = source();
+    let header = Regex::new(r#"headers\s*\.\s*get\(\s*"([^"]+)"\s*\)"#).unwrap();
+    let query = Regex::new(r"web::Query<\s*(\w+)\s*>").unwrap();
+
+    for route in openapi::ROUTES {
+        let (signature, body) = handler(&source, route.handler);
+
+        let read: BTreeSet<String> = header
+            .captures_iter(body)
+            .map(|c| c[1].to_owned())
+            .collect();
+        let documented: BTreeSet<String> = route
+            .params
+            .iter()
+            .filter(|p| p.location == In::Header)
+            .map(|p| p.name.to_owned())
+            .collect();
+        assert_eq!(
+            read, documented,
+            "headers of {} {}",
+            route.method, route.path
+        );
+
+        // Free-form queries such as the export filters are described in the summary
+        if let Some(name) = query.captures(signature).map(|c| c[1].to_owned()) {
+            let documented: BTreeSet<String> = route
+                .params
+                .iter()
+                .filter(|p| p.location == In::Query)
+                .map(|p| p.name.to_owned())
+                .collect();
+            assert_eq!(
+                struct_fields(&source, &name),
+                documented,
+                "query of {} {}",
+                route.method,
+                route.path
+            );
+        }
+    }
+}
+
+// The properties a schema promises against the keys the DTO really has
+fn assert_schema(name: &str, sample: impl Serialize) {
+    let schemas = openapi::schemas();
+    let schema = &schemas[name];
+    let sample = serde_json::to_value(sample).unwrap();
+
+    let keys: BTreeSet<String> = sample.as_object().unwrap().keys().cloned().collect();
+    let properties: BTreeSet<String> = schema["properties"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect();
+    let required: BTreeSet<String> = schema["required"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_str().unwrap().to_owned())
+        .collect();
+
+    assert!(
+        required.is_subset(&keys),
+        "{} requires fields the DTO doesn't have: {:?}",
+        name,
+        required.difference(&keys).collect::<Vec<_>>()
+    );
+    assert!(
+        keys.is_subset(&properties),
+        "{} is missing properties: {:?}",
+        name,
+        keys.difference(&properties).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn schemas_match_the_dtos() {
+    let user = User {
+        id: 1,
+        username: String::from("Student"),
+        password: String::from("$argon2id$..."),
+        email: String::from("student@aubg.edu"),
+        phone: String::new(),
+        verified: true,
+        suspended: false,
+        forcenewpw: false,
+        role: String::from("student"),
+    };
+    let teacher = User {
+        id: 2,
+        role: String::from("teacher"),
+        ..user.clone()
+    };
+
+    assert_schema("AdminUserView", AdminUserView::from(&user));
+    match UserResponse::for_viewer(Some(&user), &teacher) {
+        UserResponse::Public(view) => assert_schema("UserView", view),
+        UserResponse::Full(_) => panic!("students must not see the full account of a teacher"),
+    }
+
+    let course = Courses {
+        id: 1,
+        teacher_id: 2,
+        course: String::new(),
+        course_nr: String::new(),
+        description: String::new(),
+        cr_cost: 3,
+        timeslots: String::new(),
+        capacity: 0,
+    };
+    let department = Departments {
+        id: 1,
+        name: String::new(),
+    };
+    let teacher_account = TeacherAccount {
+        id: 1,
+        teacher_id: 2,
+        dept_id: 3,
+    };
+    assert_schema("Courses", &course);
+    assert_schema("Departments", &department);
+    assert_schema("TeacherAccount", &teacher_account);
+
+    let history = history::build(
+        1,
+        vec![EnrollmentWithCourse {
+            enrollment: StudentCourse {
+                student_id: 1,
+                course_id: 1,
+                grade: 3.0,
+                semester: String::from("Fall 2026"),
+            },
+            course: course.clone(),
+        }],
+        &[Term {
+            id: 1,
+            name: String::from("Fall 2026"),
+            starts_on: String::from("2026-09-01"),
+            ends_on: String::from("2026-12-20"),
+        }],
+        &StandingRules::default(),
+    );
+    assert_schema("AcademicHistory", &history);
+    assert_schema("TermRecord", &history.terms[0]);
+    assert_schema("CourseRecord", &history.terms[0].courses[0]);
+    assert_schema("Totals", &history.cumulative);
+
+    let roster = RosterEntry {
+        student: UserResponse::for_viewer(Some(&teacher), &user),
+        grade: -1.0,
+    };
+    assert_schema("RosterEntry", &roster);
+    assert_schema(
+        "CourseDetail",
+        CourseDetail {
+            course,
+            user: UserResponse::for_viewer(Some(&user), &teacher),
+            teacher_account,
+            department: Some(department),
+            semester: String::from("Fall 2026"),
+            enrolled: 1,
+            remaining_seats: None,
+            roster: Some(vec![roster]),
+        },
+    );
+    assert_schema(
+        "StudentAccount",
+        StudentAccount {
+            id: 1,
+            student_id: 1,
+            advisor_id: 0,
+            discipline: String::new(),
+            enrollment: String::new(),
+            cgpa: 0.0,
+            can_grad: false,
+            cur_credit: 0,
+            cum_credit: 0,
+        },
+    );
+    assert_schema(
+        "StudentCourse",
+        StudentCourse {
+            student_id: 1,
+            course_id: 1,
+            grade: 0.0,
+            semester: String::new(),
+        },
+    );
+    let decision = StandingRecord {
+        id: 1,
+        student_id: 1,
+        term: String::from("Fall 2026"),
+        standing: String::from("probation"),
+        term_gpa: 1.5,
+        cumulative_gpa: 1.8,
+        decided_at: 0,
+    };
+    assert_schema("StandingRecord", &decision);
+    assert_schema(
+        "TermClosing",
+        TermClosing {
+            term: String::from("Fall 2026"),
+            students: 1,
+            decisions: vec![decision],
+        },
+    );
+    assert_schema(
+        "Notification",
+        Notification {
+            id: 1,
+            user_id: 1,
+            subject: String::new(),
+            body: String::new(),
+            created_at: 0,
+        },
+    );
+    assert_schema(
+        "Hold",
+        Hold {
+            id: 1,
+            student_id: 1,
+            kind: String::from("fees"),
+            reason: String::new(),
+            placed_by: 1,
+            placed_at: 0,
+            blocks_registration: true,
+            blocks_transcripts: false,
+            released_by: 0,
+            released_at: 0,
+        },
+    );
+    assert_schema(
+        "LoginAttempt",
+        LoginAttempt {
+            id: 1,
+            scope: String::new(),
+            key: String::new(),
+            failures: 0,
+            last_failure: 0,
+            locked_until: 0,
+        },
+    );
+
+    assert_schema(
+        "Statistics",
+        Statistics {
+            registered_users: 4,
+            suspended_users: 0,
+            faculty_members: 1,
+            active_students: 2,
+            graduated_students: 0,
+            courses: 1,
+            departments: 1,
+        },
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut settings = Settings::default();
+    settings.database.path = dir.path().join("test.db");
+    let conn = ServerConnection::system(Arc::new(settings.clone()));
+    drop(conn);
+
+    let snapshot = backup::take(&settings.database.path, &settings.backup_dir()).unwrap();
+    assert_schema("Snapshot", &snapshot);
+    assert_schema(
+        "Verification",
+        backup::verify(&settings.backup_dir(), &snapshot.name).unwrap(),
+    );
+
+    let mut errors = ValidationErrors::default();
+    errors.add("email", "required", "Email cannot be empty.");
+    assert_schema("FieldError", &errors.errors[0]);
+
+    let mut report = ImportReport::new(ImportKind::Users, true);
+    report.record(2, errors);
+    assert_schema("ImportReport", &report);
+    assert_schema("RowReport", &report.rows[0]);
+}
+
+#[test]
+fn every_reference_resolves() {
+    let spec = openapi::spec();
+    let schemas = spec["components"]["schemas"].as_object().unwrap();
+    let text = spec.to_string();
+
+    for reference in Regex::new(r"#/components/schemas/(\w+)")
+        .unwrap()
+        .captures_iter(&text)
+    {
+        assert!(
+            schemas.contains_key(&reference[1]),
+            "Unknown schema {}",
+            &reference[1]
+        );
+    }
+}
+
+#[actix_web::test]
+async fn the_document_and_its_viewer_are_served_publicly() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut settings = Settings::default();
+    settings.database.path = dir.path().join("test.db");
+
+    let app = init_service(
+        App::new()
+            .wrap(RequireSession)
+            .app_data(web::Data::new(settings))
+            .configure(routes),
+    )
+    .await;
+
+    let req = TestRequest::get().uri("/openapi.json").to_request();
+    let served: Value = call_and_read_body_json(&app, req).await;
+    assert_eq!(served, openapi::spec());
+    assert_eq!(served["openapi"], "3.0.3");
+
+    let req = TestRequest::get().uri("/docs").to_request();
+    let res = call_service(&app, req).await;
+    assert!(res.status().is_success());
+    let page = read_body(res).await;
+    assert!(String::from_utf8_lossy(&page).contains("/openapi.json"));
+}
@@ -0,0 +1,189 @@
+// Password resets: the token mailed to the user, and what changing the password touches.
+
+#[macro_use]
+mod common;
+
+use std::path::PathBuf;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+
+use common::{reply, request, token_in, Fixture, PASSWORD};
+use student_sys::backend::db_driver::{DbDriver, ReceiverType};
+use student_sys::backend::filter::{Filter, StudentAccountFilter, TeacherAccountFilter};
+use student_sys::backend::settings::MailTransport;
+use student_sys::backend::table_models::{
+    PasswordReset, StudentAccount, StudentCourse, Table, TeacherAccount,
+};
+use student_sys::backend::tokens;
+
+const NEW_PASSWORD: &str = "N3w-passw0rd!";
+
+fn forgot(email: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/v1/password/forgot")
+        .insert_header(("email", email))
+}
+
+fn reset(token: &str, password: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/v1/password/reset")
+        .insert_header(("token", token))
+        .insert_header(("password", password))
+}
+
+fn sign_in(email: &str, password: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/v1/login")
+        .insert_header(("login_email", email))
+        .insert_header(("login_password", password))
+}
+
+fn student_account(db: &DbDriver, student_id: i32) -> StudentAccount {
+    match db
+        .find(
+            Table::StudentAccount,
+            vec![Filter::StudentAccount(StudentAccountFilter::StudentId(student_id))],
+            None,
+        )
+        .unwrap()
+        .pop()
+    {
+        Some(ReceiverType::StudentAccount(a)) => a,
+        _ => panic!("no account for student {}", student_id),
+    }
+}
+
+fn teacher_account(db: &DbDriver, teacher_id: i32) -> TeacherAccount {
+    match db
+        .find(
+            Table::TeacherAccount,
+            vec![Filter::TeacherAccount(TeacherAccountFilter::TeacherId(teacher_id))],
+            None,
+        )
+        .unwrap()
+        .pop()
+    {
+        Some(ReceiverType::TeacherAccount(a)) => a,
+        _ => panic!("no account for teacher {}", teacher_id),
+    }
+}
+
+#[actix_web::test]
+async fn a_password_reset_keeps_the_account_details() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+
+    let account = student_account(&db, fixture.student.id);
+    db.update(vec![ReceiverType::StudentAccount(StudentAccount {
+        advisor_id: fixture.teacher.id,
+        discipline: String::from("Mathematics"),
+        ..account
+    })])
+    .unwrap();
+    db.insert(vec![ReceiverType::StudentCourse(StudentCourse {
+        student_id: fixture.student.id,
+        course_id: fixture.course.id,
+        grade: 3.3,
+        semester: String::from("Fall 2026"),
+    })])
+    .unwrap();
+
+    for user in [&fixture.student, &fixture.teacher] {
+        let (status, _) = reply(call_service(&app, forgot(&user.email).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let token = token_in(fixture.outbox(&user.email).last().unwrap());
+        let (status, _) = reply(call_service(&app, reset(&token, NEW_PASSWORD).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let account = student_account(&db, fixture.student.id);
+    assert_eq!(account.advisor_id, fixture.teacher.id);
+    assert_eq!(account.discipline, "Mathematics");
+    assert!((account.cgpa - 3.3).abs() < 1e-4);
+    assert_eq!(teacher_account(&db, fixture.teacher.id).dept_id, fixture.department.id);
+}
+
+#[actix_web::test]
+async fn a_reset_token_works_once_and_signs_out_everywhere() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let email = fixture.student.email.as_str();
+    let session = fixture.session(email);
+
+    // Unknown addresses get the same answer and no mail
+    let (status, unknown) = reply(call_service(&app, forgot("nobody@aubg.edu").to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(fixture.outbox("nobody@aubg.edu").is_empty());
+
+    let (_, known) = reply(call_service(&app, forgot(email).to_request()).await).await;
+    assert_eq!(known, unknown);
+    call_service(&app, forgot(email).to_request()).await;
+    let mailed = fixture.outbox(email);
+    let (first, second) = (token_in(&mailed[0]), token_in(&mailed[1]));
+
+    let (status, _) = reply(call_service(&app, reset("not-a-token", NEW_PASSWORD).to_request()).await).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A password the policy refuses leaves the token usable
+    let (status, _) = reply(call_service(&app, reset(&first, "short").to_request()).await).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, _) = reply(call_service(&app, reset(&first, NEW_PASSWORD).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Neither the used token nor the one still outstanding works again
+    for token in [&first, &second] {
+        let (status, body) = reply(call_service(&app, reset(token, "An0ther-passw0rd!").to_request()).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Invalid or expired token.");
+    }
+
+    let (status, _) = reply(call_service(&app, request("GET", "/account", &session).to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = reply(call_service(&app, sign_in(email, PASSWORD).to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = reply(call_service(&app, sign_in(email, NEW_PASSWORD).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn an_expired_reset_token_is_refused() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+
+    db.insert(vec![ReceiverType::PasswordReset(PasswordReset {
+        id: 0,
+        user_id: fixture.student.id,
+        token_hash: tokens::digest("stale"),
+        expires_at: chrono::Utc::now().timestamp() - 1,
+        used: false,
+    })])
+    .unwrap();
+
+    let (status, _) = reply(call_service(&app, reset("stale", NEW_PASSWORD).to_request()).await).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = reply(call_service(&app, sign_in(&fixture.student.email, PASSWORD).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn mail_trouble_doesnt_give_accounts_away() {
+    let mut fixture = Fixture::seed();
+    fixture.settings.mail.transport = MailTransport::File {
+        path: PathBuf::from("/nonexistent/outbox.jsonl"),
+    };
+    let app = app!(fixture);
+
+    let (status, unknown) = reply(call_service(&app, forgot("nobody@aubg.edu").to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, known) = reply(call_service(&app, forgot(&fixture.student.email).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(known, unknown);
+}
@@ -0,0 +1,107 @@
+// The registration policy: which addresses, phone numbers and passwords are accepted, and
+// how refusals point at the offending fields.
+
+#[macro_use]
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+
+use common::{reply, Fixture};
+use student_sys::backend::policy::{RegistrationPolicy, ValidationErrors};
+
+// `(field, code)` for every error, in the order they were found
+fn codes(errors: &ValidationErrors) -> Vec<(&str, &str)> {
+    errors
+        .errors
+        .iter()
+        .map(|e| (e.field.as_str(), e.code.as_str()))
+        .collect()
+}
+
+#[actix_web::test]
+async fn every_broken_rule_is_reported_against_its_field() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let (status, body) = reply(
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri("/api/v1/register")
+                .insert_header(("username", "Mallory"))
+                .insert_header(("password", "abc"))
+                .insert_header(("email", "mallory@example.com"))
+                .insert_header(("phone", "12"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+
+    let reported: Vec<(&str, &str)> = body["details"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["field"].as_str().unwrap(), e["code"].as_str().unwrap()))
+        .collect();
+    for expected in [
+        ("email", "domain_not_allowed"),
+        ("phone", "invalid_format"),
+        ("password", "too_short"),
+        ("password", "missing_uppercase"),
+        ("password", "missing_digit"),
+        ("password", "missing_special"),
+    ] {
+        assert!(reported.contains(&expected), "{:?} in {:?}", expected, reported);
+    }
+}
+
+#[test]
+fn operators_choose_the_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let breached = dir.path().join("breached.txt");
+    std::fs::write(&breached, "hunter2\nSummer2024!\n").unwrap();
+
+    let mut policy = RegistrationPolicy {
+        email_domains: vec![String::from("example.org")],
+        ..RegistrationPolicy::default()
+    };
+    policy.phone.required = true;
+    policy.password.require_special = false;
+    policy.password.breached_list = Some(breached);
+    policy.prepare().unwrap();
+
+    let mut errors = ValidationErrors::default();
+    assert_eq!(policy.check_email(" Ada@Example.ORG ", &mut errors), "ada@example.org");
+    assert_eq!(policy.check_phone("0888 123 456", &mut errors), "+359888123456");
+    assert_eq!(policy.check_phone("+1 (415) 555-0100", &mut errors), "+14155550100");
+    policy.check_password("Correct1horse", &mut errors);
+    assert!(errors.is_empty(), "{:?}", codes(&errors));
+
+    policy.check_email("ada@aubg.edu", &mut errors);
+    policy.check_email("not an address", &mut errors);
+    policy.check_phone("", &mut errors);
+    policy.check_password("summer2024!", &mut errors);
+    assert_eq!(
+        codes(&errors),
+        [
+            ("email", "domain_not_allowed"),
+            ("email", "invalid_format"),
+            ("phone", "required"),
+            ("password", "missing_uppercase"),
+            ("password", "breached"),
+        ]
+    );
+
+    // No domains listed accepts any
+    policy.email_domains.clear();
+    let mut errors = ValidationErrors::default();
+    policy.check_email("ada@aubg.edu", &mut errors);
+    assert!(errors.is_empty());
+
+    policy.password.min_length = 200;
+    assert!(policy.prepare().is_err());
+}
@@ -0,0 +1,214 @@
+// Every handler is mounted, under `/api/v1` and (deprecated) at the root.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use actix_web::http::Method;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{web, App};
+use regex::Regex;
+use serde_json::Value;
+
+use student_sys::backend::auth::RequireSession;
+use student_sys::backend::openapi::{self, In, Type};
+use student_sys::backend::rest_api::{routes, HANDLERS};
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::Settings;
+use student_sys::backend::table_models::User;
+use student_sys::backend::versioning::{Deprecated, V1};
+
+const SUNSET: &str = "Thu, 01 Jul 2027 00:00:00 GMT";
+
+fn settings(dir: &tempfile::TempDir) -> Settings {
+    let mut settings = Settings::default();
+    settings.database.path = dir.path().join("test.db");
+    settings.auth.token_secret = b"test secret".to_vec();
+
+    let mut conn = ServerConnection::system(Arc::new(settings.clone()));
+    conn.register_user(User {
+        id: 0,
+        username: String::from("Admin"),
+        password: String::from("Passw0rd!"),
+        email: String::from("admin@aubg.edu"),
+        phone: String::new(),
+        verified: true,
+        suspended: false,
+        forcenewpw: false,
+        role: String::from("admin"),
+    })
+    .unwrap();
+    conn.new_department("Computer Science").unwrap();
+
+    settings
+}
+
+// Built the same way as in main.rs
+macro_rules! app {
+    ($settings:expr) => {
+        init_service(
+            App::new()
+                .wrap(RequireSession)
+                .app_data(web::Data::new($settings))
+                .service(web::scope(V1).configure(routes))
+                .service(
+                    web::scope("")
+                        .wrap(Deprecated::new(SUNSET))
+                        .configure(routes),
+                ),
+        )
+        .await
+    };
+}
+
+macro_rules! sign_in {
+    ($app:expr) => {{
+        let req = TestRequest::post()
+            .uri("/api/v1/login")
+            .insert_header(("login_email", "admin@aubg.edu"))
+            .insert_header(("login_password", "Passw0rd!"))
+            .to_request();
+        let res = call_service(&$app, req).await;
+        assert!(res.status().is_success());
+
+        res.headers()
+            .get("session_token")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned()
+    }};
+}
+
+#[test]
+fn every_handler_is_mounted() {
+    let source =
+        std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/src/backend/rest_api.rs"))
+            .unwrap();
+
+    let handlers: BTreeSet<String> =
+        Regex::new(r#"#\[(?:get|post|patch|delete)\(\s*"[^"]+"\s*\)\]\s*(?:pub\s+)?async\s+fn\s+(\w+)"#)
+            .unwrap()
+            .captures_iter(&source)
+            .map(|c| c[1].to_owned())
+            .collect();
+
+    let mounted: BTreeSet<String> = HANDLERS.iter().map(|(name, _)| name.to_string()).collect();
+
+    let missing: Vec<_> = handlers.difference(&mounted).collect();
+    assert!(missing.is_empty(), "Handlers missing from rest_api::HANDLERS: {:?}", missing);
+}
+
+#[actix_web::test]
+async fn every_route_answers_under_v1() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = app!(settings(&dir));
+    let token = sign_in!(app);
+    let placeholder = Regex::new(r"\{\w+\}").unwrap();
+
+    // Signing out last keeps the session valid for the rest
+    let mut routes: Vec<_> = openapi::ROUTES.iter().collect();
+    routes.sort_by_key(|r| r.path == "/logout");
+
+    for route in routes {
+        let path = placeholder.replace_all(route.path, "999999");
+        let method = Method::from_bytes(route.method.to_uppercase().as_bytes()).unwrap();
+
+        let mut req = TestRequest::default()
+            .method(method)
+            .insert_header(("session_token", token.as_str()));
+        let mut query = Vec::new();
+
+        // Handlers may reject these values, but they must get to see them
+        for param in route.params {
+            let value = match param.kind {
+                Type::Int => "999999",
+                Type::Bool => "false",
+                Type::Str => "x",
+            };
+            match param.location {
+                In::Header => req = req.insert_header((param.name, value)),
+                In::Query => query.push(format!("{}={}", param.name, value)),
+            }
+        }
+
+        let req = req
+            .uri(&format!("{}{}?{}", V1, path, query.join("&")))
+            .to_request();
+        let res = call_service(&app, req).await;
+        let status = res.status();
+        assert!(res.headers().get("deprecation").is_none());
+
+        // A 404 from a handler explains itself; the router's own 404 has no body
+        let body = read_body(res).await;
+        assert!(
+            status != 404 || !body.is_empty(),
+            "{} {}{} is not routed",
+            route.method,
+            V1,
+            route.path
+        );
+    }
+}
+
+#[actix_web::test]
+async fn unversioned_routes_are_deprecated() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = app!(settings(&dir));
+    let token = sign_in!(app);
+
+    let req = TestRequest::get()
+        .uri("/departments")
+        .insert_header(("session_token", token.as_str()))
+        .to_request();
+    let res = call_service(&app, req).await;
+    assert!(res.status().is_success());
+
+    let headers = res.headers();
+    assert_eq!(headers.get("deprecation").unwrap(), "@1792281600");
+    assert_eq!(headers.get("sunset").unwrap(), SUNSET);
+    assert_eq!(
+        headers.get("link").unwrap(),
+        "</api/v1/departments>; rel=\"successor-version\""
+    );
+
+    // Versions that don't exist yet aren't routed
+    let req = TestRequest::get()
+        .uri("/api/v2/departments")
+        .insert_header(("session_token", token.as_str()))
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 404);
+
+    // Public routes stay public under the prefix
+    let req = TestRequest::get().uri("/api/v1/openapi.json").to_request();
+    assert!(call_service(&app, req).await.status().is_success());
+}
+
+#[actix_web::test]
+async fn statistics_and_department_deletion_are_reachable() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = app!(settings(&dir));
+    let token = sign_in!(app);
+
+    let stats = |token: String| {
+        TestRequest::get()
+            .uri("/api/v1/admin/stats")
+            .insert_header(("session_token", token))
+            .to_request()
+    };
+
+    let res = call_service(&app, stats(token.clone())).await;
+    assert!(res.status().is_success());
+    let body: Value = serde_json::from_slice(&read_body(res).await).unwrap();
+    assert_eq!(body["departments"], 1);
+    assert_eq!(body["registered_users"], 1);
+
+    let req = TestRequest::delete()
+        .uri("/api/v1/departments/1")
+        .insert_header(("session_token", token.as_str()))
+        .to_request();
+    assert!(call_service(&app, req).await.status().is_success());
+
+    let res = call_service(&app, stats(token)).await;
+    let body: Value = serde_json::from_slice(&read_body(res).await).unwrap();
+    assert_eq!(body["departments"], 0);
+}
@@ -0,0 +1,139 @@
+// Generated data is reproducible and holds together the way real data would.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use student_sys::backend::error::ApiError;
+use student_sys::backend::export::{ExportFormat, ExportRequest};
+use student_sys::backend::seed::SeedPlan;
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::Settings;
+
+fn plan(seed: u64) -> SeedPlan {
+    SeedPlan {
+        seed,
+        departments: 3,
+        teachers: 7,
+        courses: 30,
+        students: 60,
+        terms: 3,
+        courses_per_term: 4,
+        ..Default::default()
+    }
+}
+
+fn seeded(plan: &SeedPlan) -> ServerConnection {
+    let mut settings = Settings::default();
+    settings.auth.hashing.memory_kib = 8;
+    settings.auth.hashing.iterations = 1;
+    settings.auth.hashing.parallelism = 1;
+
+    let mut conn = ServerConnection::in_memory(&settings).unwrap().into_system();
+    let report = conn.seed(plan).unwrap();
+    assert_eq!(report.students, plan.students);
+    assert_eq!(report.courses, plan.courses);
+
+    conn
+}
+
+fn export(conn: &ServerConnection, table: &str) -> Vec<Value> {
+    let request = ExportRequest::parse(table, ExportFormat::Jsonl, &[]).unwrap();
+    let mut out = Vec::new();
+    conn.export(&request, &mut out).unwrap();
+
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn same_seed_same_data() {
+    let (a, b, c) = (seeded(&plan(7)), seeded(&plan(7)), seeded(&plan(8)));
+
+    for table in ["users", "courses", "students", "enrollments", "terms"] {
+        assert_eq!(export(&a, table), export(&b, table), "{} differ", table);
+    }
+    assert_ne!(export(&a, "enrollments"), export(&c, "enrollments"));
+}
+
+#[test]
+fn seeded_data_is_consistent() {
+    let plan = plan(3);
+    let conn = seeded(&plan);
+
+    let courses: HashMap<i64, Value> = export(&conn, "courses")
+        .into_iter()
+        .map(|c| (c["id"].as_i64().unwrap(), c))
+        .collect();
+    let enrollments = export(&conn, "enrollments");
+    let last_term = export(&conn, "terms").pop().unwrap()["name"].clone();
+
+    // No teacher teaches two courses at once
+    let mut booked = HashSet::new();
+    for course in courses.values() {
+        assert!(booked.insert(format!("{} {}", course["teacher_id"], course["timeslots"])));
+    }
+
+    // Every teacher belongs to a department
+    for teacher in export(&conn, "teachers") {
+        assert_ne!(teacher["dept_id"], 0);
+    }
+
+    // No student takes two courses at once, or a course twice
+    let mut slots = HashSet::new();
+    let mut taken = HashSet::new();
+    for e in &enrollments {
+        let course = &courses[&e["course_id"].as_i64().unwrap()];
+        assert!(slots.insert(format!("{} {} {}", e["student_id"], e["semester"], course["timeslots"])));
+        assert!(taken.insert(format!("{} {}", e["student_id"], e["course_id"])));
+
+        // Only the last term is still in progress
+        assert_eq!(e["grade"].as_f64().unwrap() < 0.0, e["semester"] == last_term);
+    }
+
+    // The triggers kept every student's cgpa in line with their grades
+    for student in export(&conn, "students") {
+        let (mut points, mut credits) = (0.0, 0.0);
+        for e in enrollments.iter().filter(|e| e["student_id"] == student["student_id"]) {
+            let grade = e["grade"].as_f64().unwrap();
+            if grade >= 0.0 {
+                let cr = courses[&e["course_id"].as_i64().unwrap()]["cr_cost"].as_f64().unwrap();
+                points += grade * cr;
+                credits += cr;
+            }
+        }
+
+        let expected = if credits > 0.0 { points / credits } else { 0.0 };
+        assert!((student["cgpa"].as_f64().unwrap() - expected).abs() < 1e-4);
+
+        // Nobody repeats a course, so the history's GPA is the same
+        let history = conn.academic_history(student["student_id"].as_i64().unwrap() as i32).unwrap();
+        assert!(history.terms.len() <= plan.terms);
+        assert!((history.cumulative.gpa.unwrap_or(0.0) as f64 - expected).abs() < 1e-4);
+        assert_ne!(student["advisor_id"], 0);
+        assert_ne!(student["discipline"], "");
+    }
+}
+
+#[test]
+fn only_empty_databases_are_seeded() {
+    let mut conn = seeded(&plan(1));
+
+    let again = conn.seed(&plan(2)).unwrap_err();
+    assert!(matches!(ApiError::from(again), ApiError::Conflict(_)));
+
+    let too_few = conn
+        .seed(&SeedPlan {
+            teachers: 1,
+            ..plan(1)
+        })
+        .unwrap_err();
+    assert!(matches!(ApiError::from(too_few), ApiError::Validation(_)));
+
+    let mut signed_out = ServerConnection::in_memory(&Settings::default()).unwrap();
+    let denied = signed_out.seed(&plan(1)).unwrap_err();
+    assert!(matches!(ApiError::from(denied), ApiError::Forbidden(_)));
+}
@@ -0,0 +1,107 @@
+// Loading the configuration: the file, environment overrides and what gets refused.
+
+use std::sync::Mutex;
+
+use anyhow::Result;
+
+use student_sys::backend::settings::Settings;
+
+// `Settings::load` reads the process environment, so these take turns
+static ENV: Mutex<()> = Mutex::new(());
+
+fn load(config: &str, env: &[(&str, &str)]) -> Result<Settings> {
+    let _turn = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, config).unwrap();
+
+    std::env::set_var("STUDENT_SYS_CONFIG", &path);
+    for (key, value) in env {
+        std::env::set_var(key, value);
+    }
+
+    let settings = Settings::load();
+
+    std::env::remove_var("STUDENT_SYS_CONFIG");
+    for (key, _) in env {
+        std::env::remove_var(key);
+    }
+
+    settings
+}
+
+#[test]
+fn verification_links_need_a_configured_secret() {
+    // Nothing relies on the links: a random secret will do
+    let settings = load("", &[]).unwrap();
+    assert_eq!(settings.auth.token_secret.len(), 32);
+
+    let refused = load("[auth]\nrequire_verification = true\n", &[]).unwrap_err();
+    assert!(refused.to_string().contains("auth.token_secret"));
+
+    let refused = load(
+        "[mail.transport]\nkind = \"file\"\npath = \"outbox.jsonl\"\n",
+        &[],
+    )
+    .unwrap_err();
+    assert!(refused.to_string().contains("auth.token_secret"));
+
+    let settings = load(
+        "[auth]\nrequire_verification = true\n",
+        &[("STUDENT_SYS_TOKEN_SECRET", "from the environment")],
+    )
+    .unwrap();
+    assert_eq!(settings.auth.token_secret, b"from the environment");
+}
+
+#[test]
+fn the_environment_overrides_the_file() {
+    let settings = load(
+        "[server]\nbind = \"0.0.0.0\"\nport = 9000\nworkers = 2\n\n[auth.hashing]\niterations = 4\n",
+        &[
+            ("STUDENT_SYS_PORT", "9100"),
+            ("STUDENT_SYS_CORS_ORIGINS", "https://a.example, https://b.example,"),
+            ("STUDENT_SYS_ARGON2_ITERATIONS", "3"),
+            ("STUDENT_SYS_LOCKOUT_ATTEMPTS", "7"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(settings.server.bind, "0.0.0.0");
+    assert_eq!(settings.server.port, 9100);
+    assert_eq!(settings.server.workers, Some(2));
+    assert_eq!(
+        settings.server.cors_origins,
+        ["https://a.example", "https://b.example"]
+    );
+    assert_eq!(settings.auth.hashing.iterations, 3);
+    assert_eq!(settings.auth.lockout.account_attempts, 7);
+    // Untouched by either
+    assert_eq!(settings.auth.lockout.ip_attempts, 20);
+}
+
+#[test]
+fn every_problem_is_reported_at_startup() {
+    let refused = load(
+        "[server]\nport = 0\nworkers = 0\ncors_origins = [\"ftp://files.example\"]\nmetrics_token = \"short\"\n\n[auth.lockout]\nbase_seconds = 60\nmax_seconds = 30\n",
+        &[],
+    )
+    .unwrap_err()
+    .to_string();
+
+    for problem in [
+        "server.port",
+        "server.workers",
+        "server.cors_origins",
+        "server.metrics_token",
+        "auth.lockout.max_seconds",
+    ] {
+        assert!(refused.contains(problem), "{} in {}", problem, refused);
+    }
+
+    let refused = load("", &[("STUDENT_SYS_PORT", "eighty")]).unwrap_err();
+    assert!(refused.to_string().contains("STUDENT_SYS_PORT"));
+
+    let refused = load("[server]\nprot = 8080\n", &[]).unwrap_err();
+    assert!(refused.to_string().contains("prot"));
+}
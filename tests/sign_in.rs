@@ -0,0 +1,207 @@
+// Signing in: how addresses are matched, and what failed attempts lead to.
+
+#[macro_use]
+mod common;
+
+use std::net::SocketAddr;
+
+use actix_web::http::header::HeaderValue;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+use serde_json::Value;
+
+use common::{reply, request, Fixture, PASSWORD};
+use student_sys::backend::db_driver::{DbDriver, ReceiverType};
+use student_sys::backend::table_models::{LoginAttempt, Table};
+
+fn sign_in(email: &str, password: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/v1/login")
+        .insert_header(("login_email", email))
+        .insert_header(("login_password", password))
+}
+
+// What the lockout table says about `key`, as an admin sees it
+macro_rules! lockout {
+    ($app:expr, $token:expr, $key:expr) => {{
+        let (status, body) = reply(
+            call_service(&$app, request("GET", "/admin/lockouts", $token).to_request()).await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        body.as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["key"] == $key)
+            .cloned()
+            .unwrap_or(Value::Null)
+    }};
+}
+
+// As if the lockouts had run out, without waiting for them
+fn lift_lockouts(db: &mut DbDriver) {
+    let lifted = db
+        .find(Table::LoginAttempts, vec![], None)
+        .unwrap()
+        .into_iter()
+        .filter_map(|x| match x {
+            ReceiverType::LoginAttempt(a) => Some(ReceiverType::LoginAttempt(LoginAttempt {
+                locked_until: 0,
+                ..a
+            })),
+            _ => None,
+        })
+        .collect();
+    db.update(lifted).unwrap();
+}
+
+#[actix_web::test]
+async fn addresses_match_the_way_they_were_registered() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri("/api/v1/register")
+                .insert_header(("username", "Alice"))
+                .insert_header(("password", PASSWORD))
+                .insert_header(("email", "Alice@aubg.edu"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    for typed in ["Alice@aubg.edu", "alice@aubg.edu", " ALICE@aubg.edu "] {
+        let (status, body) = reply(call_service(&app, sign_in(typed, PASSWORD).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK, "{}", typed);
+        assert_eq!(body["email"], "alice@aubg.edu");
+    }
+}
+
+#[actix_web::test]
+async fn credentials_that_are_not_ascii_are_refused_not_fatal() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let umlaut = HeaderValue::from_bytes("Passwört1!".as_bytes()).unwrap();
+
+    let requests = [
+        TestRequest::post().uri("/api/v1/login"),
+        TestRequest::post().uri("/api/v1/verify/resend"),
+        TestRequest::post().uri("/api/v1/account/2fa/enroll"),
+        TestRequest::post().uri("/api/v1/account/2fa/confirm").insert_header(("totp_code", "123456")),
+        TestRequest::get().uri("/api/v1/courses"),
+    ];
+
+    for req in requests {
+        let req = req
+            .insert_header(("login_email", fixture.student.email.as_str()))
+            .insert_header(("login_password", umlaut.clone()))
+            .to_request();
+        let path = req.path().to_owned();
+
+        let (status, _) = reply(call_service(&app, req).await).await;
+        assert!(status == StatusCode::BAD_REQUEST || status == StatusCode::UNAUTHORIZED, "{} {}", path, status);
+    }
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri("/api/v1/register")
+                .insert_header(("username", "Jörg"))
+                .insert_header(("password", umlaut))
+                .insert_header(("email", "jorg@aubg.edu"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn unknown_addresses_and_wrong_passwords_look_the_same() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    let unknown = reply(call_service(&app, sign_in("nobody@aubg.edu", PASSWORD).to_request()).await).await;
+    let wrong = reply(call_service(&app, sign_in(&fixture.student.email, "Wr0ng-password").to_request()).await).await;
+
+    assert_eq!(unknown.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(unknown, wrong);
+}
+
+#[actix_web::test]
+async fn each_failure_past_the_free_attempts_doubles_the_lockout() {
+    let mut fixture = Fixture::seed();
+    fixture.settings.auth.lockout.account_attempts = 3;
+    fixture.settings.auth.lockout.base_seconds = 30;
+    fixture.settings.auth.lockout.max_seconds = 100;
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+    let email = fixture.student.email.as_str();
+
+    for _ in 0..2 {
+        let (status, _) = reply(call_service(&app, sign_in(email, "Wr0ng-password").to_request()).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(lockout!(app, &admin, email)["locked_until"], 0);
+
+    let mut lengths = vec![];
+    for _ in 0..4 {
+        call_service(&app, sign_in(email, "Wr0ng-password").to_request()).await;
+        let attempt = lockout!(app, &admin, email);
+        lengths.push(attempt["locked_until"].as_i64().unwrap() - attempt["last_failure"].as_i64().unwrap());
+
+        // Even the right password is turned away while locked
+        let (status, body) = reply(call_service(&app, sign_in(email, PASSWORD).to_request()).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body["error"].as_str().unwrap().starts_with("Too many failed sign-in attempts."));
+
+        lift_lockouts(&mut db);
+    }
+    assert_eq!(lengths, [30, 60, 100, 100]);
+
+    call_service(&app, sign_in(email, "Wr0ng-password").to_request()).await;
+    let id = lockout!(app, &admin, email)["id"].as_i64().unwrap();
+    let (status, _) = reply(
+        call_service(&app, request("DELETE", &format!("/admin/lockouts/{}", id), &admin).to_request()).await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = reply(call_service(&app, sign_in(email, PASSWORD).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(lockout!(app, &admin, email).is_null());
+}
+
+#[actix_web::test]
+async fn one_address_trying_many_accounts_is_locked_out() {
+    let mut fixture = Fixture::seed();
+    fixture.settings.auth.lockout.ip_attempts = 3;
+    let app = app!(fixture);
+    let guesser: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+    let bystander: SocketAddr = "198.51.100.1:40000".parse().unwrap();
+
+    for email in ["a@aubg.edu", "b@aubg.edu", "c@aubg.edu"] {
+        call_service(&app, sign_in(email, PASSWORD).peer_addr(guesser).to_request()).await;
+    }
+
+    let (status, _) = reply(
+        call_service(&app, sign_in(&fixture.student.email, PASSWORD).peer_addr(guesser).to_request()).await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = reply(
+        call_service(&app, sign_in(&fixture.student.email, PASSWORD).peer_addr(bystander).to_request()).await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
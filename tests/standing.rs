@@ -0,0 +1,206 @@
+// Closing terms: standings recorded from the grades, students notified, and what probation
+// and suspension do to enrolling afterwards.
+
+#[macro_use]
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::test::call_service;
+use chrono::{Duration, Local};
+
+use common::{reply, request, Fixture};
+use student_sys::backend::db_driver::{DbDriver, ReceiverType};
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::table_models::{Courses, StudentCourse, Term};
+
+// A term running from `from` to `to` days from today; returns its id
+fn add_term(fixture: &Fixture, name: &str, from: i64, to: i64) -> i32 {
+    let mut conn = ServerConnection::system(Arc::new(fixture.settings.clone()));
+    let day = |offset| (Local::now().date_naive() + Duration::days(offset)).to_string();
+
+    conn.new_term(Term {
+        id: 0,
+        name: String::from(name),
+        starts_on: day(from),
+        ends_on: day(to),
+    })
+    .unwrap();
+
+    conn.get_terms()
+        .unwrap()
+        .into_iter()
+        .find(|t| t.name == name)
+        .unwrap()
+        .id
+}
+
+fn graded(fixture: &Fixture, course_id: i32, semester: &str, grade: f32) {
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+
+    db.insert(vec![ReceiverType::StudentCourse(StudentCourse {
+        student_id: fixture.student.id,
+        course_id,
+        grade,
+        semester: String::from(semester),
+    })])
+    .unwrap();
+}
+
+fn second_course(fixture: &Fixture) -> Courses {
+    another_course(fixture, "Data Structures", "COS 201")
+}
+
+fn another_course(fixture: &Fixture, course: &str, course_nr: &str) -> Courses {
+    let mut conn = ServerConnection::system(Arc::new(fixture.settings.clone()));
+
+    conn.register_courses(vec![Courses {
+        id: 0,
+        course: String::from(course),
+        course_nr: String::from(course_nr),
+        ..fixture.course.clone()
+    }])
+    .unwrap();
+
+    conn.search_courses(String::new())
+        .unwrap()
+        .into_iter()
+        .find(|c| c.course_nr == course_nr)
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn closing_a_term_puts_weak_students_on_probation_with_fewer_credits() {
+    let mut fixture = Fixture::seed();
+    fixture.settings.standing.probation_credit_cap = 4;
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let student = fixture.session(&fixture.student.email);
+
+    let past = add_term(&fixture, "Past", -200, -100);
+    add_term(&fixture, "Now", -10, 80);
+    let close = format!("/admin/terms/{}/close", past);
+
+    // Grades first
+    graded(&fixture, fixture.course.id, "Past", -1.0);
+    let (status, _) = reply(call_service(&app, request("POST", &close, &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let mut db = DbDriver::open(&fixture.settings.database).unwrap();
+    db.update(vec![ReceiverType::StudentCourse(StudentCourse {
+        student_id: fixture.student.id,
+        course_id: fixture.course.id,
+        grade: 1.0,
+        semester: String::from("Past"),
+    })])
+    .unwrap();
+
+    let (status, _) = reply(call_service(&app, request("POST", &close, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = reply(call_service(&app, request("POST", &close, &admin).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["students"], 1);
+    assert_eq!(body["decisions"][0]["standing"], "probation");
+    assert_eq!(body["decisions"][0]["cumulative_gpa"], 1.0);
+
+    // Nothing changed since
+    let (_, body) = reply(call_service(&app, request("POST", &close, &admin).to_request()).await).await;
+    assert_eq!(body["decisions"], serde_json::json!([]));
+
+    let (_, body) = reply(call_service(&app, request("GET", "/account/notifications", &student).to_request()).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["subject"], "Academic probation");
+
+    let path = format!("/students/{}/standing", fixture.student.id);
+    let (status, body) = reply(call_service(&app, request("GET", &path, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["term"], "Past");
+    let teacher = fixture.session(&fixture.teacher.email);
+    let (status, _) = reply(call_service(&app, request("GET", &path, &teacher).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 3 credits fit under the cap of 4, another 3 don't
+    let enroll = format!("/enroll/{}", fixture.course.id);
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let enroll = format!("/enroll/{}", second_course(&fixture).id);
+    let (status, body) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error"].as_str().unwrap().contains("at most 4 credits"));
+}
+
+#[actix_web::test]
+async fn a_second_bad_term_on_probation_suspends_for_the_next_one() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let student = fixture.session(&fixture.student.email);
+    let second = second_course(&fixture);
+
+    let older = add_term(&fixture, "Older", -300, -210);
+    let past = add_term(&fixture, "Past", -200, -100);
+    add_term(&fixture, "Now", -10, 80);
+    graded(&fixture, fixture.course.id, "Older", 1.0);
+    graded(&fixture, second.id, "Past", 0.5);
+
+    for id in [older, past] {
+        let close = format!("/admin/terms/{}/close", id);
+        let (status, _) = reply(call_service(&app, request("POST", &close, &admin).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let path = format!("/students/{}/standing", fixture.student.id);
+    let (_, body) = reply(call_service(&app, request("GET", &path, &admin).to_request()).await).await;
+    assert_eq!(body[0]["standing"], "probation");
+    assert_eq!(body[1]["standing"], "suspension");
+
+    let (_, body) = reply(call_service(&app, request("GET", "/account/notifications", &student).to_request()).await).await;
+    assert_eq!(body[0]["subject"], "Academic suspension");
+
+    let enroll = format!("/enroll/{}", fixture.course.id);
+    let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn closing_an_older_term_again_goes_by_term_not_by_when() {
+    let mut fixture = Fixture::seed();
+    fixture.settings.standing.probation_credit_cap = 4;
+    let app = app!(fixture);
+    let admin = fixture.session(&fixture.admin.email);
+    let student = fixture.session(&fixture.student.email);
+    let second = second_course(&fixture);
+
+    let fall = add_term(&fixture, "Fall", -300, -210);
+    let spring = add_term(&fixture, "Spring", -200, -100);
+    add_term(&fixture, "Now", -10, 80);
+    graded(&fixture, fixture.course.id, "Fall", 1.0);
+    graded(&fixture, second.id, "Spring", 4.0);
+
+    for id in [fall, spring, fall] {
+        let close = format!("/admin/terms/{}/close", id);
+        let (status, _) = reply(call_service(&app, request("POST", &close, &admin).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let path = format!("/students/{}/standing", fixture.student.id);
+    let (_, body) = reply(call_service(&app, request("GET", &path, &admin).to_request()).await).await;
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0]["term"], "Fall");
+    assert_eq!(body[0]["standing"], "probation");
+    assert_eq!(body[1]["term"], "Spring");
+    assert_eq!(body[1]["standing"], "good");
+
+    // Back in good standing after Spring, so no credit cap
+    for course in [
+        another_course(&fixture, "Algorithms", "COS 301"),
+        another_course(&fixture, "Databases", "COS 302"),
+    ] {
+        let enroll = format!("/enroll/{}", course.id);
+        let (status, _) = reply(call_service(&app, request("POST", &enroll, &student).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
@@ -0,0 +1,487 @@
+// Every storage backend behaves the same through `DbDriver`. The PostgreSQL run needs
+// `--features postgres` and a disposable database in STUDENT_SYS_TEST_POSTGRES_URL; its
+// public schema is dropped first.
+
+use anyhow::anyhow;
+
+use student_sys::backend::db_driver::{DbDriver, ReceiverType};
+use student_sys::backend::error::ApiError;
+use student_sys::backend::filter::*;
+use student_sys::backend::settings::DatabaseSettings;
+use student_sys::backend::storage::SCHEMA_VERSION;
+use student_sys::backend::table_models::*;
+
+#[test]
+fn sqlite_conformance() {
+    let dir = tempfile::tempdir().unwrap();
+    let database = DatabaseSettings {
+        path: dir.path().join("test.db"),
+        ..Default::default()
+    };
+
+    conformance(&mut DbDriver::open(&database).unwrap());
+
+    // Opening an up-to-date database doesn't rewrite the schema
+    let schema_cookie = || {
+        rusqlite::Connection::open(&database.path)
+            .unwrap()
+            .query_row("PRAGMA schema_version", [], |row| row.get::<_, i64>(0))
+            .unwrap()
+    };
+    let before = schema_cookie();
+    DbDriver::open(&database).unwrap();
+    assert_eq!(schema_cookie(), before);
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn postgres_conformance() {
+    let url = match std::env::var("STUDENT_SYS_TEST_POSTGRES_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("STUDENT_SYS_TEST_POSTGRES_URL is not set, skipping");
+            return;
+        }
+    };
+
+    postgres::Client::connect(&url, postgres::NoTls)
+        .unwrap()
+        .batch_execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public;")
+        .unwrap();
+
+    let database = DatabaseSettings {
+        url: Some(url),
+        ..Default::default()
+    };
+
+    conformance(&mut DbDriver::open(&database).unwrap());
+
+    // Opening an up-to-date database leaves the schema alone
+    let db = DbDriver::open(&database).unwrap();
+    assert_eq!(db.count("USERS", &[]).unwrap(), 2);
+
+    // Locked rows wait for the transaction holding them
+    let courses = [Filter::Courses(CoursesFilter::All)];
+    let mut first = DbDriver::open(&database).unwrap();
+    let mut second = DbDriver::open(&database).unwrap();
+    let (done, finished) = std::sync::mpsc::channel();
+    first
+        .transaction(|db| {
+            db.lock("COURSES", &courses)?;
+            let waiting = std::thread::spawn(move || {
+                second.transaction(|db| db.lock("COURSES", &courses)).unwrap();
+                done.send(()).unwrap();
+            });
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            assert!(finished.try_recv().is_err());
+            Ok(waiting)
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    finished.recv().unwrap();
+}
+
+// Relies on a fresh database: the first user gets id 1 and so does their student account
+fn conformance(db: &mut DbDriver) {
+    assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+    db.check_writable().unwrap();
+
+    // Users round trip, and the triggers give each role its account
+    db.insert(vec![
+        user("Ana", "ana@aubg.edu", "student"),
+        user("Ben", "ben@aubg.edu", "teacher"),
+        user("Cleo", "cleo@aubg.edu", "admin"),
+    ])
+    .unwrap();
+
+    let ana = users(db, vec![Filter::Users(UsersFilter::Email(String::from("ana@aubg.edu")))]);
+    assert_eq!(ana.len(), 1);
+    let ana = ana[0].clone();
+    assert_eq!(ana.id, 1);
+    assert_eq!(ana.username, "Ana");
+    assert_eq!(ana.phone, "+359 123");
+    assert!(ana.verified);
+    assert!(!ana.suspended);
+    assert!(!ana.forcenewpw);
+
+    let ben = users(db, vec![Filter::Users(UsersFilter::Username(String::from("Ben")))])[0].clone();
+    let cleo = users(db, vec![Filter::Users(UsersFilter::Role(String::from("admin")))])[0].clone();
+
+    let account = student_account(db, ana.id).unwrap();
+    assert_eq!(account.id, 1);
+    assert_eq!(account.cgpa, 0.0);
+    assert!(!account.can_grad);
+    assert_eq!(teacher_accounts(db).iter().map(|t| t.teacher_id).collect::<Vec<_>>(), [ben.id]);
+    assert!(student_account(db, cleo.id).is_none());
+
+    // And/Or filters
+    let either = db
+        .find(
+            Table::Users,
+            vec![
+                Filter::Users(UsersFilter::Role(String::from("student"))),
+                Filter::Users(UsersFilter::Role(String::from("teacher"))),
+            ],
+            Some(Associativity::Or),
+        )
+        .unwrap();
+    assert_eq!(either.len(), 2);
+
+    let both = users(
+        db,
+        vec![
+            Filter::Users(UsersFilter::Role(String::from("student"))),
+            Filter::Users(UsersFilter::Verified(false)),
+        ],
+    );
+    assert!(both.is_empty());
+    assert_eq!(users(db, vec![]).len(), 3);
+
+    // A unique column reports a conflict
+    let duplicate = db
+        .insert(vec![user("Ana again", "ana@aubg.edu", "student")])
+        .unwrap_err();
+    assert!(matches!(ApiError::from(duplicate), ApiError::Conflict(_)));
+
+    // Departments
+    db.insert(vec![ReceiverType::Department(Departments {
+        id: 0,
+        name: String::from("Physics"),
+    })])
+    .unwrap();
+    let mut physics = departments(db).pop().unwrap();
+    physics.name = String::from("Physics & Astronomy");
+    db.update(vec![ReceiverType::Department(physics.clone())]).unwrap();
+    assert_eq!(departments(db)[0].name, "Physics & Astronomy");
+
+    // Courses and enrollments; the cgpa weighs grades by credits
+    db.insert(vec![course(ben.id, "PHY101", 4), course(ben.id, "PHY102", 2)])
+        .unwrap();
+    let found = db.find(Table::Courses, vec![], None).unwrap();
+    let courses: Vec<Courses> = found
+        .into_iter()
+        .map(|r| match r {
+            ReceiverType::Course(c) => c,
+            other => panic!("expected a course, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(courses.len(), 2);
+    assert_eq!(courses[0].cr_cost, 4);
+    assert_eq!(courses[0].capacity, 30);
+    assert_eq!(courses[0].description, "Mechanics");
+
+    db.insert(vec![
+        enrollment(ana.id, courses[0].id, 3.5),
+        enrollment(ana.id, courses[1].id, 2.0),
+    ])
+    .unwrap();
+    assert_eq!(student_account(db, ana.id).unwrap().cgpa, 3.0);
+
+    // A student takes a course once a term
+    let again = db.insert(vec![enrollment(ana.id, courses[0].id, 3.5)]).unwrap_err();
+    assert!(matches!(ApiError::from(again), ApiError::Conflict(_)));
+
+    db.update(vec![enrollment(ana.id, courses[1].id, 4.0)]).unwrap();
+    assert!((student_account(db, ana.id).unwrap().cgpa - 22.0 / 6.0).abs() < 1e-5);
+
+    let enrolled = db
+        .find(
+            Table::StudentCourses,
+            vec![Filter::StudentCourses(StudentCoursesFilter::CourseId(courses[1].id))],
+            None,
+        )
+        .unwrap();
+    assert!(matches!(&enrolled[..], [ReceiverType::StudentCourse(s)] if s.grade == 4.0));
+
+    db.delete(vec![enrollment(ana.id, courses[1].id, 4.0)]).unwrap();
+    assert_eq!(student_account(db, ana.id).unwrap().cgpa, 3.5);
+
+    // Joined reads keep every table's columns apart, ids included
+    let joined = db
+        .enrollments_with_courses(vec![Filter::StudentCourses(StudentCoursesFilter::StudentId(ana.id))])
+        .unwrap();
+    assert_eq!(joined.len(), 1);
+    assert_eq!(joined[0].course.id, courses[0].id);
+    assert_eq!(joined[0].course.course_nr, "PHY101");
+    assert_eq!(joined[0].enrollment.grade, 3.5);
+    assert_eq!(joined[0].enrollment.semester, "Fall 2026");
+
+    let taught = db
+        .courses_with_teachers(vec![Filter::Courses(CoursesFilter::Id(courses[1].id))])
+        .unwrap();
+    assert_eq!(taught.len(), 1);
+    assert_eq!(taught[0].course.course_nr, "PHY102");
+    assert_eq!(taught[0].teacher.id, ben.id);
+    assert_eq!(taught[0].teacher.username, "Ben");
+    assert!(taught[0].department.is_none());
+
+    let mut account = teacher_accounts(db).pop().unwrap();
+    account.dept_id = physics.id;
+    db.update(vec![ReceiverType::TeacherAccount(account.clone())]).unwrap();
+
+    let taught = db.courses_with_teachers(vec![]).unwrap();
+    assert_eq!(taught.len(), 2);
+    assert!(taught.iter().all(|c| c.teacher_account.id == account.id));
+    assert!(taught.iter().all(|c| c.department.as_ref().unwrap().name == "Physics & Astronomy"));
+
+    // Timestamps beyond 2038 survive
+    db.insert(vec![ReceiverType::Session(Session {
+        id: 0,
+        user_id: ana.id,
+        token_hash: String::from("hash"),
+        created_at: 4_000_000_000,
+        expires_at: 5_000_000_000,
+    })])
+    .unwrap();
+    let sessions = db.find(Table::Sessions, vec![], None).unwrap();
+    assert!(matches!(&sessions[..], [ReceiverType::Session(s)] if s.expires_at == 5_000_000_000));
+    assert_eq!(
+        db.count("SESSIONS", &[Filter::Sessions(SessionsFilter::ExpiresAfter(4_500_000_000))])
+            .unwrap(),
+        1
+    );
+
+    // Settings are upserted
+    for value in ["1", "2"] {
+        db.insert(vec![ReceiverType::SystemSetting(SystemSetting {
+            key: String::from("max_credits"),
+            value: String::from(value),
+        })])
+        .unwrap();
+    }
+    let settings = db.find(Table::SystemSettings, vec![], None).unwrap();
+    assert!(matches!(&settings[..], [ReceiverType::SystemSetting(s)] if s.value == "2"));
+
+    // Transactions
+    let failed: anyhow::Result<()> = db.transaction(|db| {
+        db.insert(vec![ReceiverType::Department(Departments {
+            id: 0,
+            name: String::from("Rolled back"),
+        })])?;
+        Err(anyhow!("stop"))
+    });
+    assert!(failed.is_err());
+    assert_eq!(departments(db).len(), 1);
+
+    db.transaction(|db| {
+        db.insert(vec![ReceiverType::Department(Departments {
+            id: 0,
+            name: String::from("Chemistry"),
+        })])
+    })
+    .unwrap();
+    assert_eq!(departments(db).len(), 2);
+
+    // Other user updates leave the account alone
+    let advised = StudentAccount {
+        advisor_id: ben.id,
+        ..student_account(db, ana.id).unwrap()
+    };
+    db.update(vec![ReceiverType::StudentAccount(advised.clone())]).unwrap();
+    db.update(vec![ReceiverType::User(User {
+        phone: String::from("+359 456"),
+        ..ana.clone()
+    })])
+    .unwrap();
+    let account = student_account(db, ana.id).unwrap();
+    assert_eq!((account.id, account.advisor_id, account.cgpa), (advised.id, ben.id, advised.cgpa));
+
+    // A role change moves the account
+    let mut ben_student = ben.clone();
+    ben_student.role = String::from("student");
+    db.update(vec![ReceiverType::User(ben_student)]).unwrap();
+    assert!(teacher_accounts(db).is_empty());
+    assert!(student_account(db, ben.id).is_some());
+
+    // Enrollments and taught courses keep a user from being deleted, and enrollments keep
+    // a course; sessions, credentials and accounts go with the user
+    let enrolled = db.delete(vec![ReceiverType::User(ana.clone())]).unwrap_err();
+    assert!(matches!(ApiError::from(enrolled), ApiError::Conflict(_)));
+    let teaching = db.delete(vec![ReceiverType::User(ben.clone())]).unwrap_err();
+    assert!(matches!(ApiError::from(teaching), ApiError::Conflict(_)));
+    assert_eq!(users(db, vec![]).len(), 3);
+    let taken = db.delete(vec![ReceiverType::Course(courses[0].clone())]).unwrap_err();
+    assert!(matches!(ApiError::from(taken), ApiError::Conflict(_)));
+    assert_eq!(db.count("COURSES", &[]).unwrap(), 2);
+
+    db.insert(vec![
+        ReceiverType::Standing(StandingRecord {
+            id: 0,
+            student_id: ana.id,
+            term: String::from("Fall 2026"),
+            standing: String::from("deans_list"),
+            term_gpa: 3.75,
+            cumulative_gpa: 3.5,
+            decided_at: 1_700_000_000,
+        }),
+        ReceiverType::Notification(Notification {
+            id: 0,
+            user_id: ana.id,
+            subject: String::from("Dean's list for Fall 2026"),
+            body: String::from("Congratulations!"),
+            created_at: 1_700_000_000,
+        }),
+        ReceiverType::Hold(Hold {
+            id: 0,
+            student_id: ana.id,
+            kind: String::from("fees"),
+            reason: String::from("Tuition isn't paid."),
+            placed_by: ben.id,
+            placed_at: 1_700_000_000,
+            blocks_registration: true,
+            blocks_transcripts: false,
+            released_by: 0,
+            released_at: 0,
+        }),
+        ReceiverType::TwoFactor(TwoFactor {
+            id: 0,
+            user_id: ana.id,
+            secret: String::from("GEZDGNBVGY3TQOJQ"),
+            enabled: true,
+            last_step: 0,
+        }),
+        ReceiverType::RecoveryCode(RecoveryCode {
+            id: 0,
+            user_id: ana.id,
+            code_hash: String::from("hash"),
+            used: false,
+        }),
+    ])
+    .unwrap();
+    let holds = db
+        .find(
+            Table::Holds,
+            vec![
+                Filter::Holds(HoldsFilter::StudentId(ana.id)),
+                Filter::Holds(HoldsFilter::Active),
+            ],
+            None,
+        )
+        .unwrap();
+    assert!(matches!(
+        &holds[..],
+        [ReceiverType::Hold(h)] if h.reason == "Tuition isn't paid." && h.blocks_registration && !h.blocks_transcripts
+    ));
+    let standings = db
+        .find(
+            Table::StandingHistory,
+            vec![Filter::StandingHistory(StandingHistoryFilter::StudentId(ana.id))],
+            None,
+        )
+        .unwrap();
+    assert!(matches!(&standings[..], [ReceiverType::Standing(s)] if s.term_gpa == 3.75));
+
+    db.delete(vec![enrollment(ana.id, courses[0].id, 3.5)]).unwrap();
+    db.delete(vec![ReceiverType::User(ana.clone())]).unwrap();
+    assert!(student_account(db, ana.id).is_none());
+    assert_eq!(db.count("SESSIONS", &[]).unwrap(), 0);
+    assert_eq!(db.count("TWO_FACTOR", &[]).unwrap(), 0);
+    assert_eq!(db.count("RECOVERY_CODES", &[]).unwrap(), 0);
+    assert_eq!(db.count("STANDING_HISTORY", &[]).unwrap(), 0);
+    assert_eq!(db.count("NOTIFICATIONS", &[]).unwrap(), 0);
+    assert_eq!(db.count("HOLDS", &[]).unwrap(), 0);
+
+    // Streaming reads
+    let columns = db.columns("USERS").unwrap();
+    assert_eq!(
+        columns,
+        ["id", "username", "password", "email", "phone", "verified", "suspended", "forcenewpw", "role"]
+    );
+
+    let mut seen = Vec::new();
+    let visited = db
+        .for_each_row("USERS", &[Filter::Users(UsersFilter::All)], |columns, values| {
+            assert_eq!(columns.len(), values.len());
+            seen.push(values[1].as_str().unwrap().to_owned());
+            Ok(())
+        })
+        .unwrap();
+    seen.sort();
+    assert_eq!(visited, 2);
+    assert_eq!(seen, ["Ben", "Cleo"]);
+}
+
+fn user(username: &str, email: &str, role: &str) -> ReceiverType {
+    ReceiverType::User(User {
+        id: 0,
+        username: String::from(username),
+        password: String::from("$argon2id$not-a-real-hash"),
+        email: String::from(email),
+        phone: String::from("+359 123"),
+        verified: true,
+        suspended: false,
+        forcenewpw: false,
+        role: String::from(role),
+    })
+}
+
+fn course(teacher_id: i32, course_nr: &str, cr_cost: i32) -> ReceiverType {
+    ReceiverType::Course(Courses {
+        id: 0,
+        teacher_id,
+        course: String::from("Physics"),
+        course_nr: String::from(course_nr),
+        description: String::from("Mechanics"),
+        cr_cost,
+        timeslots: String::from("MWF 10:00"),
+        capacity: 30,
+    })
+}
+
+fn enrollment(student_id: i32, course_id: i32, grade: f32) -> ReceiverType {
+    ReceiverType::StudentCourse(StudentCourse {
+        student_id,
+        course_id,
+        grade,
+        semester: String::from("Fall 2026"),
+    })
+}
+
+fn users(db: &DbDriver, filters: Vec<Filter>) -> Vec<User> {
+    db.find(Table::Users, filters, None)
+        .unwrap()
+        .into_iter()
+        .map(|r| match r {
+            ReceiverType::User(u) => u,
+            other => panic!("expected a user, got {:?}", other),
+        })
+        .collect()
+}
+
+fn student_account(db: &DbDriver, student_id: i32) -> Option<StudentAccount> {
+    db.find(
+        Table::StudentAccount,
+        vec![Filter::StudentAccount(StudentAccountFilter::StudentId(student_id))],
+        None,
+    )
+    .unwrap()
+    .into_iter()
+    .map(|r| match r {
+        ReceiverType::StudentAccount(s) => s,
+        other => panic!("expected a student account, got {:?}", other),
+    })
+    .next()
+}
+
+fn teacher_accounts(db: &DbDriver) -> Vec<TeacherAccount> {
+    db.find(Table::TeacherAccount, vec![], None)
+        .unwrap()
+        .into_iter()
+        .map(|r| match r {
+            ReceiverType::TeacherAccount(t) => t,
+            other => panic!("expected a teacher account, got {:?}", other),
+        })
+        .collect()
+}
+
+fn departments(db: &DbDriver) -> Vec<Departments> {
+    db.find(Table::Departments, vec![], None)
+        .unwrap()
+        .into_iter()
+        .map(|r| match r {
+            ReceiverType::Department(d) => d,
+            other => panic!("expected a department, got {:?}", other),
+        })
+        .collect()
+}
@@ -0,0 +1,115 @@
+// The administration binary, run the way operators run it: against the configured database,
+// with human or JSON output and a failing exit status on errors.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+fn studentctl(config: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_studentctl"))
+        .args(args)
+        .env("STUDENT_SYS_CONFIG", config)
+        .output()
+        .unwrap()
+}
+
+fn json(config: &Path, args: &[&str]) -> Value {
+    let output = studentctl(config, &[&["--json"], args].concat());
+    assert!(
+        output.status.success(),
+        "{:?}: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn setup(dir: &Path) -> PathBuf {
+    let config = dir.join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[database]\npath = \"{}\"\n\n[auth.hashing]\nmemory_kib = 8\niterations = 1\nparallelism = 1\n",
+            dir.join("system.db").display()
+        ),
+    )
+    .unwrap();
+
+    config
+}
+
+#[test]
+fn operators_manage_users_and_terms() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = setup(dir.path());
+
+    let output = studentctl(
+        &config,
+        &[
+            "users", "create", "--username", "Tess", "--email", "Tess@aubg.edu", "--password",
+            "Passw0rd!", "--role", "teacher",
+        ],
+    );
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let users = json(&config, &["users", "list", "--role", "teacher"]);
+    let tess = &users.as_array().unwrap()[0];
+    assert_eq!(tess["email"], "tess@aubg.edu");
+    assert!(tess.get("password").is_none());
+    let id = tess["id"].to_string();
+
+    json(&config, &["users", "set-role", &id, "admin"]);
+    json(&config, &["users", "suspend", &id]);
+    let users = json(&config, &["users", "list"]);
+    assert_eq!(users[0]["role"], "admin");
+    assert_eq!(users[0]["suspended"], true);
+
+    json(&config, &["terms", "create", "Fall 2026", "--starts", "2026-09-01", "--ends", "2026-12-20"]);
+    let terms = json(&config, &["terms", "list"]);
+    assert_eq!(terms[0]["name"], "Fall 2026");
+
+    // The table header, then the row
+    let output = studentctl(&config, &["terms", "list"]);
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(table.lines().next().unwrap().starts_with("ID"), "{}", table);
+    assert!(table.contains("Fall 2026"));
+}
+
+#[test]
+fn refusals_exit_with_failure_and_name_the_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = setup(dir.path());
+
+    let output = studentctl(
+        &config,
+        &[
+            "--json", "users", "create", "--username", "Mallory", "--email", "mallory@example.com",
+            "--password", "abc",
+        ],
+    );
+    assert!(!output.status.success());
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    let fields: Vec<&str> = error["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["field"].as_str().unwrap())
+        .collect();
+    assert!(fields.contains(&"email") && fields.contains(&"password"), "{}", error);
+
+    let output = studentctl(
+        &config,
+        &[
+            "users", "create", "--username", "Mallory", "--email", "mallory@example.com",
+            "--password", "abc",
+        ],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: validation failed"));
+    assert!(stderr.contains("  email: "));
+
+    assert!(json(&config, &["users", "list"]).as_array().unwrap().is_empty());
+}
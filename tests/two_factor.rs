@@ -0,0 +1,100 @@
+// Two-factor sign-in: the codes authenticator apps compute, and codes that only work once.
+
+#[macro_use]
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+
+use common::{reply, Fixture, PASSWORD};
+use student_sys::backend::totp;
+
+fn sign_in(email: &str, code: Option<&str>) -> TestRequest {
+    let req = TestRequest::post()
+        .uri("/api/v1/login")
+        .insert_header(("login_email", email))
+        .insert_header(("login_password", PASSWORD));
+
+    match code {
+        Some(code) => req.insert_header(("totp_code", code)),
+        None => req,
+    }
+}
+
+#[test]
+fn codes_match_the_rfc_6238_vectors() {
+    // The RFC's SHA-1 key, "12345678901234567890", in base32
+    let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    // Appendix B, cut to the six digits apps show
+    for (time, code) in [
+        (59, "287082"),
+        (1111111109, "081804"),
+        (1111111111, "050471"),
+        (1234567890, "005924"),
+        (2000000000, "279037"),
+        (20000000000, "353130"),
+    ] {
+        assert_eq!(totp::code_at(secret, time / 30).unwrap(), code, "at {}", time);
+    }
+}
+
+#[actix_web::test]
+async fn a_code_signs_in_once() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let email = fixture.student.email.as_str();
+
+    let (status, body) = reply(
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri("/api/v1/account/2fa/enroll")
+                .insert_header(("login_email", email))
+                .insert_header(("login_password", PASSWORD))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let secret = body["secret"].as_str().unwrap().to_owned();
+    assert!(body["otpauth_uri"].as_str().unwrap().contains(&secret));
+
+    let now = totp::current_step();
+    let confirming = totp::code_at(&secret, now).unwrap();
+    let (status, body) = reply(
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri("/api/v1/account/2fa/confirm")
+                .insert_header(("login_email", email))
+                .insert_header(("login_password", PASSWORD))
+                .insert_header(("totp_code", confirming.as_str()))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let recovery = body["recovery_codes"][0].as_str().unwrap().to_owned();
+
+    let (status, _) = reply(call_service(&app, sign_in(email, None).to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The code that confirmed enrollment has been used
+    let (status, _) = reply(call_service(&app, sign_in(email, Some(&confirming)).to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The next one is within the allowed drift, but only the first time
+    let next = totp::code_at(&secret, now + 1).unwrap();
+    let (status, _) = reply(call_service(&app, sign_in(email, Some(&next)).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = reply(call_service(&app, sign_in(email, Some(&next)).to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = reply(call_service(&app, sign_in(email, Some(&recovery)).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = reply(call_service(&app, sign_in(email, Some(&recovery)).to_request()).await).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,147 @@
+// Email verification: signed links, what they accept, changed addresses and signing up while
+// mail is down.
+
+#[macro_use]
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use common::{reply, request, token_in, Fixture, PASSWORD};
+use student_sys::backend::filter::{Filter, UsersFilter};
+use student_sys::backend::server_connection_impl::ServerConnection;
+use student_sys::backend::settings::MailTransport;
+use student_sys::backend::table_models::User;
+use student_sys::backend::tokens::{self, VERIFY_EMAIL};
+
+fn sign_up(username: &str, email: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/v1/register")
+        .insert_header(("username", username))
+        .insert_header(("password", PASSWORD))
+        .insert_header(("email", email))
+}
+
+fn follow(token: &str) -> TestRequest {
+    TestRequest::get().uri(&format!("/api/v1/verify?token={}", token))
+}
+
+fn account(fixture: &Fixture, email: &str) -> User {
+    ServerConnection::system(Arc::new(fixture.settings.clone()))
+        .get_users_by_filters(vec![Filter::Users(UsersFilter::Email(email.to_owned()))])
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn links_confirm_only_the_account_they_were_issued_for() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+
+    for (username, email) in [("Nina", "nina@aubg.edu"), ("Omar", "omar@aubg.edu")] {
+        let (status, _) = reply(call_service(&app, sign_up(username, email).to_request()).await).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let nina = account(&fixture, "nina@aubg.edu");
+    let omar = account(&fixture, "omar@aubg.edu");
+    let link = token_in(&fixture.outbox("nina@aubg.edu")[0]);
+
+    let secret = &fixture.settings.auth.token_secret;
+    let subject = format!("{}:{}", omar.id, omar.email);
+    let tomorrow = chrono::Utc::now().timestamp() + 24 * 60 * 60;
+
+    // Nina's signature on a payload naming Omar
+    let (_, signature) = link.split_once('.').unwrap();
+    let tampered = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(format!("{}|{}|{}", VERIFY_EMAIL, tomorrow, subject)),
+        signature
+    );
+    let forged = [
+        tampered,
+        tokens::sign(b"another secret", VERIFY_EMAIL, &subject, tomorrow),
+        tokens::sign(secret, "reset-password", &subject, tomorrow),
+        tokens::sign(secret, VERIFY_EMAIL, &subject, chrono::Utc::now().timestamp() - 1),
+        format!("{}.", link.split_once('.').unwrap().0),
+    ];
+
+    for token in forged {
+        let (status, body) = reply(call_service(&app, follow(&token).to_request()).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", token);
+        assert_eq!(body["error"], "Invalid or expired token.");
+    }
+    assert!(!account(&fixture, "omar@aubg.edu").verified);
+
+    let (status, _) = reply(call_service(&app, follow(&link).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(account(&fixture, "nina@aubg.edu").verified);
+    assert!(!account(&fixture, "omar@aubg.edu").verified);
+
+    // Once the address changes, links sent to the old one stop working
+    let moved = tokens::sign(secret, VERIFY_EMAIL, &format!("{}:old@aubg.edu", nina.id), tomorrow);
+    let (status, _) = reply(call_service(&app, follow(&moved).to_request()).await).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn signing_up_works_while_mail_is_down() {
+    let mut fixture = Fixture::seed();
+    fixture.settings.mail.transport = MailTransport::File {
+        path: PathBuf::from("/nonexistent/outbox.jsonl"),
+    };
+    let app = app!(fixture);
+
+    let (status, _) = reply(call_service(&app, sign_up("Nina", "nina@aubg.edu").to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The account is there, waiting for a link from /verify/resend
+    let (status, _) = reply(call_service(&app, sign_up("Nina", "nina@aubg.edu").to_request()).await).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_web::test]
+async fn a_new_address_has_to_be_confirmed_again() {
+    let fixture = Fixture::seed();
+    let app = app!(fixture);
+    let token = fixture.session(&fixture.student.email);
+    assert!(account(&fixture, &fixture.student.email).verified);
+
+    // The same address typed differently isn't a change
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("PATCH", "/account", &token)
+                .insert_header(("email", fixture.student.email.to_uppercase()))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(account(&fixture, &fixture.student.email).verified);
+
+    let (status, _) = reply(
+        call_service(
+            &app,
+            request("PATCH", "/account", &token)
+                .insert_header(("email", "moved@aubg.edu"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!account(&fixture, "moved@aubg.edu").verified);
+
+    let mail = fixture.outbox("moved@aubg.edu");
+    assert_eq!(mail.len(), 1);
+    let (status, _) = reply(call_service(&app, follow(&token_in(&mail[0])).to_request()).await).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(account(&fixture, "moved@aubg.edu").verified);
+}